@@ -0,0 +1,15 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        // Use a bundled protoc rather than requiring one to be installed.
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("no bundled protoc");
+        std::env::set_var("PROTOC", protoc);
+        // The generated client needs the 2021 prelude, and only the server
+        // is served.
+        tonic_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/fzy.proto"], &["proto"])
+            .expect("failed to compile proto/fzy.proto");
+    }
+}
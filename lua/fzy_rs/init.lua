@@ -0,0 +1,48 @@
+-- LuaJIT FFI bindings to the fzy-rs C ABI. Build the library first with
+-- `cargo build --release`.
+local ffi = require('ffi')
+
+ffi.cdef([[
+bool fzy_has_match(const char *pat, size_t pat_len, const char *text, size_t text_len);
+double fzy_score(const char *pat, size_t pat_len, const char *text, size_t text_len);
+ptrdiff_t fzy_positions(const char *pat, size_t pat_len, const char *text, size_t text_len, size_t *out);
+]])
+
+local function library_path()
+  local source = debug.getinfo(1, 'S').source:sub(2)
+  local root = source:match('(.*)/lua/fzy_rs/init%.lua$') or '.'
+  local ext = ffi.os == 'OSX' and 'dylib' or ffi.os == 'Windows' and 'dll' or 'so'
+  local prefix = ffi.os == 'Windows' and '' or 'lib'
+  return root .. '/target/release/' .. prefix .. 'fzy_rs.' .. ext
+end
+
+local lib = ffi.load(library_path())
+
+local M = {}
+
+M.SCORE_MIN = -math.huge
+M.SCORE_MAX = math.huge
+
+function M.has_match(pat, text)
+  return lib.fzy_has_match(pat, #pat, text, #text)
+end
+
+function M.score(pat, text)
+  return lib.fzy_score(pat, #pat, text, #text)
+end
+
+-- Returns the 1-based byte positions of the match, or nil.
+function M.positions(pat, text)
+  local out = ffi.new('size_t[?]', #pat)
+  local n = tonumber(lib.fzy_positions(pat, #pat, text, #text, out))
+  if n < 0 then
+    return nil
+  end
+  local positions = {}
+  for i = 0, n - 1 do
+    positions[i + 1] = tonumber(out[i]) + 1
+  end
+  return positions
+end
+
+return M
@@ -0,0 +1,44 @@
+-- A telescope.nvim sorter backed by fzy-rs:
+--
+--   require('telescope').setup({
+--     defaults = { file_sorter = require('fzy_rs.telescope').sorter },
+--   })
+local fzy = require('fzy_rs')
+local sorters = require('telescope.sorters')
+
+local M = {}
+
+-- Telescope ranks lower scores first and drops entries scored -1, while fzy
+-- scores grow with match quality and are bounded below by the longest gaps
+-- over a candidate, so scores are shifted positive and inverted.
+local OFFSET = 1024 * 0.01
+
+local function rank(score)
+  if score == fzy.SCORE_MAX then
+    return 0
+  end
+  return 1 / math.max(score + OFFSET, 1e-9)
+end
+
+function M.sorter(opts)
+  opts = opts or {}
+  return sorters.Sorter:new({
+    discard = true,
+
+    scoring_function = function(_, prompt, line)
+      if prompt == '' then
+        return 1
+      end
+      if not fzy.has_match(prompt, line) then
+        return -1
+      end
+      return rank(fzy.score(prompt, line))
+    end,
+
+    highlighter = function(_, prompt, display)
+      return fzy.positions(prompt, display) or {}
+    end,
+  })
+end
+
+return M
@@ -0,0 +1,276 @@
+use crate::{
+    compat, completion, flx, fzf, has_match, positions, score, sublime, Match, QuerySession, Score,
+    SCORE_MIN,
+};
+
+/// A matching and scoring scheme. The ranking layers ([`RankedSet`],
+/// [`filter_reader`], [`Picker`]) are generic over it so other crates can plug
+/// in their own.
+///
+/// [`QuerySession`] is not: it only implements fzy, and a picker only ranks
+/// through one for an algorithm whose [`session`](FuzzyAlgorithm::session)
+/// ranks just like it.
+///
+/// [`RankedSet`]: crate::RankedSet
+/// [`filter_reader`]: crate::filter_reader
+/// [`Picker`]: crate::picker::Picker
+pub trait FuzzyAlgorithm {
+    fn has_match(&self, pat: &[u8], text: &[u8]) -> bool;
+
+    /// Scores a candidate; higher is better and scores are only comparable
+    /// within one algorithm.
+    fn score(&self, pat: &[u8], text: &[u8]) -> Score;
+
+    /// Indices into `text` matched by `pat`, or `None` if it doesn't match.
+    fn positions(&self, pat: &[u8], text: &[u8]) -> Option<Vec<usize>>;
+
+    /// A [`QuerySession`] ranking exactly like this algorithm, which the
+    /// [`Picker`] then ranks with instead. Sessions only implement fzy, so
+    /// other algorithms have none.
+    ///
+    /// [`Picker`]: crate::picker::Picker
+    fn session(&self) -> Option<QuerySession> {
+        None
+    }
+}
+
+impl<A: FuzzyAlgorithm + ?Sized> FuzzyAlgorithm for &A {
+    fn has_match(&self, pat: &[u8], text: &[u8]) -> bool {
+        (**self).has_match(pat, text)
+    }
+
+    fn score(&self, pat: &[u8], text: &[u8]) -> Score {
+        (**self).score(pat, text)
+    }
+
+    fn positions(&self, pat: &[u8], text: &[u8]) -> Option<Vec<usize>> {
+        (**self).positions(pat, text)
+    }
+
+    fn session(&self) -> Option<QuerySession> {
+        (**self).session()
+    }
+}
+
+impl<A: FuzzyAlgorithm + ?Sized> FuzzyAlgorithm for Box<A> {
+    fn has_match(&self, pat: &[u8], text: &[u8]) -> bool {
+        (**self).has_match(pat, text)
+    }
+
+    fn score(&self, pat: &[u8], text: &[u8]) -> Score {
+        (**self).score(pat, text)
+    }
+
+    fn positions(&self, pat: &[u8], text: &[u8]) -> Option<Vec<usize>> {
+        (**self).positions(pat, text)
+    }
+
+    fn session(&self) -> Option<QuerySession> {
+        (**self).session()
+    }
+}
+
+/// The built-in algorithms, selectable at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// fzy's scoring, as implemented by [`score`](crate::score).
+    #[default]
+    Fzy,
+    /// Bit-exact scores of the reference C fzy, see [`compat`](crate::compat).
+    FzyCompat,
+    /// fzf's greedy v1 algorithm, always case-insensitive.
+    FzfV1,
+    /// fzf's default v2 algorithm, always case-insensitive.
+    FzfV2,
+    /// Tuned for completing code identifiers rather than paths.
+    Completion,
+    /// Sublime Text's matcher as reproduced by `fts_fuzzy_match`.
+    Sublime,
+    /// Emacs' flx, which heavily favours word starts in the file name.
+    Flx,
+}
+
+impl FuzzyAlgorithm for Algorithm {
+    fn has_match(&self, pat: &[u8], text: &[u8]) -> bool {
+        match self {
+            Algorithm::Fzy => has_match(pat, text),
+            Algorithm::FzyCompat => compat::has_match(pat, text),
+            Algorithm::Flx => pat.is_empty() || flx::has_match(pat, text),
+            // These find a match wherever the query is a subsequence up to
+            // case, which is much cheaper to check than their best match.
+            _ => {
+                let mut text = text.iter();
+                (pat.iter()).all(|p| text.any(|c| c.eq_ignore_ascii_case(p)))
+            }
+        }
+    }
+
+    fn score(&self, pat: &[u8], text: &[u8]) -> Score {
+        match self {
+            Algorithm::Fzy => score(pat, text),
+            Algorithm::FzyCompat => compat::score(pat, text),
+            _ => self
+                .match_positions(pat, text)
+                .map_or(SCORE_MIN, |(score, _)| score),
+        }
+    }
+
+    fn positions(&self, pat: &[u8], text: &[u8]) -> Option<Vec<usize>> {
+        match self {
+            Algorithm::Fzy => positions(pat, text),
+            Algorithm::FzyCompat => compat::positions(pat, text),
+            _ if pat.is_empty() => Some(Vec::new()),
+            _ => self
+                .match_positions(pat, text)
+                .map(|(_, positions)| positions),
+        }
+    }
+
+    fn session(&self) -> Option<QuerySession> {
+        (*self == Algorithm::Fzy).then(QuerySession::new)
+    }
+}
+
+impl Algorithm {
+    fn match_positions(&self, pat: &[u8], text: &[u8]) -> Option<(Score, Vec<usize>)> {
+        match self {
+            Algorithm::Fzy => positions(pat, text).map(|p| (score(pat, text), p)),
+            Algorithm::FzyCompat => {
+                compat::positions(pat, text).map(|p| (compat::score(pat, text), p))
+            }
+            Algorithm::FzfV1 => fzf::match_v1(pat, text),
+            Algorithm::FzfV2 => fzf::match_v2(pat, text),
+            Algorithm::Completion => completion::match_completion(pat, text),
+            Algorithm::Sublime => sublime::match_sublime(pat, text),
+            Algorithm::Flx => flx::match_flx(pat, text),
+        }
+    }
+}
+
+/// Matches and scores a single candidate; an empty pattern matches everything
+/// with `SCORE_MIN` so that input order is preserved.
+pub(crate) fn match_candidate<A>(
+    algorithm: &A,
+    pat: &[u8],
+    text: &[u8],
+    index: usize,
+) -> Option<Match>
+where
+    A: FuzzyAlgorithm + ?Sized,
+{
+    if !algorithm.has_match(pat, text) {
+        return None;
+    }
+    let score = if pat.is_empty() {
+        SCORE_MIN
+    } else {
+        algorithm.score(pat, text)
+    };
+    Some(Match { index, score })
+}
+
+/// Matches `pat` against every candidate, best first.
+pub(crate) fn rank<A, T>(algorithm: &A, pat: &[u8], candidates: &[T]) -> Vec<Match>
+where
+    A: FuzzyAlgorithm + ?Sized,
+    T: AsRef<[u8]>,
+{
+    let mut matches: Vec<Match> = (candidates.iter().enumerate())
+        .filter_map(|(index, text)| match_candidate(algorithm, pat, text.as_ref(), index))
+        .collect();
+    matches.sort_by(Match::rank_cmp);
+    matches
+}
+
+impl std::fmt::Display for Algorithm {
+    /// The name [`FromStr`](std::str::FromStr) parses.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Algorithm::Fzy => "fzy",
+            Algorithm::FzyCompat => "fzy-compat",
+            Algorithm::FzfV1 => "fzf-v1",
+            Algorithm::FzfV2 => "fzf-v2",
+            Algorithm::Completion => "completion",
+            Algorithm::Sublime => "sublime",
+            Algorithm::Flx => "flx",
+        })
+    }
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Algorithm, String> {
+        match s {
+            "fzy" => Ok(Algorithm::Fzy),
+            "fzy-compat" => Ok(Algorithm::FzyCompat),
+            "fzf-v1" => Ok(Algorithm::FzfV1),
+            "fzf-v2" => Ok(Algorithm::FzfV2),
+            "completion" => Ok(Algorithm::Completion),
+            "sublime" => Ok(Algorithm::Sublime),
+            "flx" => Ok(Algorithm::Flx),
+            _ => Err(format!("unknown algorithm: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatches() {
+        for algorithm in [
+            Algorithm::Fzy,
+            Algorithm::FzyCompat,
+            Algorithm::FzfV1,
+            Algorithm::FzfV2,
+            Algorithm::Completion,
+            Algorithm::Sublime,
+            Algorithm::Flx,
+        ] {
+            assert!(algorithm.has_match(b"amo", b"app/models/foo"));
+            assert!(!algorithm.has_match(b"oma", b"app/models/foo"));
+            assert!(
+                algorithm.score(b"amo", b"app/models/foo") > algorithm.score(b"amo", b"app/m/xfoo")
+            );
+            assert_eq!(
+                algorithm.positions(b"amo", b"app/models/foo"),
+                Some(vec![0, 4, 5])
+            );
+            assert_eq!(algorithm.to_string().parse(), Ok(algorithm));
+        }
+        assert_eq!("fzf-v1".parse(), Ok(Algorithm::FzfV1));
+        assert!(Algorithm::FzfV2.has_match(b"AMo", b"app/Models/foo"));
+        assert!(!Algorithm::Flx.has_match(b"Amo", b"app/models/foo"));
+        assert!(Algorithm::Flx.has_match(b"amo", b"App/Models/foo"));
+        assert_eq!("flx".parse(), Ok(Algorithm::Flx));
+    }
+
+    #[test]
+    fn has_match_agrees_with_best_match() {
+        let texts = ["", "a", "app/Models/foo", "A_b-C.d", "src/ÉcoleBB", "bBa"];
+        let pats = [
+            "a", "A", "amf", "AMF", "abc", "aBc", "bb", "BB", "é", "xyz", "aa",
+        ];
+        for algorithm in [
+            Algorithm::FzfV1,
+            Algorithm::FzfV2,
+            Algorithm::Completion,
+            Algorithm::Sublime,
+            Algorithm::Flx,
+        ] {
+            for (pat, text) in pats.iter().flat_map(|p| texts.iter().map(move |t| (p, t))) {
+                let (pat, text) = (pat.as_bytes(), text.as_bytes());
+                assert_eq!(
+                    algorithm.has_match(pat, text),
+                    algorithm.match_positions(pat, text).is_some(),
+                    "{} {:?} {:?}",
+                    algorithm,
+                    pat,
+                    text
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! ANSI escape sequences in candidates read from colored output, such as
+//! `rg --color=always`.
+
+use std::borrow::Cow;
+
+use crate::{FuzzyAlgorithm, Score};
+
+const ESC: u8 = 0x1b;
+
+/// The length of the escape sequence starting at `text[i]`, or 0 when there
+/// is none: CSI sequences such as colors, OSC sequences such as hyperlinks,
+/// and shorter escapes.
+fn escape_len(text: &[u8], i: usize) -> usize {
+    if text[i] != ESC || i + 1 == text.len() {
+        return 0;
+    }
+    let rest = &text[i + 2..];
+    match text[i + 1] {
+        b'[' => match rest.iter().position(|b| (0x40..=0x7e).contains(b)) {
+            Some(end) => end + 3,
+            None => text.len() - i,
+        },
+        b']' => {
+            // Ends with BEL or ESC \.
+            let mut end = 0;
+            while end < rest.len() {
+                if rest[end] == 0x07 {
+                    return end + 3;
+                }
+                if rest[end] == ESC && rest.get(end + 1) == Some(&b'\\') {
+                    return end + 4;
+                }
+                end += 1;
+            }
+            text.len() - i
+        }
+        // Charset designations and the like: intermediates, then a final byte.
+        0x20..=0x2f => match rest.iter().position(|b| (0x30..=0x7e).contains(b)) {
+            Some(end) => end + 3,
+            None => text.len() - i,
+        },
+        0x30..=0x7e => 2,
+        _ => 0,
+    }
+}
+
+/// `text` without its escape sequences.
+pub fn strip_ansi(text: &[u8]) -> Cow<'_, [u8]> {
+    if !text.contains(&ESC) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        match escape_len(text, i) {
+            0 => {
+                out.push(text[i]);
+                i += 1;
+            }
+            len => i += len,
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Maps positions in the [`strip_ansi`] text back to positions in `text`,
+/// for highlighting the colored original.
+pub fn raw_positions(text: &[u8], positions: &[usize]) -> Vec<usize> {
+    let mut raw = Vec::with_capacity(positions.len());
+    let mut wanted = positions.iter().peekable();
+    let (mut i, mut stripped) = (0, 0);
+    while i < text.len() && wanted.peek().is_some() {
+        match escape_len(text, i) {
+            0 => {
+                if wanted.peek() == Some(&&stripped) {
+                    raw.push(i);
+                    wanted.next();
+                }
+                stripped += 1;
+                i += 1;
+            }
+            len => i += len,
+        }
+    }
+    raw
+}
+
+/// An algorithm matching the [`strip_ansi`] text of colored candidates, with
+/// positions into the colored text.
+#[derive(Debug, Clone, Default)]
+pub struct AnsiMatching<A> {
+    pub algorithm: A,
+}
+
+impl<A: FuzzyAlgorithm> FuzzyAlgorithm for AnsiMatching<A> {
+    fn has_match(&self, pat: &[u8], text: &[u8]) -> bool {
+        self.algorithm.has_match(pat, &strip_ansi(text))
+    }
+
+    fn score(&self, pat: &[u8], text: &[u8]) -> Score {
+        self.algorithm.score(pat, &strip_ansi(text))
+    }
+
+    fn positions(&self, pat: &[u8], text: &[u8]) -> Option<Vec<usize>> {
+        let positions = self.algorithm.positions(pat, &strip_ansi(text))?;
+        Some(raw_positions(text, &positions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{positions, Algorithm};
+
+    #[test]
+    fn strips_escapes() {
+        let colored = b"\x1b[35msrc/main.rs\x1b[0m:\x1b[32m12\x1b[0m:fn main";
+        assert_eq!(&strip_ansi(colored)[..], b"src/main.rs:12:fn main");
+        let link = b"\x1b]8;;file:///a\x1b\\a\x1b]8;;\x07b\x1b(Bc\x1b";
+        assert_eq!(&strip_ansi(link)[..], b"abc\x1b");
+        assert!(matches!(strip_ansi(b"plain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn maps_positions_back() {
+        let colored = b"\x1b[1ma\x1b[0mb/\x1b[31mc\x1b[0m";
+        let found = positions(b"ac", strip_ansi(colored)).unwrap();
+        assert_eq!(found, vec![0, 3]);
+        let raw = raw_positions(colored, &found);
+        assert_eq!(raw, vec![4, 16]);
+        assert!(raw.iter().all(|&i| b"ac".contains(&colored[i])));
+    }
+
+    #[test]
+    fn matches_colored_text() {
+        let ansi = AnsiMatching {
+            algorithm: Algorithm::Fzy,
+        };
+        let colored = b"[1mfoo[0mxm";
+        assert_eq!(
+            ansi.score(b"om", colored),
+            Algorithm::Fzy.score(b"om", b"fooxm")
+        );
+        // The `m`s of the escapes are not what matched.
+        assert_eq!(ansi.positions(b"om", colored), Some(vec![6, 12]));
+        assert!(!ansi.has_match(b"1", colored));
+    }
+}
@@ -0,0 +1,98 @@
+//! A compact set of candidate indices, one bit per candidate.
+
+/// A fixed length sequence of bits, such as which candidates match a query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitVec {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitVec {
+    /// `len` bits, all unset.
+    pub fn new(len: usize) -> BitVec {
+        BitVec {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bit at `index`; `false` past the end.
+    pub fn get(&self, index: usize) -> bool {
+        index < self.len && self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// # Panics
+    ///
+    /// If `index` is past the end.
+    pub fn set(&mut self, index: usize, value: bool) {
+        assert!(index < self.len, "bit {} out of {}", index, self.len);
+        let (word, bit) = (&mut self.words[index / 64], 1 << (index % 64));
+        if value {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    /// Number of set bits.
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Indices of the set bits, in order.
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        (self.words.iter().enumerate()).flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+
+    /// Unsets every set bit whose index `keep` rejects, such as candidates
+    /// that stop matching once the query grows.
+    pub fn retain<F: FnMut(usize) -> bool>(&mut self, mut keep: F) {
+        for (i, word) in self.words.iter_mut().enumerate() {
+            let mut ones = *word;
+            while ones != 0 {
+                let bit = ones.trailing_zeros() as usize;
+                ones &= ones - 1;
+                if !keep(i * 64 + bit) {
+                    *word &= !(1 << bit);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_and_retains_bits() {
+        let mut bits = BitVec::new(130);
+        for i in [0, 63, 64, 129] {
+            bits.set(i, true);
+        }
+        assert!(bits.get(64) && !bits.get(65) && !bits.get(130));
+        assert_eq!(bits.count_ones(), 4);
+        bits.retain(|i| i % 2 == 1);
+        assert_eq!(bits.ones().collect::<Vec<_>>(), vec![63, 129]);
+        bits.set(63, false);
+        assert_eq!(bits.ones().collect::<Vec<_>>(), vec![129]);
+    }
+}
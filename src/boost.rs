@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::{Candidate, FuzzyAlgorithm, QuerySession, Score};
+
+/// Outside knowledge about candidates added to their scores, such as which
+/// files were changed recently. Where [`FilterOptions::adjust`] rescores a
+/// match as a whole, a boost only adds to it, so any number can be combined.
+///
+/// [`FilterOptions::adjust`]: crate::FilterOptions::adjust
+pub trait Boost: Debug + Send + Sync {
+    /// Added to the score of a matching candidate.
+    fn boost(&self, candidate: &Candidate) -> Score;
+}
+
+/// How a [`RecencyBoost`] fades as files get older.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decay {
+    /// Halves every `half_life`.
+    Exponential { half_life: Duration },
+    /// Falls steadily to nothing at `horizon`.
+    Linear { horizon: Duration },
+}
+
+impl Decay {
+    /// The share of the boost left at `age`, from 1 down to 0.
+    fn factor(&self, age: Duration) -> Score {
+        match *self {
+            Decay::Exponential { half_life } if half_life.is_zero() => 0.0,
+            Decay::Exponential { half_life } => 0.5f64.powf(age.div_duration_f64(half_life)),
+            Decay::Linear { horizon } if age >= horizon => 0.0,
+            Decay::Linear { horizon } => 1.0 - age.div_duration_f64(horizon),
+        }
+    }
+}
+
+/// Boosts files by how recently they were modified, so that of two equally
+/// good matches the one worked on lately ranks first.
+///
+/// Modification times come from the caller, such as the `modified` of the
+/// entries found by `source::walk` with the `walk` feature.
+#[derive(Debug, Clone)]
+pub struct RecencyBoost {
+    modified: HashMap<Vec<u8>, SystemTime>,
+    now: SystemTime,
+    /// The boost of a file modified just now, 0.5 by default.
+    pub boost: Score,
+    /// A week's half life by default.
+    pub decay: Decay,
+}
+
+impl RecencyBoost {
+    /// Measures ages from `now`.
+    pub fn new(now: SystemTime) -> RecencyBoost {
+        RecencyBoost {
+            modified: HashMap::new(),
+            now,
+            boost: 0.5,
+            decay: Decay::Exponential {
+                half_life: Duration::from_secs(7 * 24 * 60 * 60),
+            },
+        }
+    }
+
+    pub fn insert(&mut self, path: &[u8], modified: SystemTime) {
+        self.modified.insert(path.to_vec(), modified);
+    }
+}
+
+impl Boost for RecencyBoost {
+    fn boost(&self, candidate: &Candidate) -> Score {
+        match self.modified.get(candidate.text) {
+            Some(modified) => {
+                // Files from the future count as modified just now.
+                let age = self.now.duration_since(*modified).unwrap_or_default();
+                self.boost * self.decay.factor(age)
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// Boosts items picked before, the more the more often and lately they were
+/// picked, as loaded from a [`SelectionHistory`].
+///
+/// [`SelectionHistory`]: crate::history::SelectionHistory
+#[derive(Debug, Clone)]
+pub struct SelectionBoost {
+    picked: HashMap<Vec<u8>, Vec<SystemTime>>,
+    now: SystemTime,
+    /// The boost approached by an item picked ever more often, 1 by default.
+    pub boost: Score,
+    /// How each pick fades, a 30 days half life by default.
+    pub decay: Decay,
+}
+
+impl SelectionBoost {
+    /// Measures ages from `now`.
+    pub fn new(now: SystemTime) -> SelectionBoost {
+        SelectionBoost {
+            picked: HashMap::new(),
+            now,
+            boost: 1.0,
+            decay: Decay::Exponential {
+                half_life: Duration::from_secs(30 * 24 * 60 * 60),
+            },
+        }
+    }
+
+    pub fn insert(&mut self, item: &[u8], picked: SystemTime) {
+        self.picked.entry(item.to_vec()).or_default().push(picked);
+    }
+
+    /// Every item picked before, with its boost.
+    pub fn boosts(&self) -> impl Iterator<Item = (&[u8], Score)> + '_ {
+        (self.picked.keys()).map(move |text| (&text[..], self.boost(&Candidate { index: 0, text })))
+    }
+}
+
+impl Boost for SelectionBoost {
+    fn boost(&self, candidate: &Candidate) -> Score {
+        let Some(picked) = self.picked.get(candidate.text) else {
+            return 0.0;
+        };
+        let weight: Score = (picked.iter())
+            .map(|when| (self.decay).factor(self.now.duration_since(*when).unwrap_or_default()))
+            .sum();
+        self.boost * weight / (1.0 + weight)
+    }
+}
+
+/// An algorithm scoring with boosts added, for ranking layers without
+/// [`FilterOptions::boosts`], such as the picker.
+///
+/// Algorithms don't see candidate indices, so boosts get an `index` of 0.
+/// An empty query scores nothing, leaving candidates in input order.
+///
+/// [`FilterOptions::boosts`]: crate::FilterOptions::boosts
+#[derive(Debug, Clone, Default)]
+pub struct Boosted<A> {
+    pub algorithm: A,
+    pub boosts: Vec<Arc<dyn Boost>>,
+}
+
+impl<A: FuzzyAlgorithm> FuzzyAlgorithm for Boosted<A> {
+    fn has_match(&self, pat: &[u8], text: &[u8]) -> bool {
+        self.algorithm.has_match(pat, text)
+    }
+
+    fn score(&self, pat: &[u8], text: &[u8]) -> Score {
+        let candidate = Candidate { index: 0, text };
+        let boost: Score = self.boosts.iter().map(|b| b.boost(&candidate)).sum();
+        self.algorithm.score(pat, text) + boost
+    }
+
+    fn positions(&self, pat: &[u8], text: &[u8]) -> Option<Vec<usize>> {
+        self.algorithm.positions(pat, text)
+    }
+
+    fn session(&self) -> Option<QuerySession> {
+        self.boosts.is_empty().then(|| self.algorithm.session())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::rank;
+    use crate::Algorithm;
+
+    #[test]
+    fn decays_with_age() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let day = Duration::from_secs(24 * 60 * 60);
+        let mut recency = RecencyBoost::new(now);
+        recency.boost = 1.0;
+        recency.decay = Decay::Exponential { half_life: day };
+        recency.insert(b"new", now);
+        recency.insert(b"old", now - 2 * day);
+        recency.insert(b"future", now + day);
+        let boost =
+            |recency: &RecencyBoost, text: &[u8]| recency.boost(&Candidate { index: 0, text });
+        assert_eq!(boost(&recency, b"new"), 1.0);
+        assert_eq!(boost(&recency, b"old"), 0.25);
+        assert_eq!(boost(&recency, b"future"), 1.0);
+        assert_eq!(boost(&recency, b"unknown"), 0.0);
+
+        recency.decay = Decay::Linear { horizon: 4 * day };
+        assert_eq!(boost(&recency, b"old"), 0.5);
+        recency.decay = Decay::Linear { horizon: day };
+        assert_eq!(boost(&recency, b"old"), 0.0);
+    }
+
+    #[test]
+    fn boosts_picked_items() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let day = Duration::from_secs(24 * 60 * 60);
+        let mut picked = SelectionBoost::new(now);
+        picked.decay = Decay::Exponential { half_life: day };
+        picked.insert(b"often", now);
+        picked.insert(b"often", now);
+        picked.insert(b"once", now);
+        picked.insert(b"old", now - 2 * day);
+        let boost = |text: &[u8]| picked.boost(&Candidate { index: 0, text });
+        assert_eq!(boost(b"often"), 2.0 / 3.0);
+        assert_eq!(boost(b"once"), 0.5);
+        assert_eq!(boost(b"old"), 0.2);
+        assert_eq!(boost(b"never"), 0.0);
+        let mut boosts: Vec<_> = picked.boosts().collect();
+        boosts.sort_by(|a, b| a.0.cmp(b.0));
+        assert_eq!(boosts[2], (&b"once"[..], 0.5));
+
+        let boosted = Boosted {
+            algorithm: Algorithm::Fzy,
+            boosts: vec![Arc::new(picked.clone()) as Arc<dyn Boost>],
+        };
+        let texts = ["never/a", "once/a"];
+        let ranked = rank(&boosted, b"a", &texts);
+        assert_eq!(ranked[0].index, 1);
+    }
+}
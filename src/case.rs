@@ -0,0 +1,128 @@
+//! Case handling applied the same way to filtering and scoring, whatever the
+//! algorithm does by itself.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+    has_match, positions_respecting_case, score_respecting_case, Algorithm, FuzzyAlgorithm,
+    QuerySession, Score,
+};
+
+/// Whether query letters match either case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Case {
+    /// Ignore case unless the query has an uppercase letter.
+    #[default]
+    Smart,
+    Ignore,
+    Respect,
+}
+
+impl Case {
+    /// Whether a query is matched case-sensitively.
+    pub fn respects(&self, pat: &[u8]) -> bool {
+        match self {
+            Case::Smart => pat.iter().any(u8::is_ascii_uppercase),
+            Case::Ignore => false,
+            Case::Respect => true,
+        }
+    }
+}
+
+impl fmt::Display for Case {
+    /// The name [`FromStr`] parses.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Case::Smart => "smart",
+            Case::Ignore => "ignore",
+            Case::Respect => "respect",
+        })
+    }
+}
+
+impl FromStr for Case {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Case, String> {
+        match s {
+            "smart" => Ok(Case::Smart),
+            "ignore" => Ok(Case::Ignore),
+            "respect" => Ok(Case::Respect),
+            _ => Err(format!("unknown case mode: {}", s)),
+        }
+    }
+}
+
+/// A built-in algorithm matching with a given [`Case`].
+///
+/// Ignoring case, the query is lowercased and candidates match regardless of
+/// case. Respecting it, a candidate must contain the query as a
+/// case-sensitive subsequence, and fzy's scores and positions only align
+/// bytes of the same case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Cased {
+    pub algorithm: Algorithm,
+    pub case: Case,
+}
+
+impl FuzzyAlgorithm for Cased {
+    fn has_match(&self, pat: &[u8], text: &[u8]) -> bool {
+        if self.case.respects(pat) {
+            has_match(pat, text) && self.algorithm.has_match(pat, text)
+        } else {
+            let (pat, text) = (pat.to_ascii_lowercase(), text.to_ascii_lowercase());
+            self.algorithm.has_match(&pat, &text)
+        }
+    }
+
+    fn score(&self, pat: &[u8], text: &[u8]) -> Score {
+        if !self.case.respects(pat) {
+            self.algorithm.score(&pat.to_ascii_lowercase(), text)
+        } else if self.algorithm == Algorithm::Fzy {
+            score_respecting_case(pat, text)
+        } else {
+            self.algorithm.score(pat, text)
+        }
+    }
+
+    fn positions(&self, pat: &[u8], text: &[u8]) -> Option<Vec<usize>> {
+        if !self.case.respects(pat) {
+            self.algorithm.positions(&pat.to_ascii_lowercase(), text)
+        } else if self.algorithm == Algorithm::Fzy {
+            positions_respecting_case(pat, text)
+        } else {
+            self.algorithm.positions(pat, text)
+        }
+    }
+
+    fn session(&self) -> Option<QuerySession> {
+        (self.algorithm == Algorithm::Fzy).then(|| QuerySession::with_case(self.case))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_case_mode() {
+        let cased = |case| Cased {
+            algorithm: Algorithm::Fzy,
+            case,
+        };
+        let smart = cased(Case::Smart);
+        assert!(smart.has_match(b"readme", b"README.md"));
+        assert!(!smart.has_match(b"Readme", b"readme.md"));
+        assert!(!cased(Case::Respect).has_match(b"readme", b"README.md"));
+        assert!(cased(Case::Ignore).has_match(b"README", b"readme.md"));
+
+        // Respecting case, the lowercase `b` later on is aligned rather than
+        // the capital that would score better.
+        let respect = cased(Case::Respect);
+        assert_eq!(respect.positions(b"ab", b"a/Bb"), Some(vec![0, 3]));
+        assert_eq!(smart.positions(b"ab", b"a/Bb"), Some(vec![0, 2]));
+        assert!(respect.score(b"ab", b"a/Bb") < smart.score(b"ab", b"a/Bb"));
+        assert_eq!("respect".parse(), Ok(Case::Respect));
+    }
+}
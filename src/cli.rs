@@ -0,0 +1,668 @@
+//! The command line tool behind `main`. It drives the terminal, spawns
+//! `--source` commands and serves unix sockets, so it only builds on unix.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::TcpListener;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use fzy_rs::daemon::Daemon;
+use fzy_rs::history::{QueryHistory, SelectionHistory};
+use fzy_rs::picker::{Outcome, Picker, Preselect};
+use fzy_rs::record::{Config, Recorder, Replay};
+use fzy_rs::source::{CommandSource, FileSource, Source, Stdin, TagSource, Walk};
+use fzy_rs::{
+    filter_lines, http, parent_dir, tty, write_grouped, write_json, write_terminated, write_tsv,
+    Algorithm, AnsiMatching, Boosted, Case, Cased, Exclude, FieldMatching, FieldRange, Fields,
+    FilterOptions, Sanitize, Step,
+};
+
+const USAGE: &str = "Usage: fzy-rs [OPTION]...
+Reads candidates from stdin and prints the one picked interactively.
+
+  -e, --show-matches=QUERY  output the sorted matches of QUERY
+  -l, --lines=LINES         output at most LINES matches
+      --algorithm=NAME      rank with fzy (default), fzy-compat, fzf-v1,
+                            fzf-v2, completion, sublime or flx
+  -d, --delimiter=STR       separate the fields of --nth with STR rather
+                            than runs of whitespace
+  -n, --nth=FIELDS          only match the comma separated FIELDS of each
+                            candidate, e.g. 1 or 2..,-1 counting from the
+                            end, while still showing and printing it whole
+  -i                        match case-insensitively
+  +i                        match case-sensitively
+      --smart-case          match case-insensitively unless the query has
+                            an uppercase letter (default)
+      --output=FORMAT       output format: lines (default), tsv (score,
+                            input index and candidate), json or grouped
+                            (matching paths under their directory)
+      --record=FILE         record candidates and queries of the interactive
+                            picker to FILE
+      --replay=FILE         print the rankings of a recording made with
+                            --record, each after a ? QUERY line
+      --history=FILE        remember accepted queries in FILE, recalled with
+                            ctrl-p and ctrl-n
+      --selections=FILE     remember accepted items in FILE and rank them
+                            higher the more often and lately they were
+                            accepted
+      --no-selection-boost  keep remembering accepted items in the
+                            --selections FILE without ranking them higher
+      --prune-selections=DAYS
+                            forget items accepted more than DAYS days ago
+                            from the --selections FILE and exit
+      --preselect=ITEM      open the interactive picker with the candidate
+                            ITEM highlighted, or the candidate at input
+                            index ITEM when it's a number
+  -m, --multi[=MAX]         let tab mark several candidates, at most MAX,
+                            and print the marked ones when accepting
+      --expect=KEYS         also accept with any of the comma separated
+                            KEYS, e.g. ctrl-o,ctrl-v, printing the key used
+                            before the selection, or an empty line for
+                            enter
+      --print-query         print the query before the selection, also when
+                            aborting
+      --tac                 reverse the order of the candidates, so that the
+                            last ones read win ties; the interactive picker
+                            shows candidates only once all are read
+      --sync                read every candidate before showing the
+                            interactive picker, rather than showing them as
+                            they arrive
+      --mouse               enable mouse support in the interactive picker
+      --reverse             show the prompt at the top, results below it
+      --tabstop=SPACES      width of tabs in the interactive picker
+                            (default 8)
+      --file=PATH           read candidates from PATH instead of stdin
+      --source=COMMAND      read candidates from the output of COMMAND, run
+                            again on ctrl-r
+      --walk=DIR            use the paths of the files below DIR as
+                            candidates; --file, --source and --walk may be
+                            repeated and combined
+      --tags=FILE           use the tags of the ctags file FILE as
+                            candidates, printed as the whole tags line;
+                            without --nth, only the tag name, the text
+                            before the first tab, of every candidate is
+                            matched
+      --exclude=PATTERN     skip candidates containing PATTERN, or matching
+                            it as a whole when it has * or ? wildcards; may
+                            be repeated
+      --ext=EXTS            only use candidates with one of the comma
+                            separated extensions EXTS, e.g. rs,toml
+      --prefer-shallow      rank paths with fewer directories first among
+                            equally scored matches; only for printed
+                            matches, the interactive picker keeps ties in
+                            input order
+      --ansi                ignore ANSI color codes in candidates when
+                            matching; printed matches keep them, the
+                            interactive picker shows them stripped
+      --read0               read input delimited by ASCII NUL characters
+      --print0              print output delimited by ASCII NUL characters
+      --http=ADDR           serve POST /match on ADDR, matching against the
+                            candidates read from stdin
+      --daemon=PATH         serve named candidate sessions on the Unix
+                            socket PATH
+      --grpc=ADDR           serve the daemon's sessions over gRPC on ADDR,
+                            with the grpc feature
+      --memory-limit=BYTES  refuse daemon candidates past BYTES of memory
+  -h, --help                display this help and exit";
+
+#[derive(Clone, Copy)]
+enum Output {
+    Lines,
+    Tsv,
+    Json,
+    Grouped,
+}
+
+enum Input {
+    File(PathBuf),
+    Walk(PathBuf),
+    Command(String),
+    Tags(PathBuf),
+}
+
+struct Args {
+    query: Option<String>,
+    inputs: Vec<Input>,
+    exclude: Vec<Exclude>,
+    extensions: Vec<Vec<u8>>,
+    prefer_shallow: bool,
+    ansi: bool,
+    algorithm: Algorithm,
+    case: Case,
+    fields: Fields,
+    limit: Option<usize>,
+    output: Output,
+    history: Option<PathBuf>,
+    selections: Option<PathBuf>,
+    selection_boost: bool,
+    prune_selections: Option<u64>,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    preselect: Option<Preselect>,
+    multi: Option<Option<usize>>,
+    expect: Vec<(String, tty::Input)>,
+    print_query: bool,
+    tac: bool,
+    sync: bool,
+    mouse: bool,
+    reverse: bool,
+    tabstop: usize,
+    read0: bool,
+    print0: bool,
+    http: Option<String>,
+    daemon: Option<String>,
+    grpc: Option<String>,
+    memory_limit: Option<usize>,
+}
+
+impl Args {
+    /// The algorithm with the case mode and fields asked for. Reading tags,
+    /// only the tag names are matched unless other fields are.
+    fn matching(&self) -> FieldMatching<Cased> {
+        let mut fields = self.fields.clone();
+        let tags = (self.inputs.iter()).any(|input| matches!(input, Input::Tags(_)));
+        if tags && fields.ranges.is_empty() {
+            fields = Fields {
+                delimiter: Some(b"\t".to_vec()),
+                ranges: vec![FieldRange {
+                    start: Some(1),
+                    end: Some(1),
+                }],
+            };
+        }
+        FieldMatching {
+            algorithm: Cased {
+                algorithm: self.algorithm,
+                case: self.case,
+            },
+            fields,
+        }
+    }
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        query: None,
+        inputs: Vec::new(),
+        exclude: Vec::new(),
+        extensions: Vec::new(),
+        prefer_shallow: false,
+        ansi: false,
+        algorithm: Algorithm::Fzy,
+        case: Case::Smart,
+        fields: Fields::default(),
+        limit: None,
+        output: Output::Lines,
+        history: None,
+        selections: None,
+        selection_boost: true,
+        prune_selections: None,
+        record: None,
+        replay: None,
+        preselect: None,
+        multi: None,
+        expect: Vec::new(),
+        print_query: false,
+        tac: false,
+        sync: false,
+        mouse: false,
+        reverse: false,
+        tabstop: 8,
+        read0: false,
+        print0: false,
+        http: None,
+        daemon: None,
+        grpc: None,
+        memory_limit: None,
+    };
+    let mut argv = env::args_os().skip(1);
+    while let Some(arg) = argv.next() {
+        let arg = arg
+            .into_string()
+            .map_err(|arg| format!("invalid argument: {:?}", arg))?;
+        let (flag, inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (&arg[..i], Some(arg[i + 1..].to_string())),
+            _ => (&arg[..], None),
+        };
+        let mut value = || match inline.clone() {
+            Some(v) => Ok(v),
+            None => argv
+                .next()
+                .and_then(|v| v.into_string().ok())
+                .ok_or_else(|| format!("option {} requires an argument", flag)),
+        };
+        match flag {
+            "-e" | "--show-matches" => args.query = Some(value()?),
+            "-l" | "--lines" => {
+                let lines = value()?;
+                args.limit = Some(
+                    lines
+                        .parse()
+                        .map_err(|_| format!("invalid line count: {}", lines))?,
+                );
+            }
+            "--algorithm" => args.algorithm = value()?.parse()?,
+            "-d" | "--delimiter" => args.fields.delimiter = Some(value()?.into_bytes()),
+            "-n" | "--nth" => args.fields.ranges = Fields::parse_ranges(&value()?)?,
+            "-i" => args.case = Case::Ignore,
+            "+i" => args.case = Case::Respect,
+            "--smart-case" => args.case = Case::Smart,
+            "--output" => {
+                args.output = match &value()?[..] {
+                    "lines" => Output::Lines,
+                    "tsv" => Output::Tsv,
+                    "json" => Output::Json,
+                    "grouped" => Output::Grouped,
+                    other => return Err(format!("unknown output format: {}", other)),
+                }
+            }
+            "--history" => args.history = Some(value()?.into()),
+            "--selections" => args.selections = Some(value()?.into()),
+            "--no-selection-boost" => args.selection_boost = false,
+            "--prune-selections" => {
+                let days = value()?;
+                args.prune_selections = Some(
+                    days.parse()
+                        .map_err(|_| format!("invalid day count: {}", days))?,
+                );
+            }
+            "--record" => args.record = Some(value()?.into()),
+            "--replay" => args.replay = Some(value()?.into()),
+            "--preselect" => {
+                let item = value()?;
+                args.preselect = Some(match item.parse() {
+                    Ok(id) => Preselect::Id(id),
+                    Err(_) => Preselect::Text(item.into_bytes()),
+                });
+            }
+            "-m" | "--multi" => {
+                let max = inline.as_ref().map(|max| {
+                    max.parse()
+                        .map_err(|_| format!("invalid selection count: {}", max))
+                });
+                args.multi = Some(max.transpose()?);
+            }
+            "--expect" => {
+                for name in value()?.split(',').filter(|name| !name.is_empty()) {
+                    let key =
+                        tty::parse_key(name).ok_or_else(|| format!("unknown key: {}", name))?;
+                    args.expect.push((name.to_string(), key));
+                }
+            }
+            "--print-query" => args.print_query = true,
+            "--tac" => args.tac = true,
+            "--sync" => args.sync = true,
+            "--mouse" => args.mouse = true,
+            "--reverse" => args.reverse = true,
+            "--tabstop" => {
+                let spaces = value()?;
+                args.tabstop = spaces
+                    .parse()
+                    .map_err(|_| format!("invalid tab width: {}", spaces))?;
+            }
+            "--file" => args.inputs.push(Input::File(value()?.into())),
+            "--walk" => args.inputs.push(Input::Walk(value()?.into())),
+            "--source" => args.inputs.push(Input::Command(value()?)),
+            "--tags" => args.inputs.push(Input::Tags(value()?.into())),
+            "--exclude" => args.exclude.push(Exclude::new(&value()?)),
+            "--ext" => {
+                let exts = value()?;
+                let exts = exts.split(',').filter(|ext| !ext.is_empty());
+                args.extensions
+                    .extend(exts.map(|ext| ext.as_bytes().to_vec()));
+            }
+            "--prefer-shallow" => args.prefer_shallow = true,
+            "--ansi" => args.ansi = true,
+            "--read0" => args.read0 = true,
+            "--print0" => args.print0 = true,
+            "--http" => args.http = Some(value()?),
+            "--daemon" => args.daemon = Some(value()?),
+            "--grpc" => args.grpc = Some(value()?),
+            "--memory-limit" => {
+                let bytes = value()?;
+                args.memory_limit = Some(
+                    bytes
+                        .parse()
+                        .map_err(|_| format!("invalid memory limit: {}", bytes))?,
+                );
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+    Ok(args)
+}
+
+fn sources(args: &Args) -> Vec<Box<dyn Source>> {
+    if args.inputs.is_empty() {
+        return vec![Box::new(Stdin)];
+    }
+    args.inputs
+        .iter()
+        .map(|input| -> Box<dyn Source> {
+            match input {
+                Input::File(path) => Box::new(FileSource(path.clone())),
+                Input::Walk(dir) => Box::new(Walk(dir.clone())),
+                Input::Command(command) => Box::new(CommandSource(command.clone())),
+                Input::Tags(path) => Box::new(TagSource(path.clone())),
+            }
+        })
+        .collect()
+}
+
+fn read_all(args: &Args, opts: &FilterOptions) -> io::Result<Vec<Vec<u8>>> {
+    let mut lines = Vec::new();
+    sources(args).read(opts, &mut |line| lines.push(line.to_vec()))?;
+    if args.tac {
+        lines.reverse();
+    }
+    Ok(lines)
+}
+
+fn stream(args: &Args, opts: &FilterOptions) -> (Receiver<Vec<u8>>, JoinHandle<io::Result<()>>) {
+    let (tx, rx) = mpsc::channel();
+    let mut sources = sources(args);
+    let opts = opts.clone();
+    let tac = args.tac;
+    let reader = thread::spawn(move || {
+        // Reversed candidates can only be sent once the last one is read.
+        let mut lines = Vec::new();
+        // Reading stops, killing any --source command, once the picker has
+        // closed or reloaded and so dropped the receiver.
+        sources.read_while(&opts, &mut |line| {
+            if opts.excludes(line) {
+                true
+            } else if tac {
+                lines.push(line.to_vec());
+                true
+            } else {
+                tx.send(line.to_vec()).is_ok()
+            }
+        })?;
+        for line in lines.into_iter().rev() {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+    (rx, reader)
+}
+
+/// Queries are recalled per working directory and set of inputs.
+fn history_context(args: &Args) -> Vec<u8> {
+    let mut context = env::current_dir()
+        .map(|dir| dir.into_os_string().into_vec())
+        .unwrap_or_default();
+    for input in &args.inputs {
+        let (flag, value) = match input {
+            Input::File(path) => ("--file", path.as_os_str().as_bytes()),
+            Input::Walk(dir) => ("--walk", dir.as_os_str().as_bytes()),
+            Input::Command(command) => ("--source", command.as_bytes()),
+            Input::Tags(path) => ("--tags", path.as_os_str().as_bytes()),
+        };
+        context.push(b' ');
+        context.extend_from_slice(flag.as_bytes());
+        context.push(b'=');
+        context.extend_from_slice(value);
+    }
+    context
+}
+
+fn pick(args: &Args, opts: FilterOptions) -> io::Result<i32> {
+    let selections =
+        (args.selections.as_ref()).map(|path| SelectionHistory::new(path, &history_context(args)));
+    let mut algorithm = Boosted {
+        algorithm: args.matching(),
+        boosts: Vec::new(),
+    };
+    let mut config = Config {
+        matching: args.matching(),
+        boosts: Vec::new(),
+    };
+    if let Some(selections) = selections.as_ref().filter(|_| args.selection_boost) {
+        let boost = selections.load(SystemTime::now())?;
+        (config.boosts).extend(boost.boosts().map(|(text, boost)| (text.to_vec(), boost)));
+        algorithm.boosts.push(Arc::new(boost));
+    }
+    let mut picker = Picker::with_algorithm(algorithm);
+    if let Some(max) = args.multi {
+        picker.set_multi(max);
+    }
+    if let Some(preselect) = &args.preselect {
+        picker.preselect(preselect.clone());
+    }
+    let history =
+        (args.history.as_ref()).map(|path| QueryHistory::new(path, &history_context(args)));
+    if let Some(history) = &history {
+        picker.set_history(history.load()?);
+    }
+    if let Some(path) = &args.record {
+        picker.record(Recorder::new(File::create(path)?, &config)?);
+    }
+    let tty_opts = tty::Options {
+        mouse: args.mouse,
+        reverse: args.reverse,
+        sanitize: Sanitize {
+            tab_width: args.tabstop,
+            ..Sanitize::default()
+        },
+        expect: args.expect.iter().map(|(_, key)| *key).collect(),
+        start: if args.sync {
+            tty::Start::Sync
+        } else {
+            tty::Start::Streaming
+        },
+    };
+    // Standard input can't be read twice, so only other sources reload.
+    let (incoming, mut reader) = stream(args, &opts);
+    let mut reload = || {
+        let (incoming, next) = stream(args, &opts);
+        reader = next;
+        incoming
+    };
+    let reload: Option<&mut dyn FnMut() -> Receiver<Vec<u8>>> = if args.inputs.is_empty() {
+        None
+    } else {
+        Some(&mut reload)
+    };
+    let outcome = tty::run(&mut picker, &tty_opts, Some(incoming), reload)?;
+    // Candidates still being read are left to stop on their own; a source
+    // that failed is reported rather than passing for a short list.
+    if reader.is_finished() {
+        reader.join().unwrap_or(Ok(()))?;
+    }
+    let stdout = io::stdout();
+    let terminator = if args.print0 { b'\0' } else { b'\n' };
+    let query = args.print_query.then(|| picker.query());
+    let (id, key) = match outcome {
+        Outcome::Accept(id) => (id, None),
+        Outcome::AcceptKey { id, key } => (id, Some(key)),
+        Outcome::Abort => {
+            write_terminated(stdout.lock(), query, terminator)?;
+            return Ok(1);
+        }
+    };
+    if let Some(history) = &history {
+        history.append(picker.query())?;
+    }
+    let ids = match picker.marked() {
+        [] => vec![id],
+        marked => marked.to_vec(),
+    };
+    let items: Vec<&[u8]> = ids.iter().filter_map(|id| picker.get(*id)).collect();
+    if let Some(selections) = &selections {
+        for item in &items {
+            selections.append(item, SystemTime::now())?;
+        }
+    }
+    // With --expect, which key accepted comes first, empty for enter.
+    let key = (!args.expect.is_empty())
+        .then(|| key.map_or(&b""[..], |key| args.expect[key].0.as_bytes()));
+    let lines = query.into_iter().chain(key).chain(items);
+    write_terminated(stdout.lock(), lines, terminator)?;
+    Ok(0)
+}
+
+fn replay(path: &Path) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut replay = Replay::new(BufReader::new(File::open(path)?))?;
+    while let Some(ranking) = replay.next() {
+        let ranking = ranking?;
+        out.write_all(b"? ")?;
+        out.write_all(&ranking.query)?;
+        out.write_all(b"\n")?;
+        for m in &ranking.matches {
+            write!(out, "{}\t{}\t", m.score, m.index)?;
+            out.write_all(replay.set().get(m.index).unwrap_or_default())?;
+            out.write_all(b"\n")?;
+        }
+    }
+    out.flush()
+}
+
+/// The daemon of --daemon and --grpc. Its sessions rank with a plain
+/// algorithm, which their clients may choose, so the case and field options
+/// are refused rather than ignored.
+fn daemon(args: &Args) -> io::Result<Daemon> {
+    if args.case != Case::Smart || args.fields != Fields::default() {
+        return Err(io::Error::other(
+            "--daemon and --grpc don't support -i, +i, -d or --nth",
+        ));
+    }
+    let daemon = Daemon::new(args.algorithm);
+    Ok(match args.memory_limit {
+        Some(bytes) => daemon.with_memory_limit(bytes),
+        None => daemon,
+    })
+}
+
+#[cfg(feature = "grpc")]
+fn serve_grpc(args: &Args, addr: &str) -> io::Result<()> {
+    use std::net::ToSocketAddrs;
+
+    let resolved = addr.to_socket_addrs()?.next();
+    let addr = resolved.ok_or_else(|| io::Error::other(format!("no address for {}", addr)))?;
+    fzy_rs::grpc::serve(Arc::new(daemon(args)?), addr)
+}
+
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc(_: &Args, _: &str) -> io::Result<()> {
+    Err(io::Error::other(
+        "--grpc needs fzy-rs built with the grpc feature",
+    ))
+}
+
+fn run(args: &Args) -> io::Result<i32> {
+    let mut opts = FilterOptions {
+        algorithm: args.algorithm,
+        limit: args.limit,
+        delimiter: if args.read0 { b'\0' } else { b'\n' },
+        exclude: args.exclude.clone(),
+        extensions: args.extensions.clone(),
+        shallow_first: args.prefer_shallow,
+        ..FilterOptions::default()
+    };
+    if args.ansi {
+        opts.match_transform.push(Step::StripAnsi);
+    }
+    if let Some(path) = &args.replay {
+        return replay(path).map(|()| 0);
+    }
+    if let Some(days) = args.prune_selections {
+        let path = (args.selections.as_ref())
+            .ok_or_else(|| io::Error::other("--prune-selections needs --selections"))?;
+        let cutoff = (days.checked_mul(24 * 60 * 60))
+            .and_then(|seconds| SystemTime::now().checked_sub(Duration::from_secs(seconds)))
+            .ok_or_else(|| {
+                io::Error::other(format!("--prune-selections: {} days is too long", days))
+            })?;
+        return SelectionHistory::prune(path, cutoff).map(|_| 0);
+    }
+    if let Some(path) = &args.daemon {
+        let daemon = Arc::new(daemon(args)?);
+        return daemon.serve(UnixListener::bind(path)?).map(|()| 0);
+    }
+    if let Some(addr) = &args.grpc {
+        return serve_grpc(args, addr).map(|()| 0);
+    }
+    if let Some(addr) = &args.http {
+        let mut corpus = read_all(args, &opts)?;
+        corpus.retain(|line| !opts.excludes(line));
+        return http::serve(TcpListener::bind(addr)?, args.matching(), corpus).map(|()| 0);
+    }
+    let query = match &args.query {
+        Some(query) => query.as_bytes(),
+        None if args.ansi => {
+            // The picker draws its own highlighting over the text it matches.
+            opts.display_transform.push(Step::StripAnsi);
+            return pick(args, opts);
+        }
+        None => return pick(args, opts),
+    };
+
+    let lines = read_all(args, &opts)?;
+    let filtered = filter_lines(query, lines, &opts.with_algorithm(args.matching()));
+    let stdout = io::stdout();
+    let out = BufWriter::new(stdout.lock());
+    let terminator = if args.print0 { b'\0' } else { b'\n' };
+    match args.output {
+        Output::Lines => write_terminated(out, filtered.iter().map(|(line, _)| line), terminator)?,
+        Output::Tsv => write_tsv(out, &filtered.lines, &filtered.matches, terminator)?,
+        // Positions are found in the text that was matched and point into
+        // the line as printed.
+        Output::Json if args.ansi => write_json(
+            out,
+            &AnsiMatching {
+                algorithm: args.matching(),
+            },
+            query,
+            &filtered.lines,
+            &filtered.matches,
+        )?,
+        Output::Json => write_json(
+            out,
+            &args.matching(),
+            query,
+            &filtered.lines,
+            &filtered.matches,
+        )?,
+        Output::Grouped => write_grouped(
+            out,
+            &filtered.lines,
+            &filtered.groups(parent_dir),
+            terminator,
+        )?,
+    }
+    Ok(0)
+}
+
+pub fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("fzy-rs: {}\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    match run(&args) {
+        Ok(code) => process::exit(code),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(err) => {
+            eprintln!("fzy-rs: {}", err);
+            process::exit(1);
+        }
+    }
+}
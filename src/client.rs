@@ -0,0 +1,222 @@
+//! A client for the [`daemon`](crate::daemon) protocol.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use crate::json::{self, Value};
+use crate::output::write_json_str;
+
+/// One entry of a daemon's answer to a match request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteMatch {
+    pub text: String,
+    /// `None` for the scores JSON can't represent, see
+    /// [`write_json`](crate::write_json).
+    pub score: Option<f64>,
+    pub index: usize,
+    pub positions: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<Client> {
+        Client::from_stream(UnixStream::connect(path)?)
+    }
+
+    pub fn from_stream(stream: UnixStream) -> io::Result<Client> {
+        Ok(Client {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    /// Replaces the named session's candidates, returning how many there are.
+    pub fn load<T: AsRef<str>>(&mut self, session: &str, items: &[T]) -> io::Result<usize> {
+        self.update("load", session, items)
+    }
+
+    /// Adds to the named session's candidates, returning how many there are.
+    pub fn append<T: AsRef<str>>(&mut self, session: &str, items: &[T]) -> io::Result<usize> {
+        self.update("append", session, items)
+    }
+
+    /// Frees the named session, returning how many candidates it had.
+    pub fn unload(&mut self, session: &str) -> io::Result<usize> {
+        self.update::<&str>("unload", session, &[])
+    }
+
+    /// Makes `query` the named session's active query and ranks against it.
+    pub fn query(
+        &mut self,
+        session: &str,
+        query: &str,
+        limit: Option<usize>,
+    ) -> io::Result<Vec<RemoteMatch>> {
+        self.send(&match_request("match", session, query, limit)?)?;
+        parse_matches(&self.receive()?)
+    }
+
+    /// Like [`query`](Client::query), then ranks again whenever the session
+    /// changes. The connection is used for nothing else
+    /// afterwards.
+    pub fn subscribe(
+        mut self,
+        session: &str,
+        query: &str,
+        limit: Option<usize>,
+    ) -> io::Result<Subscription> {
+        self.send(&match_request("subscribe", session, query, limit)?)?;
+        Ok(Subscription {
+            reader: self.reader,
+        })
+    }
+
+    fn update<T: AsRef<str>>(&mut self, op: &str, session: &str, items: &[T]) -> io::Result<usize> {
+        let mut request = Vec::new();
+        write!(request, "{{\"op\":\"{}\",\"session\":", op)?;
+        write_json_str(&mut request, session)?;
+        if op != "unload" {
+            request.extend_from_slice(b",\"items\":[");
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    request.push(b',');
+                }
+                write_json_str(&mut request, item.as_ref())?;
+            }
+            request.push(b']');
+        }
+        request.extend_from_slice(b"}\n");
+        self.send(&request)?;
+        (self.receive()?.get("len").and_then(Value::as_usize))
+            .ok_or_else(|| invalid("expected a length"))
+    }
+
+    fn send(&mut self, request: &[u8]) -> io::Result<()> {
+        self.writer.write_all(request)?;
+        self.writer.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<Value> {
+        read_response(&mut self.reader)?.ok_or_else(|| invalid("connection closed"))
+    }
+}
+
+/// Results pushed by the daemon after [`Client::subscribe`], ending when the
+/// connection does.
+#[derive(Debug)]
+pub struct Subscription {
+    reader: BufReader<UnixStream>,
+}
+
+impl Iterator for Subscription {
+    type Item = io::Result<Vec<RemoteMatch>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<RemoteMatch>>> {
+        match read_response(&mut self.reader) {
+            Ok(Some(response)) => Some(parse_matches(&response)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn match_request(
+    op: &str,
+    session: &str,
+    query: &str,
+    limit: Option<usize>,
+) -> io::Result<Vec<u8>> {
+    let mut request = Vec::new();
+    write!(request, "{{\"op\":\"{}\",\"session\":", op)?;
+    write_json_str(&mut request, session)?;
+    request.extend_from_slice(b",\"query\":");
+    write_json_str(&mut request, query)?;
+    if let Some(limit) = limit {
+        write!(request, ",\"limit\":{}", limit)?;
+    }
+    request.extend_from_slice(b"}\n");
+    Ok(request)
+}
+
+/// Reads one response line, turning `{"error": ...}` into an error.
+fn read_response<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let response = json::parse(&line).map_err(|e| invalid(&e))?;
+    match response.get("error").and_then(Value::as_str) {
+        Some(err) => Err(io::Error::other(err.to_string())),
+        None => Ok(Some(response)),
+    }
+}
+
+fn parse_matches(response: &Value) -> io::Result<Vec<RemoteMatch>> {
+    let parse = |m: &Value| {
+        Some(RemoteMatch {
+            text: m.get("text")?.as_str()?.to_string(),
+            score: match m.get("score")? {
+                Value::Number(n) => Some(*n),
+                _ => None,
+            },
+            index: m.get("index")?.as_usize()?,
+            positions: (m.get("positions")?.as_array()?.iter())
+                .map(Value::as_usize)
+                .collect::<Option<_>>()?,
+        })
+    };
+    let matches = response
+        .as_array()
+        .ok_or_else(|| invalid("expected an array"))?;
+    matches
+        .iter()
+        .map(|m| parse(m).ok_or_else(|| invalid("malformed match")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::Daemon;
+    use crate::Algorithm;
+    use std::os::unix::net::UnixListener;
+    use std::sync::Arc;
+    use std::{env, fs, process, thread};
+
+    #[test]
+    fn talks_to_daemon() {
+        let path = env::temp_dir().join(format!("fzy-rs-client-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        thread::spawn(move || Arc::new(Daemon::new(Algorithm::Fzy)).serve(listener));
+
+        let mut client = Client::connect(&path).unwrap();
+        assert_eq!(client.load("files", &["app/m/foo", "bar"]).unwrap(), 2);
+        assert_eq!(client.append("files", &["app/models/foo"]).unwrap(), 3);
+        let matches = client.query("files", "amo", Some(1)).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].text, "app/models/foo");
+        assert_eq!(matches[0].index, 2);
+        assert_eq!(matches[0].positions, vec![0, 4, 5]);
+        assert!(client.query("nope", "a", None).is_err());
+
+        let mut updates = Client::connect(&path)
+            .unwrap()
+            .subscribe("files", "bar", None)
+            .unwrap();
+        assert_eq!(updates.next().unwrap().unwrap()[0].score, None);
+        assert_eq!(client.unload("files").unwrap(), 3);
+        assert!(updates.next().unwrap().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
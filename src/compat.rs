@@ -0,0 +1,130 @@
+//! Scoring that reproduces the reference C implementation (`match.c` in
+//! jhawthorn/fzy) bit for bit, for projects migrating from libfzy.
+//!
+//! On top of the shared DP this means:
+//!
+//! - inputs end at the first NUL byte, as C strings do;
+//! - a lowercase query byte matches either case while an uppercase one only
+//!   matches itself;
+//! - candidates longer than `MATCH_MAX_LEN` still match but score `SCORE_MIN`;
+//! - equal length inputs score `SCORE_MAX` without looking at their content.
+
+use crate::{score_dp, Score, SCORE_MAX, SCORE_MIN};
+
+pub const MATCH_MAX_LEN: usize = 1024;
+
+fn c_str(s: &[u8]) -> &[u8] {
+    &s[..s.iter().position(|b| *b == 0).unwrap_or(s.len())]
+}
+
+pub fn has_match<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> bool {
+    let mut text = c_str(text.as_ref());
+    for pc in c_str(pat.as_ref()) {
+        let upper = pc.to_ascii_uppercase();
+        match text.iter().position(|tc| tc == pc || *tc == upper) {
+            Some(i) => text = &text[i + 1..],
+            None => return false,
+        }
+    }
+    true
+}
+
+pub fn score<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> Score {
+    let (pat, text) = (c_str(pat.as_ref()), c_str(text.as_ref()));
+    if pat.is_empty() || text.len() > MATCH_MAX_LEN || pat.len() > text.len() {
+        return SCORE_MIN;
+    }
+    if pat.len() == text.len() {
+        return SCORE_MAX;
+    }
+    score_dp(pat, text)
+}
+
+/// Positions as reported by C fzy's `match_positions`: none for candidates
+/// over `MATCH_MAX_LEN` and every index for equal length inputs.
+pub fn positions<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> Option<Vec<usize>> {
+    let (pat, text) = (pat.as_ref(), text.as_ref());
+    if !has_match(pat, text) {
+        return None;
+    }
+    let (pat, text) = (c_str(pat), c_str(text));
+    if text.len() > MATCH_MAX_LEN {
+        Some(Vec::new())
+    } else if pat.len() == text.len() {
+        Some((0..pat.len()).collect())
+    } else {
+        crate::positions(pat, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Generated with the reference match.c: (query, candidate, has_match,
+    // score bits).
+    const GOLDEN: &[(&[u8], &[u8], bool, u64)] = &[
+        (b"amor", b"app/models/order", true, 0x400cc28f5c28f5c3),
+        (b"amor", b"app/models/zrder", true, 0x40058f5c28f5c293),
+        (b"amo", b"app/m/foo", true, 0x3ffbeb851eb851ec),
+        (b"amo", b"app/models/foo", true, 0x4005d70a3d70a3d9),
+        (b"gemfil", b"Gemfile.lock", true, 0x40177ae147ae147c),
+        (b"gemfil", b"Gemfile", true, 0x4017947ae147ae15),
+        (b"abce", b"abcdef", true, 0x4007147ae147ae15),
+        (b"abce", b"abc de", true, 0x40070a3d70a3d70b),
+        (b"abc", b"    a b c ", true, 0x4002d70a3d70a3d8),
+        (b"abc", b" a  b  c ", true, 0x4002ccccccccccce),
+        (b"abc", b" a b c    ", true, 0x4002d70a3d70a3d8),
+        (b"test", b"tests", true, 0x400f28f5c28f5c29),
+        (b"test", b"testing", true, 0x400f147ae147ae15),
+        (b"test", b"/testing", true, 0x400f0a3d70a3d70b),
+        (b"fbb", b"foo/bar/baz", true, 0x40050a3d70a3d70b),
+        (b"fbb", b"FooBarBaz", true, 0x4002000000000000),
+        (b"fbb", b"foo_bar-baz.rs", true, 0x400351eb851eb853),
+        (b"ss", b"src/sublime.rs", true, 0x3ffb99999999999e),
+        (b"mr", b"src/main.rs", true, 0x3ff6f5c28f5c28f6),
+        (
+            b"mr",
+            b"vendor/foo/bar/src/main.rs",
+            true,
+            0x3ff5c28f5c28f5c3,
+        ),
+        (b"lib", b"src/lib.rs", true, 0x4006eb851eb851ec),
+        (b"tty", b"src/tty.rs", true, 0x4006eb851eb851ec),
+        (b"cfg", b".config/nvim/init.lua", true, 0x3fdfae147ae147ac),
+        (b"abc", b"xyz", false, 0x7ff0000000000000),
+        (b"ab", b"Ab", true, 0x7ff0000000000000),
+        (b"A", b"a", false, 0x7ff0000000000000),
+        (b"a", b"A", true, 0x7ff0000000000000),
+        (b"aB", b"xaxbxB", true, 0x3fe547ae147ae147),
+        (b"x", b"Xbox 360 controller", true, 0x3fe9eb851eb851eb),
+        (b"zz", b"fizz/buzz/jazz", true, 0x3fee147ae147ae14),
+        (b"", b"abc", true, 0xfff0000000000000),
+    ];
+
+    #[test]
+    fn matches_reference_implementation() {
+        for (pat, text, matches, bits) in GOLDEN {
+            assert_eq!(has_match(pat, text), *matches);
+            assert_eq!(score(pat, text).to_bits(), *bits);
+        }
+    }
+
+    #[test]
+    fn long_candidates() {
+        let mut text = vec![b'a'; 1099];
+        text[0] = b'x';
+        assert!(has_match(b"xa", &text));
+        assert_eq!(score(b"xa", &text), SCORE_MIN);
+        assert_eq!(positions(b"xa", &text), Some(vec![]));
+        text.truncate(MATCH_MAX_LEN);
+        assert_eq!(score(b"xa", &text).to_bits(), 0xc009ae147ae1471d);
+    }
+
+    #[test]
+    fn stops_at_nul() {
+        assert!(!has_match(b"ab", b"a\0b"));
+        assert_eq!(score(b"ab\0c", b"ab"), SCORE_MAX);
+        assert_eq!(positions(b"ab", b"ab\0cd"), Some(vec![0, 1]));
+    }
+}
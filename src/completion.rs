@@ -0,0 +1,159 @@
+//! A scorer for identifier completion in the spirit of clangd's: matches are
+//! expected to start words (`fooBar`, `foo_bar`, `Foo2`), prefixes win and an
+//! uppercase query character that matches case exactly earns a bonus.
+
+use crate::{Score, SCORE_MIN};
+
+const MATCH: Score = 1.0;
+const MATCH_HEAD: Score = 2.0;
+const MATCH_PREFIX: Score = 3.0;
+const MATCH_CASE: Score = 1.0;
+const CONSECUTIVE: Score = 1.0;
+const GAP: Score = 1.0;
+const MID_WORD: Score = 3.0;
+const TRAILING: Score = 0.05;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Role {
+    Head,
+    Tail,
+    Separator,
+}
+
+fn roles(text: &[u8]) -> Vec<Role> {
+    let mut prev = b'_';
+    text.iter()
+        .map(|&c| {
+            let role = if !c.is_ascii_alphanumeric() {
+                Role::Separator
+            } else if !prev.is_ascii_alphanumeric()
+                || (c.is_ascii_uppercase() && prev.is_ascii_lowercase())
+                || (c.is_ascii_digit() && !prev.is_ascii_digit())
+            {
+                Role::Head
+            } else {
+                Role::Tail
+            };
+            prev = c;
+            role
+        })
+        .collect()
+}
+
+fn bonus(pc: u8, tc: u8, role: Role, ti: usize) -> Score {
+    let mut bonus = MATCH;
+    if role == Role::Head {
+        bonus += MATCH_HEAD;
+    }
+    if ti == 0 {
+        bonus += MATCH_PREFIX;
+    }
+    if pc.is_ascii_uppercase() && pc == tc {
+        bonus += MATCH_CASE;
+    }
+    bonus
+}
+
+/// Case-insensitively matches `pat` against `text`, returning the score and
+/// matched positions.
+pub(crate) fn match_completion(pat: &[u8], text: &[u8]) -> Option<(Score, Vec<usize>)> {
+    let (m, n) = (pat.len(), text.len());
+    if m == 0 || m > n {
+        return None;
+    }
+    let roles = roles(text);
+
+    // dp[pi * n + ti] is the best score with pat[pi] matched at text[ti], from
+    // the position pat[pi - 1] was matched at.
+    let mut dp = vec![SCORE_MIN; m * n];
+    let mut from = vec![0; m * n];
+    for (pi, pc) in pat.iter().enumerate() {
+        let mut best = SCORE_MIN;
+        let mut best_at = 0;
+        for (ti, tc) in text.iter().enumerate() {
+            if pi > 0 && ti >= 2 && dp[(pi - 1) * n + ti - 2] > best {
+                best = dp[(pi - 1) * n + ti - 2];
+                best_at = ti - 2;
+            }
+            if !pc.eq_ignore_ascii_case(tc) {
+                continue;
+            }
+            let mid_word = if roles[ti] == Role::Tail {
+                MID_WORD
+            } else {
+                0.0
+            };
+            let bonus = bonus(*pc, *tc, roles[ti], ti);
+            let cell = pi * n + ti;
+            if pi == 0 {
+                dp[cell] = bonus - mid_word;
+                continue;
+            }
+            let consecutive = if ti > 0 {
+                dp[(pi - 1) * n + ti - 1] + CONSECUTIVE
+            } else {
+                SCORE_MIN
+            };
+            let jump = best - GAP - mid_word;
+            if consecutive >= jump {
+                dp[cell] = consecutive + bonus;
+                from[cell] = ti.saturating_sub(1);
+            } else {
+                dp[cell] = jump + bonus;
+                from[cell] = best_at;
+            }
+        }
+    }
+
+    let (score, mut ti) = (0..n)
+        .map(|ti| (dp[(m - 1) * n + ti] - TRAILING * (n - 1 - ti) as Score, ti))
+        .fold(
+            (SCORE_MIN, 0),
+            |best, cur| if cur.0 > best.0 { cur } else { best },
+        );
+    if score == SCORE_MIN {
+        return None;
+    }
+    let mut positions = vec![0; m];
+    for pi in (0..m).rev() {
+        positions[pi] = ti;
+        ti = from[pi * n + ti];
+    }
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pat: &[u8], text: &[u8]) -> Score {
+        match_completion(pat, text).map_or(SCORE_MIN, |(score, _)| score)
+    }
+
+    #[test]
+    fn prefers_word_starts() {
+        assert!(score(b"fb", b"fooBar") > score(b"fb", b"fobar"));
+        assert!(score(b"fb", b"foo_bar") > score(b"fb", b"fbxyz_q"));
+        assert_eq!(match_completion(b"fb", b"fooBar").unwrap().1, vec![0, 3]);
+        assert_eq!(match_completion(b"ob", b"fooBar").unwrap().1, vec![2, 3]);
+    }
+
+    #[test]
+    fn prefers_prefixes() {
+        assert!(score(b"foo", b"fooBar") > score(b"foo", b"barFoo"));
+        assert!(score(b"get", b"getName") > score(b"get", b"doGet"));
+    }
+
+    #[test]
+    fn rewards_matching_case() {
+        assert!(score(b"FB", b"FooBar") > score(b"FB", b"fooBar"));
+        assert_eq!(score(b"fb", b"FooBar"), score(b"fb", b"fooBar"));
+    }
+
+    #[test]
+    fn rejects_non_matches() {
+        assert_eq!(match_completion(b"bf", b"fooBar"), None);
+        assert_eq!(match_completion(b"", b"fooBar"), None);
+        assert_eq!(match_completion(b"foobarx", b"fooBar"), None);
+    }
+}
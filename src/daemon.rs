@@ -0,0 +1,566 @@
+//! A long-lived matcher listening on a Unix domain socket. It keeps named
+//! sessions in memory, each with its own candidates and active query, so
+//! that one daemon can back several pickers and clients only send queries.
+//!
+//! Requests and responses are JSON objects, one per line:
+//!
+//! - `{"op": "load", "session": NAME, "items": [...]}` replaces a session's
+//!   candidates and `"append"` adds to them, both answered with `{"len": N}`.
+//!   `load` takes an optional `algorithm`, the daemon's default otherwise;
+//! - `{"op": "unload", "session": NAME}` frees a session;
+//! - `{"op": "match", "session": NAME, "query": Q}` makes `Q` the session's
+//!   active query and is answered with the array written by
+//!   [`write_json`](crate::write_json), at most `limit` long if given.
+//!   Without `query` the active query is used;
+//! - `"subscribe"` takes the same fields as `"match"` and answers the same
+//!   way, then again whenever the session's candidates or active query
+//!   change. A subscribed connection accepts no further requests;
+//! - `{"op": "stats"}` is answered with `{"sessions": N, "memory": BYTES,
+//!   "limit": BYTES}`, `limit` being `null` without a memory limit.
+//!
+//! With a [memory limit](Daemon::with_memory_limit), a `load` or `append`
+//! that would take the candidates of all sessions over it is refused as a
+//! whole, leaving the session as it was or not creating it.
+//!
+//! `session` defaults to `""`. Failed requests are answered with
+//! `{"error": MESSAGE}`.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread;
+
+use crate::algorithm::{match_candidate, rank};
+use crate::json::{self, Value};
+use crate::output::json_error;
+use crate::ranked::merge;
+use crate::{write_json, Algorithm, Match};
+
+/// A candidate set with the ranking for its active query.
+#[derive(Debug, Default)]
+struct Session {
+    algorithm: Algorithm,
+    items: Vec<Vec<u8>>,
+    query: Vec<u8>,
+    matches: Vec<Match>,
+    /// Bumped whenever `matches` may have changed.
+    generation: u64,
+    /// Memory taken by `items`, see [`item_bytes`].
+    bytes: usize,
+}
+
+/// A session's candidates ranked against its active query, as seen by
+/// [`Daemon::ranking`].
+pub(crate) struct Ranking<'a> {
+    pub algorithm: &'a Algorithm,
+    pub query: &'a [u8],
+    pub items: &'a [Vec<u8>],
+    pub matches: &'a [Match],
+}
+
+/// Roughly the memory a candidate takes in a session: its text, the vector
+/// holding it and its match.
+fn item_bytes(text: &[u8]) -> usize {
+    text.len() + mem::size_of::<Vec<u8>>() + mem::size_of::<Match>()
+}
+
+impl Session {
+    /// Adds candidates, merging their matches into the ranking in one pass.
+    fn append(&mut self, items: Vec<Vec<u8>>) {
+        let mut new = Vec::new();
+        for text in items {
+            let index = self.items.len();
+            new.extend(match_candidate(&self.algorithm, &self.query, &text, index));
+            self.bytes += item_bytes(&text);
+            self.items.push(text);
+        }
+        new.sort_by(Match::rank_cmp);
+        merge(&mut self.matches, new, Match::rank_cmp);
+        self.generation += 1;
+    }
+
+    fn set_query(&mut self, query: &[u8]) {
+        if query != self.query {
+            self.query = query.to_vec();
+            self.matches = rank(&self.algorithm, query, &self.items);
+            self.generation += 1;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Daemon {
+    algorithm: Algorithm,
+    sessions: RwLock<HashMap<String, Arc<Mutex<Session>>>>,
+    /// Bumped and notified on every change to any session.
+    changes: (Mutex<u64>, Condvar),
+    memory_limit: Option<usize>,
+    /// Memory taken by the candidates of all sessions. Locked after a
+    /// session, never before.
+    memory: Mutex<usize>,
+}
+
+impl Daemon {
+    /// A daemon ranking with `algorithm` unless a session asks for another.
+    pub fn new(algorithm: Algorithm) -> Daemon {
+        Daemon {
+            algorithm,
+            sessions: RwLock::default(),
+            changes: Default::default(),
+            memory_limit: None,
+            memory: Mutex::new(0),
+        }
+    }
+
+    /// Refuses candidates that would take more than `bytes` of memory across
+    /// all sessions, see [`memory`](Daemon::memory).
+    pub fn with_memory_limit(self, bytes: usize) -> Daemon {
+        Daemon {
+            memory_limit: Some(bytes),
+            ..self
+        }
+    }
+
+    /// The algorithm of sessions loaded without one.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Roughly the memory taken by the candidates of all sessions: their
+    /// texts and a few words each.
+    pub fn memory(&self) -> usize {
+        *self.memory.lock().unwrap()
+    }
+
+    /// Serves connections from `listener` forever, one thread each.
+    pub fn serve(self: Arc<Self>, listener: UnixListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let daemon = Arc::clone(&self);
+            thread::spawn(move || daemon.handle(stream));
+        }
+        Ok(())
+    }
+
+    fn handle(&self, stream: UnixStream) -> io::Result<()> {
+        let mut out = &stream;
+        for line in BufReader::new(&stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = match json::parse(&line) {
+                Ok(request) => request,
+                Err(err) => {
+                    out.write_all(&json_error(&err))?;
+                    continue;
+                }
+            };
+            // Read before answering so that no change slips in between.
+            let seen = *self.changes.0.lock().unwrap();
+            let (response, generation) = self.answer(&request, true);
+            out.write_all(&response)?;
+            if request.get("op").and_then(Value::as_str) == Some("subscribe") {
+                return self.push_changes(out, &request, seen, generation);
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers `request` again each time its session changes.
+    fn push_changes(
+        &self,
+        mut out: &UnixStream,
+        request: &Value,
+        mut seen: u64,
+        mut pushed: Option<u64>,
+    ) -> io::Result<()> {
+        loop {
+            let (lock, cvar) = &self.changes;
+            seen = *cvar
+                .wait_while(lock.lock().unwrap(), |g| *g == seen)
+                .unwrap();
+            let name = request.get("session").and_then(Value::as_str).unwrap_or("");
+            let session = self.sessions.read().unwrap().get(name).cloned();
+            let generation = session.map(|s| s.lock().unwrap().generation);
+            if generation != pushed {
+                let (response, generation) = self.answer(request, false);
+                pushed = generation;
+                out.write_all(&response)?;
+            }
+        }
+    }
+
+    fn changed(&self) {
+        *self.changes.0.lock().unwrap() += 1;
+        self.changes.1.notify_all();
+    }
+
+    /// Answers a single request line, the response ending with a newline.
+    pub fn respond(&self, request: &str) -> Vec<u8> {
+        match json::parse(request) {
+            Ok(request) => self.answer(&request, true).0,
+            Err(err) => json_error(&err),
+        }
+    }
+
+    /// The response to `request`, along with the generation of the session
+    /// it reflects for matches. Subscriptions only set the query once.
+    fn answer(&self, request: &Value, set_query: bool) -> (Vec<u8>, Option<u64>) {
+        match self.try_answer(request, set_query) {
+            Ok(answer) => answer,
+            Err(err) => (json_error(&err), None),
+        }
+    }
+
+    fn try_answer(
+        &self,
+        request: &Value,
+        set_query: bool,
+    ) -> Result<(Vec<u8>, Option<u64>), String> {
+        let op = request
+            .get("op")
+            .and_then(Value::as_str)
+            .ok_or("missing op")?;
+        let name = match request.get("session") {
+            None => "",
+            Some(name) => name.as_str().ok_or("session must be a string")?,
+        };
+        match op {
+            "load" | "append" => {
+                let items = items(request)?;
+                let algorithm = match request.get("algorithm") {
+                    None => None,
+                    Some(name) => Some(name.as_str().ok_or("algorithm must be a string")?.parse()?),
+                };
+                let len = self.load(name, items, algorithm, op == "load")?;
+                Ok((format!("{{\"len\":{}}}\n", len).into_bytes(), None))
+            }
+            "unload" => {
+                let session = self.sessions.write().unwrap().remove(name);
+                let len = session.map_or(0, |s| {
+                    let session = s.lock().unwrap();
+                    *self.memory.lock().unwrap() -= session.bytes;
+                    session.items.len()
+                });
+                self.changed();
+                Ok((format!("{{\"len\":{}}}\n", len).into_bytes(), None))
+            }
+            "match" | "subscribe" => {
+                let query = match request.get("query") {
+                    Some(query) if set_query => {
+                        Some(query.as_str().ok_or("query must be a string")?.as_bytes())
+                    }
+                    _ => None,
+                };
+                let limit = match request.get("limit") {
+                    None => None,
+                    Some(limit) => Some(limit.as_usize().ok_or("limit must be a count")?),
+                };
+                let (out, generation) = self.ranking(name, query, |ranking| {
+                    let len = limit.map_or(ranking.matches.len(), |limit| {
+                        limit.min(ranking.matches.len())
+                    });
+                    let matches = &ranking.matches[..len];
+                    let mut out = Vec::new();
+                    write_json(
+                        &mut out,
+                        ranking.algorithm,
+                        ranking.query,
+                        ranking.items,
+                        matches,
+                    )
+                    .map(|()| out)
+                })?;
+                Ok((out.map_err(|e| e.to_string())?, Some(generation)))
+            }
+            "stats" => {
+                let sessions = self.sessions.read().unwrap().len();
+                let limit = self
+                    .memory_limit
+                    .map_or("null".to_string(), |l| l.to_string());
+                let stats = format!(
+                    "{{\"sessions\":{},\"memory\":{},\"limit\":{}}}\n",
+                    sessions,
+                    self.memory(),
+                    limit
+                );
+                Ok((stats.into_bytes(), None))
+            }
+            _ => Err(format!("unknown op: {}", op)),
+        }
+    }
+
+    /// Replaces the candidates of session `name` with `items`, or with
+    /// `replace` off adds them, creating the session if needed. `algorithm`
+    /// defaults to the daemon's. Returns how many candidates the session has.
+    pub(crate) fn load(
+        &self,
+        name: &str,
+        items: Vec<Vec<u8>>,
+        algorithm: Option<Algorithm>,
+        replace: bool,
+    ) -> Result<usize, String> {
+        let algorithm = algorithm.unwrap_or(self.algorithm);
+        let added = items.iter().map(|text| item_bytes(text)).sum();
+        let len = loop {
+            let shared = {
+                let mut sessions = self.sessions.write().unwrap();
+                match sessions.get(name) {
+                    Some(session) => Arc::clone(session),
+                    None => {
+                        // A session is only created once its candidates fit.
+                        self.reserve(0, added)?;
+                        let mut session = Session {
+                            algorithm,
+                            ..Session::default()
+                        };
+                        session.append(items);
+                        let len = session.items.len();
+                        sessions.insert(name.to_string(), Arc::new(Mutex::new(session)));
+                        break len;
+                    }
+                }
+            };
+            let mut session = shared.lock().unwrap();
+            // An unload that got to the session first has already taken its
+            // memory back, so start over with a new one.
+            let current =
+                (self.sessions.read().unwrap().get(name)).is_some_and(|s| Arc::ptr_eq(s, &shared));
+            if !current {
+                continue;
+            }
+            let freed = if replace { session.bytes } else { 0 };
+            self.reserve(freed, added)?;
+            if replace {
+                let generation = session.generation;
+                *session = Session {
+                    algorithm,
+                    generation,
+                    ..Session::default()
+                };
+            }
+            session.append(items);
+            break session.items.len();
+        };
+        self.changed();
+        Ok(len)
+    }
+
+    /// Calls `f` with the ranking of session `name`, first making `query`
+    /// its active query if given. Also returns the session's generation.
+    pub(crate) fn ranking<R, F>(
+        &self,
+        name: &str,
+        query: Option<&[u8]>,
+        f: F,
+    ) -> Result<(R, u64), String>
+    where
+        F: FnOnce(Ranking) -> R,
+    {
+        let session = self.sessions.read().unwrap().get(name).cloned();
+        let session = session.ok_or_else(|| format!("no such session: {}", name))?;
+        let mut session = session.lock().unwrap();
+        if let Some(query) = query {
+            let generation = session.generation;
+            session.set_query(query);
+            if session.generation != generation {
+                self.changed();
+            }
+        }
+        let ranking = Ranking {
+            algorithm: &session.algorithm,
+            query: &session.query,
+            items: &session.items,
+            matches: &session.matches,
+        };
+        Ok((f(ranking), session.generation))
+    }
+
+    /// Accounts for a session dropping `freed` bytes of candidates and
+    /// taking `added` more, unless that goes over the memory limit.
+    fn reserve(&self, freed: usize, added: usize) -> Result<(), String> {
+        let mut memory = self.memory.lock().unwrap();
+        let after = *memory - freed + added;
+        if let Some(limit) = self.memory_limit {
+            if after > limit {
+                return Err(format!(
+                    "memory limit exceeded: {} of {} bytes in use, {} more requested",
+                    *memory, limit, added
+                ));
+            }
+        }
+        *memory = after;
+        Ok(())
+    }
+}
+
+fn items(request: &Value) -> Result<Vec<Vec<u8>>, String> {
+    (request.get("items").and_then(Value::as_array))
+        .ok_or("missing array field: items")?
+        .iter()
+        .map(|item| item.as_str().map(|s| s.as_bytes().to_vec()))
+        .collect::<Option<_>>()
+        .ok_or_else(|| "items must be strings".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn respond(daemon: &Daemon, request: &str) -> String {
+        String::from_utf8(daemon.respond(request)).unwrap()
+    }
+
+    #[test]
+    fn keeps_sessions() {
+        let daemon = Daemon::new(Algorithm::Fzy);
+        assert_eq!(
+            respond(
+                &daemon,
+                r#"{"op":"load","session":"files","items":["a/b","c"]}"#
+            ),
+            "{\"len\":2}\n"
+        );
+        assert_eq!(
+            respond(
+                &daemon,
+                r#"{"op":"append","session":"files","items":["ab"]}"#
+            ),
+            "{\"len\":3}\n"
+        );
+        assert_eq!(
+            respond(
+                &daemon,
+                r#"{"op":"match","session":"files","query":"ab","limit":1}"#
+            ),
+            "[{\"text\":\"ab\",\"score\":null,\"index\":2,\"positions\":[0,1]}]\n"
+        );
+        assert_eq!(
+            respond(&daemon, r#"{"op":"unload","session":"files"}"#),
+            "{\"len\":3}\n"
+        );
+        assert_eq!(
+            respond(&daemon, r#"{"op":"match","session":"files","query":"ab"}"#),
+            "{\"error\":\"no such session: files\"}\n"
+        );
+        assert_eq!(
+            respond(&daemon, r#"{"op":"frob"}"#),
+            "{\"error\":\"unknown op: frob\"}\n"
+        );
+    }
+
+    #[test]
+    fn limits_memory() {
+        let limit = 3 * item_bytes(b"ab");
+        let daemon = Daemon::new(Algorithm::Fzy).with_memory_limit(limit);
+        respond(
+            &daemon,
+            r#"{"op":"load","session":"a","items":["ab","cd"]}"#,
+        );
+        assert_eq!(daemon.memory(), 2 * item_bytes(b"ab"));
+        assert!(respond(
+            &daemon,
+            r#"{"op":"append","session":"b","items":["ef","gh"]}"#
+        )
+        .starts_with("{\"error\":\"memory limit exceeded"));
+        assert_eq!(
+            respond(&daemon, r#"{"op":"match","session":"b"}"#),
+            "{\"error\":\"no such session: b\"}\n"
+        );
+        assert_eq!(
+            respond(
+                &daemon,
+                r#"{"op":"load","session":"a","items":["x","y","z"]}"#
+            ),
+            "{\"len\":3}\n"
+        );
+        assert_eq!(daemon.memory(), 3 * item_bytes(b"x"));
+        respond(&daemon, r#"{"op":"unload","session":"a"}"#);
+        assert_eq!(
+            respond(&daemon, r#"{"op":"stats"}"#),
+            format!("{{\"sessions\":0,\"memory\":0,\"limit\":{}}}\n", limit)
+        );
+    }
+
+    #[test]
+    fn serves_socket() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let daemon = Daemon::new(Algorithm::Fzy);
+        let handle = thread::spawn(move || daemon.handle(server));
+        let mut client = client;
+        client
+            .write_all(b"{\"op\":\"load\",\"items\":[\"x\"]}\n{\"op\":\"match\",\"query\":\"\"}\n")
+            .unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert_eq!(
+            response,
+            "{\"len\":1}\n[{\"text\":\"x\",\"score\":null,\"index\":0,\"positions\":[]}]\n"
+        );
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn pushes_to_subscribers() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let daemon = Arc::new(Daemon::new(Algorithm::Fzy));
+        daemon.respond(r#"{"op":"load","items":["a"]}"#);
+        let subscribed = Arc::clone(&daemon);
+        thread::spawn(move || subscribed.handle(server));
+
+        let mut client = client;
+        client
+            .write_all(b"{\"op\":\"subscribe\",\"query\":\"b\"}\n")
+            .unwrap();
+        let mut lines = BufReader::new(client).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "[]");
+        daemon.respond(r#"{"op":"append","items":["b"]}"#);
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            "[{\"text\":\"b\",\"score\":null,\"index\":1,\"positions\":[0]}]"
+        );
+        // Other sessions don't wake this one's subscribers.
+        daemon.respond(r#"{"op":"load","session":"other","items":["c"]}"#);
+        daemon.respond(r#"{"op":"match","query":""}"#);
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            "[{\"text\":\"a\",\"score\":null,\"index\":0,\"positions\":[]},\
+             {\"text\":\"b\",\"score\":null,\"index\":1,\"positions\":[]}]"
+        );
+    }
+
+    #[test]
+    fn sessions_keep_their_queries() {
+        let daemon = Daemon::new(Algorithm::Fzy);
+        respond(
+            &daemon,
+            r#"{"op":"load","session":"files","items":["a","b"]}"#,
+        );
+        respond(
+            &daemon,
+            r#"{"op":"load","session":"buffers","items":["a","b"],"algorithm":"fzf-v1"}"#,
+        );
+        respond(&daemon, r#"{"op":"match","session":"files","query":"a"}"#);
+        respond(&daemon, r#"{"op":"match","session":"buffers","query":"b"}"#);
+        assert_eq!(
+            respond(&daemon, r#"{"op":"match","session":"files"}"#),
+            "[{\"text\":\"a\",\"score\":null,\"index\":0,\"positions\":[0]}]\n"
+        );
+        assert_eq!(
+            respond(
+                &daemon,
+                r#"{"op":"append","session":"buffers","items":["bb"]}"#
+            ),
+            "{\"len\":3}\n"
+        );
+        assert_eq!(
+            respond(&daemon, r#"{"op":"match","session":"buffers","limit":1}"#),
+            "[{\"text\":\"b\",\"score\":32,\"index\":1,\"positions\":[0]}]\n"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Keeping a ranked set of paths current from add, remove and rename events
+//! instead of rebuilding it.
+//!
+//! A feed of events has one per line: `+ PATH`, `- PATH` or `> FROM\tTO`,
+//! with paths escaped as in history files.
+
+use std::collections::HashMap;
+
+use crate::history::unescape;
+use crate::{Algorithm, FuzzyAlgorithm, RankedSet};
+
+/// A change to the paths of a [`PathIndex`], as reported by a file watcher.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delta {
+    Add(Vec<u8>),
+    Remove(Vec<u8>),
+    Rename { from: Vec<u8>, to: Vec<u8> },
+}
+
+impl Delta {
+    /// Parses a line of an event feed, without its terminator.
+    pub fn parse(line: &[u8]) -> Option<Delta> {
+        let (kind, rest) = (line.get(..2)?, &line[2..]);
+        match kind {
+            b"+ " => Some(Delta::Add(unescape(rest))),
+            b"- " => Some(Delta::Remove(unescape(rest))),
+            b"> " => {
+                let tab = rest.iter().position(|&b| b == b'\t')?;
+                Some(Delta::Rename {
+                    from: unescape(&rest[..tab]),
+                    to: unescape(&rest[tab + 1..]),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A [`RankedSet`] of distinct paths, updated by [`Delta`]s.
+///
+/// Each delta only scores the paths it names. A renamed path keeps its id,
+/// and so its pin.
+#[derive(Debug, Default)]
+pub struct PathIndex<A = Algorithm> {
+    set: RankedSet<A>,
+    ids: HashMap<Vec<u8>, usize>,
+}
+
+impl PathIndex {
+    pub fn new(query: &[u8]) -> PathIndex {
+        PathIndex::with_algorithm(query, Algorithm::Fzy)
+    }
+}
+
+impl<A: FuzzyAlgorithm> PathIndex<A> {
+    pub fn with_algorithm(query: &[u8], algorithm: A) -> PathIndex<A> {
+        PathIndex {
+            set: RankedSet::with_algorithm(query, algorithm),
+            ids: HashMap::new(),
+        }
+    }
+
+    /// The ranked paths.
+    pub fn set(&self) -> &RankedSet<A> {
+        &self.set
+    }
+
+    /// The id of `path` in [`set`](PathIndex::set).
+    pub fn id(&self, path: &[u8]) -> Option<usize> {
+        self.ids.get(path).copied()
+    }
+
+    pub fn set_query(&mut self, query: &[u8]) {
+        self.set.set_query(query);
+    }
+
+    /// Applies a delta, returning whether it changed anything: adding a path
+    /// already there, or removing or renaming one that isn't, does nothing.
+    /// Renaming onto an existing path replaces it.
+    pub fn apply(&mut self, delta: Delta) -> bool {
+        match delta {
+            Delta::Add(path) => {
+                if self.ids.contains_key(&path) {
+                    return false;
+                }
+                let id = self.set.insert(&path);
+                self.ids.insert(path, id);
+            }
+            Delta::Remove(path) => {
+                let Some(id) = self.ids.remove(&path) else {
+                    return false;
+                };
+                self.set.remove(id);
+            }
+            Delta::Rename { from, to } => {
+                if from == to || !self.ids.contains_key(&from) {
+                    return false;
+                }
+                if let Some(replaced) = self.ids.remove(&to) {
+                    self.set.remove(replaced);
+                }
+                let id = self.ids.remove(&from).unwrap();
+                self.set.replace(id, &to);
+                self.ids.insert(to, id);
+            }
+        }
+        true
+    }
+}
+
+impl<A: FuzzyAlgorithm> Extend<Delta> for PathIndex<A> {
+    fn extend<I: IntoIterator<Item = Delta>>(&mut self, deltas: I) {
+        for delta in deltas {
+            self.apply(delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(index: &PathIndex) -> Vec<&[u8]> {
+        index.set().iter().map(|(t, _)| t).collect()
+    }
+
+    #[test]
+    fn applies_deltas() {
+        let mut index = PathIndex::new(b"rs");
+        let feed: &[u8] = b"+ src/lib.rs\n+ src/main.rs\n+ README\n> src/main.rs\tsrc/bin.rs";
+        index.extend(feed.split(|&b| b == b'\n').filter_map(Delta::parse));
+        assert_eq!(paths(&index), vec![&b"src/lib.rs"[..], &b"src/bin.rs"[..]]);
+        assert_eq!(index.id(b"src/bin.rs"), Some(1));
+        assert!(!index.apply(Delta::Add(b"README".to_vec())));
+        assert!(index.apply(Delta::Rename {
+            from: b"src/bin.rs".to_vec(),
+            to: b"src/lib.rs".to_vec(),
+        }));
+        assert_eq!(index.set().total(), 2);
+        assert!(index.apply(Delta::Remove(b"src/lib.rs".to_vec())));
+        assert!(!index.apply(Delta::Remove(b"src/lib.rs".to_vec())));
+        assert_eq!(index.set().total(), 1);
+        assert_eq!(Delta::parse(b"* x"), None);
+    }
+}
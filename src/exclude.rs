@@ -0,0 +1,113 @@
+/// A pattern removing candidates before they are ranked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exclude {
+    /// Excludes candidates containing these bytes anywhere, e.g. `target/`.
+    Substring(Vec<u8>),
+    /// Excludes candidates matched as a whole by a glob where `*` stands for
+    /// any run of bytes and `?` for any one byte, e.g. `*.min.js`.
+    Glob(Vec<u8>),
+}
+
+impl Exclude {
+    /// A glob if `pattern` contains `*` or `?`, a substring otherwise.
+    pub fn new(pattern: &str) -> Exclude {
+        let bytes = pattern.as_bytes().to_vec();
+        if pattern.contains(['*', '?']) {
+            Exclude::Glob(bytes)
+        } else {
+            Exclude::Substring(bytes)
+        }
+    }
+
+    pub fn matches(&self, text: &[u8]) -> bool {
+        match self {
+            Exclude::Substring(needle) => {
+                needle.is_empty() || text.windows(needle.len()).any(|w| w == &needle[..])
+            }
+            Exclude::Glob(glob) => glob_matches(glob, text),
+        }
+    }
+}
+
+/// Whether the file name at the end of `path` ends in `.ext`, compared
+/// ignoring ASCII case. `ext` may span several dots, as in `tar.gz`, and the
+/// leading dot of a hidden file doesn't start an extension.
+pub fn has_extension(path: &[u8], ext: &[u8]) -> bool {
+    let ext = ext.strip_prefix(b".").unwrap_or(ext);
+    let name = match path.iter().rposition(|b| *b == b'/') {
+        Some(slash) => &path[slash + 1..],
+        None => path,
+    };
+    let stem_start = name.iter().position(|b| *b != b'.').unwrap_or(name.len());
+    let name = &name[stem_start..];
+    !ext.is_empty()
+        && name.len() > ext.len() + 1
+        && name[name.len() - ext.len() - 1] == b'.'
+        && name[name.len() - ext.len()..].eq_ignore_ascii_case(ext)
+}
+
+/// Iterative wildcard matching, backtracking only to the last `*`.
+fn glob_matches(glob: &[u8], text: &[u8]) -> bool {
+    let (mut gi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < text.len() {
+        match glob.get(gi) {
+            Some(b'*') => {
+                star = Some((gi, ti));
+                gi += 1;
+            }
+            Some(b'?') => {
+                gi += 1;
+                ti += 1;
+            }
+            Some(b) if *b == text[ti] => {
+                gi += 1;
+                ti += 1;
+            }
+            _ => match star {
+                Some((sg, st)) => {
+                    star = Some((sg, st + 1));
+                    gi = sg + 1;
+                    ti = st + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[gi..].iter().all(|b| *b == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_patterns() {
+        let target = Exclude::new("target/");
+        assert_eq!(target, Exclude::Substring(b"target/".to_vec()));
+        assert!(target.matches(b"crate/target/debug/fzy"));
+        assert!(!target.matches(b"src/target.rs"));
+
+        let minified = Exclude::new("*.min.js");
+        assert!(minified.matches(b"static/app.min.js"));
+        assert!(!minified.matches(b"static/app.min.js.map"));
+        assert!(!minified.matches(b"static/app.js"));
+
+        assert!(Exclude::new("a?c*").matches(b"abcdef"));
+        assert!(Exclude::new("*a*b").matches(b"xxaxxab"));
+        assert!(!Exclude::new("a?c").matches(b"ac"));
+    }
+
+    #[test]
+    fn finds_extensions() {
+        assert!(has_extension(b"src/lib.rs", b"rs"));
+        assert!(has_extension(b"src/lib.RS", b".rs"));
+        assert!(has_extension(b"dist/app.tar.gz", b"gz"));
+        assert!(has_extension(b"dist/app.tar.gz", b"tar.gz"));
+        assert!(!has_extension(b"dist/app.tar.gz", b"tar"));
+        assert!(!has_extension(b"rs", b"rs"));
+        assert!(!has_extension(b"home/.rs", b"rs"));
+        assert!(has_extension(b"home/.eslintrc.json", b"json"));
+        assert!(!has_extension(b"src.rs/main", b"rs"));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{
+    compute_bonuses, positions, score, Score, SCORE_GAP_INNER, SCORE_GAP_LEADING,
+    SCORE_GAP_TRAILING, SCORE_MATCH_CONSECUTIVE,
+};
+
+/// What one matched byte adds to the score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contribution {
+    /// Index into the candidate.
+    pub position: usize,
+    /// Penalty for the unmatched bytes since the previous match, or since the
+    /// start of the candidate for the first one.
+    pub gap: Score,
+    /// Bonus for where the byte sits: after a slash, a separator, a dot, or a
+    /// lowercase letter when it is a capital. Zero for consecutive matches.
+    pub bonus: Score,
+    /// Bonus for directly following the previous match.
+    pub consecutive: Score,
+}
+
+impl Contribution {
+    pub fn total(&self) -> Score {
+        self.gap + self.bonus + self.consecutive
+    }
+}
+
+/// How [`score`] arrived at a candidate's score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// The pattern that was matched, after any rewriting.
+    pub query: Vec<u8>,
+    pub score: Score,
+    pub contributions: Vec<Contribution>,
+    /// Penalty for the unmatched bytes after the last match.
+    pub trailing_gap: Score,
+}
+
+/// An explanation's contributions summed by kind.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Breakdown {
+    pub leading_gap: Score,
+    pub inner_gap: Score,
+    pub trailing_gap: Score,
+    pub bonus: Score,
+    pub consecutive: Score,
+}
+
+impl Breakdown {
+    pub fn total(&self) -> Score {
+        self.leading_gap + self.inner_gap + self.trailing_gap + self.bonus + self.consecutive
+    }
+
+    fn sub(&self, other: &Breakdown) -> Breakdown {
+        Breakdown {
+            leading_gap: self.leading_gap - other.leading_gap,
+            inner_gap: self.inner_gap - other.inner_gap,
+            trailing_gap: self.trailing_gap - other.trailing_gap,
+            bonus: self.bonus - other.bonus,
+            consecutive: self.consecutive - other.consecutive,
+        }
+    }
+}
+
+impl Explanation {
+    pub fn breakdown(&self) -> Breakdown {
+        let mut breakdown = Breakdown {
+            trailing_gap: self.trailing_gap,
+            ..Breakdown::default()
+        };
+        for (i, c) in self.contributions.iter().enumerate() {
+            if i == 0 {
+                breakdown.leading_gap = c.gap;
+            } else {
+                breakdown.inner_gap += c.gap;
+            }
+            breakdown.bonus += c.bonus;
+            breakdown.consecutive += c.consecutive;
+        }
+        breakdown
+    }
+}
+
+/// Why one candidate scores differently from another, see
+/// [`explain_comparison`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub a: Option<Explanation>,
+    pub b: Option<Explanation>,
+    /// `a`'s breakdown less `b`'s when both match: positive fields are where
+    /// `a` gained on `b`.
+    pub difference: Option<Breakdown>,
+}
+
+/// Explains both candidates and how their score breakdowns differ.
+pub fn explain_comparison<P, A, B>(pat: P, a: A, b: B) -> Comparison
+where
+    P: AsRef<[u8]>,
+    A: AsRef<[u8]>,
+    B: AsRef<[u8]>,
+{
+    let pat = pat.as_ref();
+    let (a, b) = (explain(pat, a), explain(pat, b));
+    let difference = match (&a, &b) {
+        (Some(a), Some(b)) => Some(a.breakdown().sub(&b.breakdown())),
+        _ => None,
+    };
+    Comparison { a, b, difference }
+}
+
+/// Breaks the score of `text` down by matched byte. Contributions and the
+/// trailing gap add up to `score` up to rounding, except for the special
+/// cases `score` doesn't compute: an empty `pat` or one equal to `text`.
+pub fn explain<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> Option<Explanation> {
+    let (pat, text) = (pat.as_ref(), text.as_ref());
+    let positions = positions(pat, text)?;
+    let bonuses = compute_bonuses(text);
+    let mut contributions = Vec::with_capacity(positions.len());
+    let mut prev: Option<usize> = None;
+    for &ti in &positions {
+        let contribution = match prev {
+            None => Contribution {
+                position: ti,
+                gap: ti as Score * SCORE_GAP_LEADING,
+                bonus: bonuses[ti],
+                consecutive: 0.0,
+            },
+            Some(prev) if ti == prev + 1 => Contribution {
+                position: ti,
+                gap: 0.0,
+                bonus: 0.0,
+                consecutive: SCORE_MATCH_CONSECUTIVE,
+            },
+            Some(prev) => Contribution {
+                position: ti,
+                gap: (ti - prev - 1) as Score * SCORE_GAP_INNER,
+                bonus: bonuses[ti],
+                consecutive: 0.0,
+            },
+        };
+        contributions.push(contribution);
+        prev = Some(ti);
+    }
+    let trailing_gap = match prev {
+        Some(last) => (text.len() - last - 1) as Score * SCORE_GAP_TRAILING,
+        None => 0.0,
+    };
+    Some(Explanation {
+        query: pat.to_vec(),
+        score: score(pat, text),
+        contributions,
+        trailing_gap,
+    })
+}
+
+/// The matched bytes as `(pattern index, text index)` pairs, in pattern order.
+pub fn alignment<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> Option<Vec<(usize, usize)>> {
+    Some(positions(pat, text)?.into_iter().enumerate().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_up_to_score() {
+        for (pat, text) in [
+            (&b"amor"[..], &b"app/models/order"[..]),
+            (b"amo", b"app/m/foo"),
+            (b"fbb", b"FooBarBaz"),
+            (b"gemfil", b"Gemfile.lock"),
+            (b"cfg", b".config/nvim/init.lua"),
+        ] {
+            let explanation = explain(pat, text).unwrap();
+            let total: Score = explanation
+                .contributions
+                .iter()
+                .map(Contribution::total)
+                .sum::<Score>()
+                + explanation.trailing_gap;
+            assert!((total - explanation.score).abs() < 1e-9, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn splits_contributions() {
+        let explanation = explain(b"amo", b"app/models/foo").unwrap();
+        assert_eq!(
+            explanation.contributions,
+            vec![
+                Contribution {
+                    position: 0,
+                    gap: 0.0,
+                    bonus: 0.9,
+                    consecutive: 0.0
+                },
+                Contribution {
+                    position: 4,
+                    gap: 3.0 * -0.01,
+                    bonus: 0.9,
+                    consecutive: 0.0
+                },
+                Contribution {
+                    position: 5,
+                    gap: 0.0,
+                    bonus: 0.0,
+                    consecutive: 1.0
+                },
+            ]
+        );
+        assert_eq!(explanation.trailing_gap, 8.0 * -0.005);
+        assert_eq!(explain(b"x", b"abc"), None);
+    }
+
+    #[test]
+    fn compares_candidates() {
+        let comparison = explain_comparison(b"amo", b"app/models/foo", b"app/m/xfoo");
+        let difference = comparison.difference.unwrap();
+        assert!(difference.consecutive > 0.0);
+        assert_eq!(difference.bonus, 0.0);
+        assert!(difference.inner_gap > 0.0);
+        let (a, b) = (comparison.a.unwrap(), comparison.b.unwrap());
+        assert!((difference.total() - (a.score - b.score)).abs() < 1e-9);
+
+        let comparison = explain_comparison(b"amo", b"app/models/foo", b"xyz");
+        assert!(comparison.a.is_some());
+        assert_eq!(comparison.b, None);
+        assert_eq!(comparison.difference, None);
+    }
+
+    #[test]
+    fn pairs_indices() {
+        assert_eq!(
+            alignment(b"amo", b"app/models/foo"),
+            Some(vec![(0, 0), (1, 4), (2, 5)])
+        );
+        assert_eq!(alignment(b"", b"abc"), Some(vec![]));
+        assert_eq!(alignment(b"x", b"abc"), None);
+    }
+}
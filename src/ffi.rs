@@ -0,0 +1,102 @@
+//! C ABI used by the Lua bindings in `lua/`. Strings are passed as pointer
+//! and length pairs and need not be NUL-terminated.
+
+use std::slice;
+
+use crate::{has_match, positions, score, Score, SortKey};
+
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if ptr.is_null() || len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len)
+    }
+}
+
+/// # Safety
+///
+/// `pat` and `text` must point to at least `pat_len` and `text_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fzy_has_match(
+    pat: *const u8,
+    pat_len: usize,
+    text: *const u8,
+    text_len: usize,
+) -> bool {
+    has_match(bytes(pat, pat_len), bytes(text, text_len))
+}
+
+/// # Safety
+///
+/// `pat` and `text` must point to at least `pat_len` and `text_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fzy_score(
+    pat: *const u8,
+    pat_len: usize,
+    text: *const u8,
+    text_len: usize,
+) -> Score {
+    score(bytes(pat, pat_len), bytes(text, text_len))
+}
+
+/// Writes the matched positions of `pat` in `text` to `out`, which must have
+/// room for `pat_len` entries, and returns how many were written or `-1` if
+/// `pat` doesn't match.
+///
+/// # Safety
+///
+/// `pat` and `text` must point to at least `pat_len` and `text_len` bytes and
+/// `out` must be valid for `pat_len` writes.
+#[no_mangle]
+pub unsafe extern "C" fn fzy_positions(
+    pat: *const u8,
+    pat_len: usize,
+    text: *const u8,
+    text_len: usize,
+    out: *mut usize,
+) -> isize {
+    match positions(bytes(pat, pat_len), bytes(text, text_len)) {
+        Some(positions) => {
+            for (i, pos) in positions.iter().enumerate() {
+                *out.add(i) = *pos;
+            }
+            positions.len() as isize
+        }
+        None => -1,
+    }
+}
+
+/// [`SortKey::sort_key`] of `score`, ordered like the score when compared as
+/// an unsigned integer.
+#[no_mangle]
+pub extern "C" fn fzy_score_sort_key(score: Score) -> u64 {
+    score.sort_key()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_matcher() {
+        let (pat, text) = (b"amo", b"app/models/foo");
+        let mut out = [0; 3];
+        unsafe {
+            assert!(fzy_has_match(pat.as_ptr(), 3, text.as_ptr(), 14));
+            assert_eq!(
+                fzy_score(pat.as_ptr(), 3, text.as_ptr(), 14),
+                score(pat, text)
+            );
+            assert_eq!(
+                fzy_positions(pat.as_ptr(), 3, text.as_ptr(), 14, out.as_mut_ptr()),
+                3
+            );
+            assert_eq!(
+                fzy_positions(pat.as_ptr(), 3, text.as_ptr(), 2, out.as_mut_ptr()),
+                -1
+            );
+        }
+        assert_eq!(out, [0, 4, 5]);
+        assert!(fzy_score_sort_key(score(pat, text)) < fzy_score_sort_key(Score::INFINITY));
+    }
+}
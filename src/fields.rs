@@ -0,0 +1,268 @@
+//! Matching only some fields of candidates, such as the file name of
+//! `grep -n` output, while keeping the whole candidate.
+
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::{FuzzyAlgorithm, QuerySession, Score};
+
+/// An inclusive range of 1-based fields, negative ones counting back from
+/// the last field: `2`, `-1`, `2..`, `..3` or `1..-2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldRange {
+    pub start: Option<isize>,
+    pub end: Option<isize>,
+}
+
+impl FieldRange {
+    /// The 0-based indices of the first and last field of `len` covered.
+    fn resolve(&self, len: usize) -> Option<(usize, usize)> {
+        let index = |field: isize| {
+            if field > 0 {
+                Some(field as usize - 1)
+            } else {
+                len.checked_sub(field.unsigned_abs())
+            }
+        };
+        let start = self.start.map_or(Some(0), index)?;
+        let end = self
+            .end
+            .map_or(len.checked_sub(1), index)?
+            .min(len.checked_sub(1)?);
+        Some((start, end)).filter(|(start, end)| start <= end)
+    }
+}
+
+impl fmt::Display for FieldRange {
+    /// The range as [`FromStr`] parses it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let field = |n: Option<isize>| n.map(|n| n.to_string()).unwrap_or_default();
+        match (self.start, self.end) {
+            (Some(start), Some(end)) if start == end => write!(f, "{}", start),
+            (start, end) => write!(f, "{}..{}", field(start), field(end)),
+        }
+    }
+}
+
+impl FromStr for FieldRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FieldRange, String> {
+        let invalid = || format!("invalid field range: {}", s);
+        let field = |n: &str| -> Result<Option<isize>, String> {
+            match n {
+                "" => Ok(None),
+                n => match n.parse() {
+                    Ok(0) | Err(_) => Err(invalid()),
+                    Ok(n) => Ok(Some(n)),
+                },
+            }
+        };
+        let (start, end) = match s.split_once("..") {
+            Some((start, end)) => (field(start)?, field(end)?),
+            None => {
+                let n = field(s)?.ok_or_else(invalid)?;
+                (Some(n), Some(n))
+            }
+        };
+        Ok(FieldRange { start, end })
+    }
+}
+
+/// Which fields of a candidate are matched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields {
+    /// Separates fields; without one, fields are separated by runs of
+    /// whitespace, ignoring leading whitespace.
+    pub delimiter: Option<Vec<u8>>,
+    /// The fields matched, in order; every field when empty.
+    pub ranges: Vec<FieldRange>,
+}
+
+impl Fields {
+    /// Parses comma separated [`FieldRange`]s, as in `1,3..`.
+    pub fn parse_ranges(nth: &str) -> Result<Vec<FieldRange>, String> {
+        nth.split(',').map(str::parse).collect()
+    }
+
+    /// Byte ranges of the fields of `text`, without delimiters.
+    fn split(&self, text: &[u8]) -> Vec<Range<usize>> {
+        let mut fields = Vec::new();
+        match self.delimiter.as_deref() {
+            Some(delimiter) if !delimiter.is_empty() => {
+                let mut start = 0;
+                let mut i = 0;
+                while i + delimiter.len() <= text.len() {
+                    if text[i..].starts_with(delimiter) {
+                        fields.push(start..i);
+                        i += delimiter.len();
+                        start = i;
+                    } else {
+                        i += 1;
+                    }
+                }
+                fields.push(start..text.len());
+            }
+            _ => {
+                let mut start = None;
+                for (i, b) in text.iter().enumerate() {
+                    match (b.is_ascii_whitespace(), start) {
+                        (false, None) => start = Some(i),
+                        (true, Some(s)) => {
+                            fields.push(s..i);
+                            start = None;
+                        }
+                        _ => {}
+                    }
+                }
+                fields.extend(start.map(|s| s..text.len()));
+            }
+        }
+        fields
+    }
+
+    /// The selected fields of `text` and where each of their bytes is in
+    /// `text`, or `None` when every field is selected.
+    ///
+    /// The fields of different ranges are kept apart by the delimiter, or a
+    /// space without one, so that they don't match as if adjacent. Those
+    /// separators aren't in `text`, so they have no offset.
+    pub fn select(&self, text: &[u8]) -> Option<(Vec<u8>, Vec<Option<usize>>)> {
+        if self.ranges.is_empty() {
+            return None;
+        }
+        let separator = match self.delimiter.as_deref() {
+            Some(delimiter) if !delimiter.is_empty() => delimiter,
+            _ => b" ",
+        };
+        let fields = self.split(text);
+        let mut selected = Vec::new();
+        let mut offsets = Vec::new();
+        for range in &self.ranges {
+            if let Some((start, end)) = range.resolve(fields.len()) {
+                if !offsets.is_empty() {
+                    selected.extend_from_slice(separator);
+                    offsets.extend(separator.iter().map(|_| None));
+                }
+                let span = fields[start].start..fields[end].end;
+                selected.extend_from_slice(&text[span.clone()]);
+                offsets.extend(span.map(Some));
+            }
+        }
+        Some((selected, offsets))
+    }
+}
+
+/// An algorithm matching only the selected [`Fields`] of candidates, with
+/// positions into the whole candidate.
+#[derive(Debug, Clone, Default)]
+pub struct FieldMatching<A> {
+    pub algorithm: A,
+    pub fields: Fields,
+}
+
+impl<A: FuzzyAlgorithm> FuzzyAlgorithm for FieldMatching<A> {
+    fn has_match(&self, pat: &[u8], text: &[u8]) -> bool {
+        match self.fields.select(text) {
+            Some((selected, _)) => self.algorithm.has_match(pat, &selected),
+            None => self.algorithm.has_match(pat, text),
+        }
+    }
+
+    fn score(&self, pat: &[u8], text: &[u8]) -> Score {
+        match self.fields.select(text) {
+            Some((selected, _)) => self.algorithm.score(pat, &selected),
+            None => self.algorithm.score(pat, text),
+        }
+    }
+
+    fn positions(&self, pat: &[u8], text: &[u8]) -> Option<Vec<usize>> {
+        match self.fields.select(text) {
+            Some((selected, offsets)) => {
+                let positions = self.algorithm.positions(pat, &selected)?;
+                Some(positions.into_iter().filter_map(|p| offsets[p]).collect())
+            }
+            None => self.algorithm.positions(pat, text),
+        }
+    }
+
+    fn session(&self) -> Option<QuerySession> {
+        // Every field is matched, just as the algorithm would match it.
+        self.fields
+            .ranges
+            .is_empty()
+            .then(|| self.algorithm.session())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    fn selected(fields: &Fields, text: &str) -> Option<String> {
+        let (selected, _) = fields.select(text.as_bytes())?;
+        Some(String::from_utf8(selected).unwrap())
+    }
+
+    #[test]
+    fn selects_fields() {
+        let mut fields = Fields {
+            delimiter: Some(b":".to_vec()),
+            ranges: Fields::parse_ranges("1").unwrap(),
+        };
+        assert_eq!(
+            selected(&fields, "src/lib.rs:12:fn main"),
+            Some("src/lib.rs".into())
+        );
+        fields.ranges = Fields::parse_ranges("2..,-1").unwrap();
+        assert_eq!(selected(&fields, "a:b:c"), Some("b:c:c".into()));
+        let (_, offsets) = fields.select(b"a:b:c").unwrap();
+        assert_eq!(offsets, vec![Some(2), Some(3), Some(4), None, Some(4)]);
+        fields.ranges = Fields::parse_ranges("..-2,5").unwrap();
+        assert_eq!(selected(&fields, "a:b:c"), Some("a:b".into()));
+        fields.delimiter = None;
+        fields.ranges = Fields::parse_ranges("2").unwrap();
+        assert_eq!(selected(&fields, "  one \t two three"), Some("two".into()));
+        fields.ranges = Fields::parse_ranges("3,1").unwrap();
+        assert_eq!(
+            selected(&fields, "  one \t two three"),
+            Some("three one".into())
+        );
+        fields.ranges.clear();
+        assert_eq!(selected(&fields, "a b"), None);
+        assert!(Fields::parse_ranges("0").is_err());
+        assert!(Fields::parse_ranges("1,x").is_err());
+    }
+
+    #[test]
+    fn matches_selected_fields() {
+        let grep = FieldMatching {
+            algorithm: Algorithm::Fzy,
+            fields: Fields {
+                delimiter: Some(b":".to_vec()),
+                ranges: Fields::parse_ranges("1").unwrap(),
+            },
+        };
+        let line = b"src/main.rs:3:use lib";
+        assert!(grep.has_match(b"main", line));
+        assert!(!grep.has_match(b"use", line));
+        assert_eq!(grep.positions(b"rs", b"x:src/rs"), None);
+        assert_eq!(grep.positions(b"rs", b"src/rs:x"), Some(vec![4, 5]));
+
+        let ends = FieldMatching {
+            fields: Fields {
+                delimiter: Some(b":".to_vec()),
+                ranges: Fields::parse_ranges("1,-1").unwrap(),
+            },
+            ..grep
+        };
+        assert_eq!(ends.positions(b"ab", b"a:x:b"), Some(vec![0, 4]));
+        // The fields of two ranges aren't adjacent, so they score like the
+        // delimited text rather than a consecutive match.
+        let joined = Algorithm::Fzy.score(b"ab", b"a:b");
+        assert_eq!(ends.score(b"ab", b"a:x:b"), joined);
+        assert!(joined < Algorithm::Fzy.score(b"ab", b"ab"));
+    }
+}
@@ -0,0 +1,879 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::algorithm::match_candidate;
+use crate::exclude::has_extension;
+use crate::metrics::MetricsSink;
+use crate::trace::{event, span};
+use crate::{
+    Algorithm, Boost, Exclude, FuzzyAlgorithm, Match, Metrics, Observer, Progress, Rewriter, Score,
+    Transform, SCORE_MIN,
+};
+
+#[derive(Clone)]
+pub struct FilterOptions<A = Algorithm> {
+    pub algorithm: A,
+    /// Keep at most this many of the best matches.
+    pub limit: Option<usize>,
+    /// Byte separating candidates, `b'\0'` for `find -print0` style input.
+    pub delimiter: u8,
+    /// Strip a `\r` left over from `\r\n` line endings.
+    pub strip_cr: bool,
+    /// Strip any trailing ASCII whitespace from each line.
+    pub trim_trailing_whitespace: bool,
+    /// Rescores every match before sorting, to blend in signals the
+    /// algorithm knows nothing about such as open buffers or recency.
+    pub adjust: Option<Adjust>,
+    /// Added to the score of every match, after `adjust`.
+    pub boosts: Vec<Arc<dyn Boost>>,
+    /// Candidates matching any of these are never ranked.
+    pub exclude: Vec<Exclude>,
+    /// When not empty, only candidates with one of these extensions, such as
+    /// `rs` or `tar.gz`, are ranked. See [`has_extension`](crate::has_extension).
+    pub extensions: Vec<Vec<u8>>,
+    /// Subtracted from a match's score once per `/` in the candidate, to
+    /// lean towards shallower paths even when scores differ slightly.
+    pub depth_penalty: Score,
+    /// Among equally scored matches, rank the one with fewer `/` first
+    /// rather than the one read first.
+    pub shallow_first: bool,
+    /// Aliases expanded in the query before matching.
+    pub rewrite: Rewriter,
+    /// Called with the counts and timings of each ranking pass.
+    pub metrics: Option<MetricsSink>,
+    /// Score only the first this many bytes of longer candidates, bounding
+    /// the cost of pathologically long lines.
+    pub max_score_len: Option<usize>,
+    /// Bound the work of scoring one candidate to about this many DP cells,
+    /// query length times candidate length, by scoring only a prefix of
+    /// longer candidates.
+    pub max_score_cells: Option<usize>,
+    /// Applied to each candidate as it is read; what comes out is what is
+    /// kept, shown and returned.
+    pub display_transform: Transform,
+    /// Applied to a candidate to get the text actually matched against the
+    /// query, leaving the displayed text as it is.
+    pub match_transform: Transform,
+}
+
+type Adjust = Arc<dyn Fn(&Candidate, Score) -> Score + Send + Sync>;
+
+impl<A: fmt::Debug> fmt::Debug for FilterOptions<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FilterOptions")
+            .field("algorithm", &self.algorithm)
+            .field("limit", &self.limit)
+            .field("delimiter", &self.delimiter)
+            .field("strip_cr", &self.strip_cr)
+            .field("trim_trailing_whitespace", &self.trim_trailing_whitespace)
+            .field("boosts", &self.boosts)
+            .field("exclude", &self.exclude)
+            .field("extensions", &self.extensions)
+            .field("depth_penalty", &self.depth_penalty)
+            .field("shallow_first", &self.shallow_first)
+            .field("rewrite", &self.rewrite)
+            .field("max_score_len", &self.max_score_len)
+            .field("max_score_cells", &self.max_score_cells)
+            .field("display_transform", &self.display_transform)
+            .field("match_transform", &self.match_transform)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A matching candidate, as passed to [`FilterOptions::adjust`].
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate<'a> {
+    pub index: usize,
+    pub text: &'a [u8],
+}
+
+impl Default for FilterOptions {
+    fn default() -> FilterOptions {
+        FilterOptions {
+            algorithm: Algorithm::Fzy,
+            limit: None,
+            delimiter: b'\n',
+            strip_cr: true,
+            trim_trailing_whitespace: false,
+            adjust: None,
+            boosts: Vec::new(),
+            exclude: Vec::new(),
+            extensions: Vec::new(),
+            depth_penalty: 0.0,
+            shallow_first: false,
+            rewrite: Rewriter::new(),
+            metrics: None,
+            max_score_len: None,
+            max_score_cells: None,
+            display_transform: Transform::new(),
+            match_transform: Transform::new(),
+        }
+    }
+}
+
+impl<A> FilterOptions<A> {
+    /// The same options ranking with another algorithm.
+    pub fn with_algorithm<B>(self, algorithm: B) -> FilterOptions<B> {
+        FilterOptions {
+            algorithm,
+            limit: self.limit,
+            delimiter: self.delimiter,
+            strip_cr: self.strip_cr,
+            trim_trailing_whitespace: self.trim_trailing_whitespace,
+            adjust: self.adjust,
+            boosts: self.boosts,
+            exclude: self.exclude,
+            extensions: self.extensions,
+            depth_penalty: self.depth_penalty,
+            shallow_first: self.shallow_first,
+            rewrite: self.rewrite,
+            metrics: self.metrics,
+            max_score_len: self.max_score_len,
+            max_score_cells: self.max_score_cells,
+            display_transform: self.display_transform,
+            match_transform: self.match_transform,
+        }
+    }
+
+    /// How much of a candidate is scored for a query of `query_len` bytes.
+    fn score_len(&self, query_len: usize) -> usize {
+        let cells = self
+            .max_score_cells
+            .map_or(usize::MAX, |cells| cells / query_len.max(1));
+        self.max_score_len.unwrap_or(usize::MAX).min(cells)
+    }
+
+    /// Whether `text` is left out by `exclude` or `extensions`.
+    pub fn excludes(&self, text: &[u8]) -> bool {
+        self.exclude.iter().any(|e| e.matches(text))
+            || !(self.extensions.is_empty()
+                || self.extensions.iter().any(|ext| has_extension(text, ext)))
+    }
+}
+
+fn trim_line<A>(line: &mut Vec<u8>, opts: &FilterOptions<A>) {
+    if line.last() == Some(&opts.delimiter) {
+        line.pop();
+    }
+    if opts.strip_cr && opts.delimiter == b'\n' && line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    if opts.trim_trailing_whitespace {
+        while line.last().is_some_and(u8::is_ascii_whitespace) {
+            line.pop();
+        }
+    }
+    if !opts.display_transform.is_empty() {
+        *line = opts.display_transform.apply(line).into_owned();
+    }
+}
+
+/// Every line read from the input together with the ranked matches, whose
+/// `index` points into `lines`.
+#[derive(Debug, Default)]
+pub struct Filtered {
+    pub lines: Vec<Vec<u8>>,
+    pub matches: Vec<Match>,
+    /// Indices into `lines` of the matches scored on a prefix only, see
+    /// [`FilterOptions::max_score_len`]. Their score may be `SCORE_MIN` when
+    /// the query only matches past the prefix.
+    pub degraded: Vec<usize>,
+}
+
+impl Filtered {
+    /// Matching lines, best first.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], Score)> + '_ {
+        self.matches
+            .iter()
+            .map(move |m| (&self.lines[m.index][..], m.score))
+    }
+
+    /// Matches grouped by `key` of their line, each group best first and the
+    /// groups ordered by their best match.
+    pub fn groups<'a, K, F>(&'a self, mut key: F) -> Vec<Group<K>>
+    where
+        K: Hash + Eq + Clone,
+        F: FnMut(&'a [u8]) -> K,
+    {
+        let mut groups: Vec<Group<K>> = Vec::new();
+        let mut index = HashMap::new();
+        for m in &self.matches {
+            let k = key(&self.lines[m.index]);
+            let i = *index.entry(k.clone()).or_insert_with(|| {
+                groups.push(Group {
+                    key: k,
+                    matches: Vec::new(),
+                });
+                groups.len() - 1
+            });
+            groups[i].matches.push(*m);
+        }
+        groups
+    }
+}
+
+/// Matches sharing a key, see [`Filtered::groups`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group<K> {
+    pub key: K,
+    pub matches: Vec<Match>,
+}
+
+/// The directory of a path, without a trailing slash; empty for a path
+/// without one.
+pub fn parent_dir(path: &[u8]) -> &[u8] {
+    match path.iter().rposition(|b| *b == b'/') {
+        Some(slash) => &path[..slash],
+        None => &[],
+    }
+}
+
+/// Calls `f` with each delimiter separated candidate read from `reader`.
+pub fn read_candidates<A, R, F>(reader: R, opts: &FilterOptions<A>, mut f: F) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(&[u8]),
+{
+    read_candidates_while(reader, opts, |line| {
+        f(line);
+        true
+    })
+    .map(|_| ())
+}
+
+/// [`read_candidates`], stopping once `f` returns `false`. Returns whether
+/// `reader` was read to the end.
+pub(crate) fn read_candidates_while<A, R, F>(
+    mut reader: R,
+    opts: &FilterOptions<A>,
+    mut f: F,
+) -> io::Result<bool>
+where
+    R: BufRead,
+    F: FnMut(&[u8]) -> bool,
+{
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(opts.delimiter, &mut buf)? == 0 {
+            return Ok(true);
+        }
+        trim_line(&mut buf, opts);
+        if !f(&buf) {
+            return Ok(false);
+        }
+    }
+}
+
+/// Reads delimiter separated candidates from `reader` and ranks them against
+/// `query`.
+pub fn filter_reader<A: FuzzyAlgorithm, R: BufRead>(
+    query: &[u8],
+    reader: R,
+    opts: &FilterOptions<A>,
+) -> io::Result<Filtered> {
+    read_and_rank(query, reader, opts, Hooks::default())
+}
+
+/// [`filter_reader`], calling `on_partial` with the best matches so far,
+/// ranked and truncated as `opts` asks, after every `every` candidates read.
+/// The returned matches are the final ranking.
+pub fn filter_reader_partial<A, R, F>(
+    query: &[u8],
+    reader: R,
+    opts: &FilterOptions<A>,
+    every: usize,
+    mut on_partial: F,
+) -> io::Result<Filtered>
+where
+    A: FuzzyAlgorithm,
+    R: BufRead,
+    F: FnMut(&[Match], &[Vec<u8>]),
+{
+    let hooks = Hooks {
+        partial: Some((every.max(1), &mut on_partial)),
+        ..Hooks::default()
+    };
+    read_and_rank(query, reader, opts, hooks)
+}
+
+/// [`filter_reader`], telling `observer` about each candidate read. The
+/// total isn't known until the input ends.
+pub fn filter_reader_observed<A: FuzzyAlgorithm, R: BufRead>(
+    query: &[u8],
+    reader: R,
+    opts: &FilterOptions<A>,
+    observer: &mut dyn Observer,
+) -> io::Result<Filtered> {
+    let hooks = Hooks {
+        observer: Some(observer),
+        ..Hooks::default()
+    };
+    read_and_rank(query, reader, opts, hooks)
+}
+
+fn read_and_rank<A: FuzzyAlgorithm, R: BufRead>(
+    query: &[u8],
+    reader: R,
+    opts: &FilterOptions<A>,
+    mut hooks: Hooks,
+) -> io::Result<Filtered> {
+    let query = opts.rewrite.rewrite(query);
+    let query = &query[..];
+    let start = Instant::now();
+    let mut metrics = Metrics::default();
+    let mut filtered = Filtered::default();
+    {
+        // Candidates are prefiltered and scored as they are read.
+        let _span = span!("ingest");
+        read_candidates(reader, opts, |line| {
+            let matching = Instant::now();
+            let index = filtered.lines.len();
+            if opts.excludes(line) {
+                metrics.excluded += 1;
+            } else {
+                let m = match_guarded(opts, query, line, index, &mut filtered.degraded);
+                filtered.matches.extend(m);
+            }
+            filtered.lines.push(line.to_vec());
+            let lines = &filtered.lines;
+            hooks.tick(&filtered.matches, lines, opts, lines.len(), None);
+            metrics.match_time += matching.elapsed();
+        })?;
+    }
+    metrics.read_time = start.elapsed().saturating_sub(metrics.match_time);
+    metrics.degraded = filtered.degraded.len();
+    finish(&mut filtered.matches, &filtered.lines, opts, metrics);
+    Ok(filtered)
+}
+
+/// Ranks already read candidates against `query`.
+pub fn filter_lines<A: FuzzyAlgorithm>(
+    query: &[u8],
+    lines: Vec<Vec<u8>>,
+    opts: &FilterOptions<A>,
+) -> Filtered {
+    rank_lines(query, lines, opts, Hooks::default())
+}
+
+/// [`filter_lines`], calling `on_partial` with the best matches so far,
+/// ranked and truncated as `opts` asks, after every `every` candidates
+/// scored. The returned matches are the final ranking.
+pub fn filter_lines_partial<A, F>(
+    query: &[u8],
+    lines: Vec<Vec<u8>>,
+    opts: &FilterOptions<A>,
+    every: usize,
+    mut on_partial: F,
+) -> Filtered
+where
+    A: FuzzyAlgorithm,
+    F: FnMut(&[Match], &[Vec<u8>]),
+{
+    let hooks = Hooks {
+        partial: Some((every.max(1), &mut on_partial)),
+        ..Hooks::default()
+    };
+    rank_lines(query, lines, opts, hooks)
+}
+
+/// [`filter_lines`], telling `observer` about each candidate scored.
+pub fn filter_lines_observed<A: FuzzyAlgorithm>(
+    query: &[u8],
+    lines: Vec<Vec<u8>>,
+    opts: &FilterOptions<A>,
+    observer: &mut dyn Observer,
+) -> Filtered {
+    let hooks = Hooks {
+        observer: Some(observer),
+        ..Hooks::default()
+    };
+    rank_lines(query, lines, opts, hooks)
+}
+
+fn rank_lines<A: FuzzyAlgorithm>(
+    query: &[u8],
+    lines: Vec<Vec<u8>>,
+    opts: &FilterOptions<A>,
+    mut hooks: Hooks,
+) -> Filtered {
+    let query = opts.rewrite.rewrite(query);
+    let query = &query[..];
+    let start = Instant::now();
+    let mut metrics = Metrics::default();
+    let excluded: Vec<bool> = {
+        let _span = span!("prefilter");
+        lines.iter().map(|line| opts.excludes(line)).collect()
+    };
+    metrics.excluded = excluded.iter().filter(|excluded| **excluded).count();
+    let mut degraded = Vec::new();
+    let mut matches = Vec::new();
+    {
+        let _span = span!("score");
+        // Excluded candidates are reported as processed too, in order.
+        for (index, excluded) in excluded.into_iter().enumerate() {
+            if !excluded {
+                matches.extend(match_guarded(
+                    opts,
+                    query,
+                    &lines[index],
+                    index,
+                    &mut degraded,
+                ));
+            }
+            hooks.tick(&matches, &lines, opts, index + 1, Some(lines.len()));
+        }
+    }
+    metrics.match_time = start.elapsed();
+    metrics.degraded = degraded.len();
+    finish(&mut matches, &lines, opts, metrics);
+    Filtered {
+        lines,
+        matches,
+        degraded,
+    }
+}
+
+/// [`match_candidate`] on the match text, scoring only as much of `text` as `opts` allows and
+/// noting the index of a candidate that was cut short in `degraded`.
+fn match_guarded<A: FuzzyAlgorithm>(
+    opts: &FilterOptions<A>,
+    query: &[u8],
+    text: &[u8],
+    index: usize,
+    degraded: &mut Vec<usize>,
+) -> Option<Match> {
+    let text = &opts.match_transform.apply(text)[..];
+    let len = opts.score_len(query.len());
+    if text.len() <= len {
+        return match_candidate(&opts.algorithm, query, text, index);
+    }
+    if !opts.algorithm.has_match(query, text) {
+        return None;
+    }
+    degraded.push(index);
+    let m = match_candidate(&opts.algorithm, query, &text[..len], index);
+    Some(m.unwrap_or(Match {
+        index,
+        score: SCORE_MIN,
+    }))
+}
+
+type OnPartial<'f> = &'f mut dyn FnMut(&[Match], &[Vec<u8>]);
+
+/// What a ranking pass reports as it goes: provisional rankings every so
+/// many candidates, and progress after each one.
+#[derive(Default)]
+struct Hooks<'f> {
+    partial: Option<(usize, OnPartial<'f>)>,
+    observer: Option<&'f mut dyn Observer>,
+}
+
+impl Hooks<'_> {
+    fn tick<A>(
+        &mut self,
+        matches: &[Match],
+        lines: &[Vec<u8>],
+        opts: &FilterOptions<A>,
+        processed: usize,
+        total: Option<usize>,
+    ) {
+        if let Some((every, on_partial)) = &mut self.partial {
+            if processed.is_multiple_of(*every) {
+                let mut best = matches.to_vec();
+                rank_matches(&mut best, lines, opts);
+                on_partial(&best, lines);
+            }
+        }
+        if let Some(observer) = &mut self.observer {
+            observer.progress(&Progress {
+                processed,
+                total,
+                matched: matches.len(),
+            });
+        }
+    }
+}
+
+/// Rescores, sorts and truncates the matches as `opts` asks, then completes
+/// and reports `metrics`.
+fn finish<A>(
+    matches: &mut Vec<Match>,
+    lines: &[Vec<u8>],
+    opts: &FilterOptions<A>,
+    mut metrics: Metrics,
+) {
+    let _span = span!("sort");
+    let start = Instant::now();
+    metrics.scanned = lines.len();
+    metrics.scored = matches.len();
+    metrics.unmatched = lines.len() - metrics.excluded - matches.len();
+    rank_matches(matches, lines, opts);
+    metrics.sort_time = start.elapsed();
+    event!(
+        scanned = metrics.scanned,
+        excluded = metrics.excluded,
+        unmatched = metrics.unmatched,
+        scored = metrics.scored;
+        "ranked candidates"
+    );
+    if let Some(sink) = &opts.metrics {
+        sink(&metrics);
+    }
+}
+
+fn rank_matches<A>(matches: &mut Vec<Match>, lines: &[Vec<u8>], opts: &FilterOptions<A>) {
+    for m in matches.iter_mut() {
+        let text = &lines[m.index];
+        let candidate = Candidate {
+            index: m.index,
+            text,
+        };
+        if let Some(adjust) = &opts.adjust {
+            m.score = adjust(&candidate, m.score);
+        }
+        for boost in &opts.boosts {
+            m.score += boost.boost(&candidate);
+        }
+        if opts.depth_penalty != 0.0 {
+            m.score -= depth(text) as Score * opts.depth_penalty;
+        }
+    }
+    if opts.shallow_first {
+        matches.sort_by(|a, b| {
+            (b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal))
+                .then_with(|| depth(&lines[a.index]).cmp(&depth(&lines[b.index])))
+                .then(a.index.cmp(&b.index))
+        });
+    } else {
+        matches.sort_by(Match::rank_cmp);
+    }
+    if let Some(limit) = opts.limit {
+        matches.truncate(limit);
+    }
+}
+
+fn depth(path: &[u8]) -> usize {
+    path.iter().filter(|b| **b == b'/').count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::GitBoost;
+    use crate::{score, Step};
+
+    fn filter(query: &[u8], input: &[u8], opts: &FilterOptions) -> Vec<Vec<u8>> {
+        filter_reader(query, input, opts)
+            .unwrap()
+            .iter()
+            .map(|(line, _)| line.to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn ranks_lines() {
+        let input = b"app/m/foo\nxyz\napp/models/foo\n";
+        assert_eq!(
+            filter(b"amo", input, &FilterOptions::default()),
+            vec![b"app/models/foo".to_vec(), b"app/m/foo".to_vec()]
+        );
+    }
+
+    #[test]
+    fn empty_query_keeps_input_order() {
+        let filtered = filter_reader(b"", &b"b\na\n"[..], &FilterOptions::default()).unwrap();
+        assert_eq!(filtered.lines, vec![b"b".to_vec(), b"a".to_vec()]);
+        assert_eq!(filtered.matches[0].index, 0);
+        assert_eq!(filtered.matches[1].index, 1);
+    }
+
+    #[test]
+    fn limit_truncates() {
+        let opts = FilterOptions {
+            limit: Some(1),
+            ..FilterOptions::default()
+        };
+        assert_eq!(
+            filter(b"t", b"tests\ntest\n", &opts),
+            vec![b"test".to_vec()]
+        );
+    }
+
+    #[test]
+    fn filters_lines() {
+        let lines = vec![b"tests".to_vec(), b"x".to_vec(), b"test".to_vec()];
+        let filtered = filter_lines(b"t", lines, &FilterOptions::default());
+        assert_eq!(filtered.lines.len(), 3);
+        let texts: Vec<&[u8]> = filtered.iter().map(|(line, _)| line).collect();
+        assert_eq!(texts, vec![&b"test"[..], &b"tests"[..]]);
+    }
+
+    #[test]
+    fn adjusts_scores() {
+        let extension = b".rs".to_vec();
+        let opts = FilterOptions {
+            adjust: Some(Arc::new(move |c: &Candidate, score| {
+                if c.text.ends_with(&extension) {
+                    score + 1.0
+                } else {
+                    score
+                }
+            })),
+            ..FilterOptions::default()
+        };
+        let input = b"test\ntests.rs\n";
+        assert_eq!(
+            filter(b"t", input, &opts),
+            vec![b"tests.rs".to_vec(), b"test".to_vec()]
+        );
+        let lines = vec![b"test".to_vec(), b"tests.rs".to_vec()];
+        assert_eq!(filter_lines(b"t", lines, &opts).matches[0].index, 1);
+    }
+
+    #[test]
+    fn applies_boosts() {
+        let mut git = GitBoost::new();
+        git.add_changed(b"tests");
+        let opts = FilterOptions {
+            boosts: vec![Arc::new(git)],
+            ..FilterOptions::default()
+        };
+        assert_eq!(
+            filter(b"t", b"test\ntests\n", &opts),
+            vec![b"tests".to_vec(), b"test".to_vec()]
+        );
+    }
+
+    #[test]
+    fn excludes_before_ranking() {
+        let opts = FilterOptions {
+            exclude: vec![Exclude::new("target/"), Exclude::new("*.min.js")],
+            ..FilterOptions::default()
+        };
+        let input = b"src/app.js\ntarget/app.js\nstatic/app.min.js\n";
+        assert_eq!(filter(b"app", input, &opts), vec![b"src/app.js".to_vec()]);
+        let lines = input.split(|b| *b == b'\n').map(<[u8]>::to_vec).collect();
+        let filtered = filter_lines(b"", lines, &opts);
+        assert_eq!(filtered.lines.len(), 4);
+        assert_eq!(filtered.matches.len(), 2);
+    }
+
+    #[test]
+    fn keeps_extensions() {
+        let opts = FilterOptions {
+            extensions: vec![b"rs".to_vec(), b"toml".to_vec()],
+            ..FilterOptions::default()
+        };
+        let input = b"src/main.rs\nCargo.toml\nREADME.md\nsrc.rs/x\n";
+        assert_eq!(
+            filter(b"", input, &opts),
+            vec![b"src/main.rs".to_vec(), b"Cargo.toml".to_vec()]
+        );
+    }
+
+    #[test]
+    fn groups_by_directory() {
+        let input = b"src/main.rs\nmake\nsrc/bin/main.rs\nsrc/mod.rs\n";
+        let filtered = filter_reader(b"m", &input[..], &FilterOptions::default()).unwrap();
+        let groups = filtered.groups(parent_dir);
+        let keys: Vec<&[u8]> = groups.iter().map(|g| g.key).collect();
+        assert_eq!(keys, vec![&b""[..], b"src", b"src/bin"]);
+        let src: Vec<usize> = groups[1].matches.iter().map(|m| m.index).collect();
+        assert_eq!(src, vec![3, 0]);
+    }
+
+    #[test]
+    fn prefers_shallow_paths() {
+        let input = b"a/b/main\nabc/main\n";
+        let opts = FilterOptions {
+            shallow_first: true,
+            ..FilterOptions::default()
+        };
+        assert_eq!(
+            filter(b"main", input, &FilterOptions::default())[0],
+            b"a/b/main".to_vec()
+        );
+        assert_eq!(filter(b"main", input, &opts)[0], b"abc/main".to_vec());
+
+        let input = b"src/main\nsrc/lib/main.rs\n";
+        let opts = FilterOptions {
+            depth_penalty: 0.1,
+            ..FilterOptions::default()
+        };
+        let filtered = filter_reader(b"main", &input[..], &opts).unwrap();
+        assert_eq!(filtered.matches[0].index, 0);
+        assert!(filtered.matches[1].score < score(b"main", b"src/lib/main.rs") - 0.19);
+    }
+
+    #[test]
+    fn rewrites_query() {
+        let mut opts = FilterOptions::default();
+        opts.rewrite.add(b"@t", b"tests/");
+        let input = b"src/main.rs\ntests/main.rs\n";
+        assert_eq!(
+            filter(b"@tm", input, &opts),
+            vec![b"tests/main.rs".to_vec()]
+        );
+    }
+
+    #[test]
+    fn reports_metrics() {
+        use std::sync::Mutex;
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reported);
+        let opts = FilterOptions {
+            exclude: vec![Exclude::new("target/")],
+            metrics: Some(Arc::new(move |m: &Metrics| sink.lock().unwrap().push(*m))),
+            ..FilterOptions::default()
+        };
+        let input = b"src/a.rs\ntarget/a.rs\nb.rs\nsrc/lib.rs\n";
+        filter_reader(b"a", &input[..], &opts).unwrap();
+        let lines = input.split(|b| *b == b'\n').map(<[u8]>::to_vec).collect();
+        filter_lines(b"a", lines, &opts);
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 2);
+        for (m, scanned) in reported.iter().zip([4, 5]) {
+            assert_eq!(m.scanned, scanned);
+            assert_eq!(m.excluded, 1);
+            assert_eq!(m.scored, 1);
+            assert_eq!(m.unmatched, scanned - 2);
+        }
+    }
+
+    #[test]
+    fn caps_scoring_work() {
+        let long = [&b"x"[..], &[b'a'; 100], b"/main"].concat();
+        let lines = vec![long.clone(), b"src/main.rs".to_vec()];
+        let opts = FilterOptions {
+            max_score_len: Some(50),
+            ..FilterOptions::default()
+        };
+        let filtered = filter_lines(b"main", lines.clone(), &opts);
+        assert_eq!(filtered.degraded, vec![0]);
+        assert_eq!(filtered.matches[1].score, SCORE_MIN);
+        let filtered = filter_lines(b"xa", lines.clone(), &opts);
+        assert_eq!(filtered.degraded, vec![0]);
+        assert_eq!(filtered.matches[0].score, score(b"xa", &long[..50]));
+
+        let opts = FilterOptions {
+            max_score_cells: Some(4 * 20),
+            ..FilterOptions::default()
+        };
+        let filtered = filter_reader(b"main", &lines.join(&b'\n')[..], &opts).unwrap();
+        assert_eq!(filtered.degraded, vec![0]);
+        assert!(filter_lines(b"main", lines, &FilterOptions::default())
+            .degraded
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_partial_results() {
+        let opts = FilterOptions {
+            limit: Some(1),
+            ..FilterOptions::default()
+        };
+        let input = b"tests\nx\ntest\ny\n";
+        let mut partials = Vec::new();
+        let filtered = filter_reader_partial(b"t", &input[..], &opts, 2, |best, lines| {
+            partials.push((best.to_vec(), lines.len()));
+        })
+        .unwrap();
+        assert_eq!(partials.len(), 2);
+        assert_eq!(partials[0].0[0].index, 0);
+        assert_eq!(partials[0].1, 2);
+        assert_eq!(partials[1].0, filtered.matches);
+
+        let lines = input.split(|b| *b == b'\n').map(<[u8]>::to_vec).collect();
+        let mut best = Vec::new();
+        let filtered = filter_lines_partial(b"t", lines, &opts, 1, |b, _| best.push(b.to_vec()));
+        assert_eq!(best.len(), 5);
+        assert_eq!(best[1][0].index, 0);
+        assert_eq!(best[2], filtered.matches);
+    }
+
+    #[test]
+    fn observes_progress() {
+        let opts = FilterOptions {
+            exclude: vec![Exclude::new("x")],
+            ..FilterOptions::default()
+        };
+        let input = b"tests\nx\ntest\ny";
+        let mut seen = Vec::new();
+        let mut observer = |p: &Progress| seen.push(*p);
+        filter_reader_observed(b"t", &input[..], &opts, &mut observer).unwrap();
+        assert_eq!(seen.len(), 4);
+        assert_eq!(
+            seen[2],
+            Progress {
+                processed: 3,
+                total: None,
+                matched: 2,
+            }
+        );
+
+        seen.clear();
+        let lines = input.split(|b| *b == b'\n').map(<[u8]>::to_vec).collect();
+        let mut observer = |p: &Progress| seen.push(*p);
+        filter_lines_observed(b"t", lines, &opts, &mut observer);
+        let processed: Vec<usize> = seen.iter().map(|p| p.processed).collect();
+        assert_eq!(processed, vec![1, 2, 3, 4]);
+        assert_eq!(seen[3].total, Some(4));
+        assert_eq!(seen[3].matched, 2);
+    }
+
+    #[test]
+    fn transforms_candidates() {
+        let mut opts = FilterOptions::default();
+        opts.display_transform.push(Step::Trim);
+        opts.match_transform
+            .push(Step::StripPrefix(b"./".to_vec()))
+            .push(Step::Map(|t| {
+                t.split(|b| *b == b':').next().unwrap().to_vec()
+            }));
+        let input = b"  ./src/a.rs:mod\n./mod.rs:fn x\n";
+        let filtered = filter_reader(b"mod", &input[..], &opts).unwrap();
+        assert_eq!(filtered.lines[0], b"./src/a.rs:mod".to_vec());
+        let texts: Vec<&[u8]> = filtered.iter().map(|(line, _)| line).collect();
+        assert_eq!(texts, vec![&b"./mod.rs:fn x"[..]]);
+    }
+
+    #[test]
+    fn handles_line_endings() {
+        let input = b"a\r\nb \t\r\nc";
+        let filtered = filter_reader(b"", &input[..], &FilterOptions::default()).unwrap();
+        assert_eq!(
+            filtered.lines,
+            vec![b"a".to_vec(), b"b \t".to_vec(), b"c".to_vec()]
+        );
+
+        let opts = FilterOptions {
+            strip_cr: false,
+            ..FilterOptions::default()
+        };
+        let filtered = filter_reader(b"", &input[..], &opts).unwrap();
+        assert_eq!(filtered.lines[0], b"a\r".to_vec());
+
+        let opts = FilterOptions {
+            trim_trailing_whitespace: true,
+            ..FilterOptions::default()
+        };
+        let filtered = filter_reader(b"", &input[..], &opts).unwrap();
+        assert_eq!(filtered.lines[1], b"b".to_vec());
+    }
+
+    #[test]
+    fn reads_nul_separated() {
+        let opts = FilterOptions {
+            delimiter: b'\0',
+            ..FilterOptions::default()
+        };
+        let filtered = filter_reader(b"", &b"a\nb\r\0c\0"[..], &opts).unwrap();
+        assert_eq!(filtered.lines, vec![b"a\nb\r".to_vec(), b"c".to_vec()]);
+    }
+}
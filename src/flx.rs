@@ -0,0 +1,228 @@
+//! The scoring of Emacs' flx (as used by ivy and helm): a per-character
+//! heatmap favouring word starts in the file name, and a search forwarding
+//! only the best match of each query suffix, as flx does.
+
+use std::collections::HashMap;
+
+use crate::Score;
+
+fn is_word(c: Option<u8>) -> bool {
+    c.is_some_and(|c| !matches!(c, b' ' | b'-' | b'_' | b':' | b'.' | b'/' | b'\\'))
+}
+
+fn is_capital(c: Option<u8>) -> bool {
+    is_word(c) && c.is_some_and(|c| c == c.to_ascii_uppercase())
+}
+
+fn is_boundary(last: Option<u8>, c: u8) -> bool {
+    last.is_none()
+        || (!is_capital(last) && is_capital(Some(c)))
+        || (!is_word(last) && is_word(Some(c)))
+}
+
+/// A path component: where its `/` is, how many words it has and where they
+/// start, latest first.
+struct Group {
+    start: isize,
+    word_count: i32,
+    words: Vec<usize>,
+}
+
+/// flx's `flx-get-heatmap-str` with `/` separating groups.
+fn heatmap(text: &[u8]) -> Vec<i32> {
+    let len = text.len();
+    let mut scores = vec![-35; len];
+    scores[len - 1] += 1;
+
+    let mut groups = vec![Group {
+        start: -1,
+        word_count: 0,
+        words: Vec::new(),
+    }];
+    let mut last: Option<u8> = None;
+    let mut word_count = 0;
+    for (i, &c) in text.iter().enumerate() {
+        // Separators before the first word are words of their own, so that
+        // `foo/__ab` scores below `foo/ab`.
+        let effective_last = if word_count == 0 { None } else { last };
+        let group = groups.last_mut().unwrap();
+        if is_boundary(effective_last, c) {
+            group.words.insert(0, i);
+        }
+        if !is_word(last) && is_word(Some(c)) {
+            word_count += 1;
+        }
+        if last == Some(b'.') {
+            scores[i] -= 45;
+        }
+        if c == b'/' {
+            group.word_count = word_count;
+            word_count = 0;
+            groups.push(Group {
+                start: i as isize,
+                word_count: 0,
+                words: Vec::new(),
+            });
+        }
+        if i == len - 1 {
+            groups.last_mut().unwrap().word_count = word_count;
+        } else {
+            last = Some(c);
+        }
+    }
+
+    let separators = groups.len() - 1;
+    if separators != 0 {
+        for score in &mut scores {
+            *score -= 2 * groups.len() as i32;
+        }
+    }
+    let mut limit: Option<usize> = None;
+    let mut basepath_found = false;
+    for (index, group) in groups.iter().enumerate().rev() {
+        let basepath = !group.words.is_empty() && !basepath_found;
+        basepath_found |= basepath;
+        let bonus = if basepath {
+            35 + separators.saturating_sub(1) as i32 - group.word_count
+        } else if index == 0 {
+            -3
+        } else {
+            -5 + (index as i32 - 1)
+        };
+        let start = (group.start + 1) as usize;
+        for score in &mut scores[start..limit.unwrap_or(len)] {
+            *score += bonus;
+        }
+        // Words are listed latest first, each running up to the next one.
+        let mut end = limit.unwrap_or(len);
+        let words = group.words.len();
+        for (n, &word) in group.words.iter().enumerate() {
+            let word_index = (words - 1 - n) as i32;
+            scores[word] += 85;
+            for (char_index, score) in scores[word..end].iter_mut().enumerate() {
+                *score += -3 * word_index - char_index as i32;
+            }
+            end = word;
+        }
+        limit = Some(start);
+    }
+    scores
+}
+
+/// The best match found for a query suffix: positions, score and how many
+/// consecutive matches lead it.
+type Candidate = (Vec<usize>, i32, i32);
+
+struct Search<'a> {
+    pat: &'a [u8],
+    /// Positions in the text each pattern byte can match.
+    indexes: Vec<Vec<usize>>,
+    heatmap: Vec<i32>,
+    cache: HashMap<(usize, isize), Vec<Candidate>>,
+}
+
+impl Search<'_> {
+    /// Matches `pat[pi..]` after text position `after`: every alignment of
+    /// the last byte, or the single best one for longer suffixes.
+    fn best(&mut self, pi: usize, after: isize) -> Vec<Candidate> {
+        if let Some(found) = self.cache.get(&(pi, after)) {
+            return found.clone();
+        }
+        let indexes: Vec<usize> = (self.indexes[pi].iter())
+            .copied()
+            .filter(|i| *i as isize > after)
+            .collect();
+        let found: Vec<Candidate> = if pi == self.pat.len() - 1 {
+            (indexes.iter())
+                .map(|&i| (vec![i], self.heatmap[i], 0))
+                .collect()
+        } else {
+            let mut best: Option<Candidate> = None;
+            for i in indexes {
+                for (positions, score, run) in self.best(pi + 1, i as isize) {
+                    let adjacent = positions[0] == i + 1;
+                    let score = if adjacent {
+                        score + self.heatmap[i] + run.min(3) * 15 + 60
+                    } else {
+                        score + self.heatmap[i]
+                    };
+                    if best.as_ref().is_none_or(|b| score > b.1) {
+                        let mut p = Vec::with_capacity(positions.len() + 1);
+                        p.push(i);
+                        p.extend(positions);
+                        best = Some((p, score, if adjacent { run + 1 } else { 0 }));
+                    }
+                }
+            }
+            best.into_iter().collect()
+        };
+        self.cache.insert((pi, after), found.clone());
+        found
+    }
+}
+
+/// Scores `text` like flx. A lowercase pattern byte matches either case, an
+/// uppercase one only itself.
+/// Whether a query byte matches a candidate byte: lowercase letters match
+/// either case, anything else only itself.
+fn matches(p: u8, c: u8) -> bool {
+    c == p || (p.is_ascii_lowercase() && c.to_ascii_lowercase() == p)
+}
+
+/// Whether [`match_flx`] finds a match of a non-empty `pat`, without looking
+/// for the best one.
+pub(crate) fn has_match(pat: &[u8], text: &[u8]) -> bool {
+    let mut text = text.iter();
+    pat.iter().all(|&p| text.any(|&c| matches(p, c)))
+}
+
+pub(crate) fn match_flx(pat: &[u8], text: &[u8]) -> Option<(Score, Vec<usize>)> {
+    if pat.is_empty() || text.is_empty() {
+        return None;
+    }
+    let indexes = (pat.iter())
+        .map(|&p| {
+            (text.iter().enumerate())
+                .filter(|(_, &c)| matches(p, c))
+                .map(|(i, _)| i)
+                .collect()
+        })
+        .collect();
+    let mut search = Search {
+        pat,
+        indexes,
+        heatmap: heatmap(text),
+        cache: HashMap::new(),
+    };
+    let (positions, score, _) =
+        (search.best(0, -1).into_iter())
+            .reduce(|best, next| if next.1 > best.1 { next } else { best })?;
+    Some((score as Score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_heatmap() {
+        // Every byte starts at -35 and the last gets 1, the basepath group
+        // 35 less its word count, word starts 85, and bytes lose one per
+        // position in their word.
+        assert_eq!(heatmap(b"ab"), vec![84, -1]);
+        let map = heatmap(b"foo/bar");
+        assert!(map[4] > map[0]);
+        assert!(map[4] > map[5]);
+    }
+
+    #[test]
+    fn favours_word_starts() {
+        let (some, positions) = match_flx(b"ss", b"some_stuff").unwrap();
+        assert_eq!(positions, vec![0, 5]);
+        let (miss, _) = match_flx(b"ss", b"mississippi").unwrap();
+        assert!(some > miss);
+        assert_eq!(match_flx(b"S", b"some_stuff"), None);
+        assert_eq!(match_flx(b"ab", b"AB").unwrap().1, vec![0, 1]);
+        assert_eq!(match_flx(b"x", b"ab"), None);
+    }
+}
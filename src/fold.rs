@@ -0,0 +1,96 @@
+//! ASCII lowercasing of candidates, 16 or 32 bytes at a time where the CPU
+//! allows it.
+
+/// Same as `<[u8]>::to_ascii_lowercase`.
+pub(crate) fn to_ascii_lowercase(text: &[u8]) -> Vec<u8> {
+    let mut out = vec![0; text.len()];
+    fold_into(text, &mut out);
+    out
+}
+
+/// Writes `src` lowercased into `dst`, which must be as long.
+pub(crate) fn fold_into(src: &[u8], dst: &mut [u8]) {
+    assert_eq!(src.len(), dst.len());
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // Safety: AVX2 support was just checked, and the lengths agree.
+            unsafe { x86::fold_avx2(src, dst) };
+        } else {
+            // Safety: SSE2 is part of the x86_64 baseline, and the lengths agree.
+            unsafe { x86::fold_sse2(src, dst) };
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    fold_scalar(src, dst);
+}
+
+fn fold_scalar(src: &[u8], dst: &mut [u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = s.to_ascii_lowercase();
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::fold_scalar;
+
+    // Bytes are compared as signed, so everything from 0x80 up falls below
+    // 'A' and is left alone.
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn fold_sse2(src: &[u8], dst: &mut [u8]) {
+        let below = _mm_set1_epi8(b'A' as i8 - 1);
+        let above = _mm_set1_epi8(b'Z' as i8 + 1);
+        let bit = _mm_set1_epi8(0x20);
+        let chunks = src.len() / 16;
+        for i in 0..chunks {
+            let v = _mm_loadu_si128(src.as_ptr().add(i * 16) as *const __m128i);
+            let upper = _mm_and_si128(_mm_cmpgt_epi8(v, below), _mm_cmplt_epi8(v, above));
+            let v = _mm_or_si128(v, _mm_and_si128(upper, bit));
+            _mm_storeu_si128(dst.as_mut_ptr().add(i * 16) as *mut __m128i, v);
+        }
+        fold_scalar(&src[chunks * 16..], &mut dst[chunks * 16..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn fold_avx2(src: &[u8], dst: &mut [u8]) {
+        let below = _mm256_set1_epi8(b'A' as i8 - 1);
+        let above = _mm256_set1_epi8(b'Z' as i8 + 1);
+        let bit = _mm256_set1_epi8(0x20);
+        let chunks = src.len() / 32;
+        for i in 0..chunks {
+            let v = _mm256_loadu_si256(src.as_ptr().add(i * 32) as *const __m256i);
+            let upper = _mm256_and_si256(_mm256_cmpgt_epi8(v, below), _mm256_cmpgt_epi8(above, v));
+            let v = _mm256_or_si256(v, _mm256_and_si256(upper, bit));
+            _mm256_storeu_si256(dst.as_mut_ptr().add(i * 32) as *mut __m256i, v);
+        }
+        fold_sse2(&src[chunks * 32..], &mut dst[chunks * 32..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_std() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..bytes.len() {
+            let text = &bytes[bytes.len() - len..];
+            assert_eq!(to_ascii_lowercase(text), text.to_ascii_lowercase());
+        }
+        let text = b"App/Models/ORDER.rb Gemfile.LOCK src/Main.RS";
+        assert_eq!(to_ascii_lowercase(text), text.to_ascii_lowercase());
+    }
+
+    #[test]
+    fn scalar_matches_std() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let mut out = vec![0; bytes.len()];
+        fold_scalar(&bytes, &mut out);
+        assert_eq!(out, bytes.to_ascii_lowercase());
+    }
+}
@@ -0,0 +1,327 @@
+//! Front coded candidate storage: each candidate keeps only what it doesn't
+//! share with the one before it, which for sorted paths is mostly the file
+//! name.
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::iter::FromIterator;
+use std::mem;
+
+use crate::algorithm::match_candidate;
+use crate::{FuzzyAlgorithm, Match};
+
+/// Candidates between two stored in full, bounding how many a lookup decodes.
+const RESTART: usize = 16;
+
+/// Starts every snapshot, see [`FrontCoded::save`].
+const MAGIC: &[u8; 8] = b"fzyfront";
+
+/// Candidates stored front coded against the previous one, decoded on demand.
+///
+/// Every [`RESTART`]th candidate is stored in full so that [`get`] decodes at
+/// most a block; scanning every candidate with [`for_each`] or [`rank`]
+/// decodes each once. Storage shrinks the more consecutive candidates share,
+/// so sorted input compresses best.
+///
+/// [`get`]: FrontCoded::get
+/// [`for_each`]: FrontCoded::for_each
+/// [`rank`]: FrontCoded::rank
+#[derive(Debug, Clone, Default)]
+pub struct FrontCoded {
+    /// Per candidate: the shared prefix length and suffix length as varints,
+    /// then the suffix.
+    bytes: Vec<u8>,
+    /// Where each block starts in `bytes`.
+    blocks: Vec<usize>,
+    len: usize,
+    /// The last candidate pushed, which the next is coded against.
+    last: Vec<u8>,
+}
+
+impl FrontCoded {
+    pub fn new() -> FrontCoded {
+        FrontCoded::default()
+    }
+
+    /// Adds a candidate and returns its index.
+    pub fn push(&mut self, text: &[u8]) -> usize {
+        let shared = if self.len.is_multiple_of(RESTART) {
+            self.blocks.push(self.bytes.len());
+            0
+        } else {
+            (self.last.iter().zip(text))
+                .take_while(|(a, b)| a == b)
+                .count()
+        };
+        write_varint(&mut self.bytes, shared);
+        write_varint(&mut self.bytes, text.len() - shared);
+        self.bytes.extend_from_slice(&text[shared..]);
+        self.last.truncate(shared);
+        self.last.extend_from_slice(&text[shared..]);
+        self.len += 1;
+        self.len - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The candidate at `index`, decoded.
+    pub fn get(&self, index: usize) -> Option<Vec<u8>> {
+        let mut text = Vec::new();
+        self.get_into(index, &mut text).then_some(text)
+    }
+
+    /// Decodes the candidate at `index` into `text`, returning whether there
+    /// is one.
+    pub fn get_into(&self, index: usize, text: &mut Vec<u8>) -> bool {
+        if index >= self.len {
+            return false;
+        }
+        text.clear();
+        let mut pos = self.blocks[index / RESTART];
+        for _ in 0..=index % RESTART {
+            pos = decode(&self.bytes, pos, text);
+        }
+        true
+    }
+
+    /// Calls `f` with the index and full text of every candidate in order.
+    pub fn for_each<F: FnMut(usize, &[u8])>(&self, mut f: F) {
+        let mut text = Vec::new();
+        let mut pos = 0;
+        for index in 0..self.len {
+            pos = decode(&self.bytes, pos, &mut text);
+            f(index, &text);
+        }
+    }
+
+    /// The candidates matching `query`, best first.
+    pub fn rank<A: FuzzyAlgorithm + ?Sized>(&self, algorithm: &A, query: &[u8]) -> Vec<Match> {
+        let mut matches = Vec::new();
+        self.for_each(|index, text| matches.extend(match_candidate(algorithm, query, text, index)));
+        matches.sort_by(Match::rank_cmp);
+        matches
+    }
+
+    /// Roughly the heap memory taken, for comparing against a plain
+    /// `Vec<Vec<u8>>` of the same candidates.
+    pub fn memory(&self) -> usize {
+        self.bytes.capacity()
+            + self.blocks.capacity() * mem::size_of::<usize>()
+            + self.last.capacity()
+    }
+
+    /// Writes a snapshot that [`load`](FrontCoded::load) restores without
+    /// re-coding the candidates: a header, then the lengths and block offsets
+    /// as little-endian `u64`s, then the coded bytes.
+    ///
+    /// Only the candidates are stored. Nothing a ranking derives from them,
+    /// such as lowercased text or fzy's bonuses, is kept: [`rank`] recomputes
+    /// it per query as it does for freshly pushed candidates. Nothing in the
+    /// picker or the command line reads snapshots; they're for library users
+    /// keeping a large candidate list across runs.
+    ///
+    /// [`rank`]: FrontCoded::rank
+    pub fn save<W: Write>(&self, mut out: W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        for n in [self.len, self.blocks.len(), self.bytes.len()] {
+            out.write_all(&(n as u64).to_le_bytes())?;
+        }
+        for &block in &self.blocks {
+            out.write_all(&(block as u64).to_le_bytes())?;
+        }
+        out.write_all(&self.bytes)?;
+        out.flush()
+    }
+
+    /// Restores a snapshot written by [`save`](FrontCoded::save), checking
+    /// that every candidate decodes.
+    pub fn load<R: Read>(mut reader: R) -> io::Result<FrontCoded> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a candidate snapshot"));
+        }
+        let mut read_u64 = || -> io::Result<usize> {
+            let mut n = [0; 8];
+            reader.read_exact(&mut n)?;
+            usize::try_from(u64::from_le_bytes(n)).map_err(|_| invalid("snapshot too large"))
+        };
+        let (len, blocks, bytes) = (read_u64()?, read_u64()?, read_u64()?);
+        if blocks != len.div_ceil(RESTART) {
+            return Err(invalid("corrupt snapshot"));
+        }
+        let blocks = (0..blocks).map(|_| read_u64()).collect::<io::Result<_>>()?;
+        let mut coded = FrontCoded {
+            bytes: Vec::new(),
+            blocks,
+            len,
+            last: Vec::new(),
+        };
+        reader.take(bytes as u64).read_to_end(&mut coded.bytes)?;
+        if coded.bytes.len() != bytes {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        coded.check()?;
+        Ok(coded)
+    }
+
+    /// Decodes every candidate with bounds checks, leaving the last in
+    /// `last` for further pushes.
+    fn check(&mut self) -> io::Result<()> {
+        let corrupt = || invalid("corrupt snapshot");
+        let mut pos = 0;
+        for index in 0..self.len {
+            if index % RESTART == 0 && self.blocks[index / RESTART] != pos {
+                return Err(corrupt());
+            }
+            let (shared, next) = checked_varint(&self.bytes, pos).ok_or_else(corrupt)?;
+            let (suffix, next) = checked_varint(&self.bytes, next).ok_or_else(corrupt)?;
+            let end = next.checked_add(suffix).ok_or_else(corrupt)?;
+            if shared > self.last.len() || end > self.bytes.len() {
+                return Err(corrupt());
+            }
+            self.last.truncate(shared);
+            self.last.extend_from_slice(&self.bytes[next..end]);
+            pos = end;
+        }
+        if pos != self.bytes.len() {
+            return Err(corrupt());
+        }
+        Ok(())
+    }
+
+    /// Releases spare capacity left by pushing.
+    pub fn shrink_to_fit(&mut self) {
+        self.bytes.shrink_to_fit();
+        self.blocks.shrink_to_fit();
+    }
+}
+
+impl<T: AsRef<[u8]>> FromIterator<T> for FrontCoded {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> FrontCoded {
+        let mut coded = FrontCoded::new();
+        for text in iter {
+            coded.push(text.as_ref());
+        }
+        coded.shrink_to_fit();
+        coded
+    }
+}
+
+/// Decodes the entry at `pos` over `text`, which holds the previous entry,
+/// returning where the next entry starts.
+fn decode(bytes: &[u8], pos: usize, text: &mut Vec<u8>) -> usize {
+    let (shared, pos) = read_varint(bytes, pos);
+    let (suffix, pos) = read_varint(bytes, pos);
+    text.truncate(shared);
+    text.extend_from_slice(&bytes[pos..pos + suffix]);
+    pos + suffix
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+/// [`read_varint`] for untrusted bytes.
+fn checked_varint(bytes: &[u8], mut pos: usize) -> Option<(usize, usize)> {
+    let mut n: usize = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+        let byte = *bytes.get(pos)?;
+        pos += 1;
+        n |= ((byte & 0x7f) as usize).checked_shl(shift)?;
+        if byte < 0x80 {
+            return Some((n, pos));
+        }
+    }
+    None
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_varint(bytes: &[u8], mut pos: usize) -> (usize, usize) {
+    let mut n = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[pos];
+        pos += 1;
+        n |= ((byte & 0x7f) as usize) << shift;
+        if byte < 0x80 {
+            return (n, pos);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::rank;
+    use crate::Algorithm;
+
+    #[test]
+    fn round_trips_candidates() {
+        let texts: Vec<String> = (0..40)
+            .map(|i| format!("src/very/long/shared/directory/file{}.rs", i))
+            .chain(["src".to_string(), "".to_string(), "x".repeat(200)])
+            .collect();
+        let coded: FrontCoded = texts.iter().collect();
+        assert_eq!(coded.len(), texts.len());
+        for (i, text) in texts.iter().enumerate() {
+            assert_eq!(coded.get(i).unwrap(), text.as_bytes());
+        }
+        assert_eq!(coded.get(texts.len()), None);
+        assert!(coded.memory() < texts.iter().map(String::len).sum());
+        let mut seen = Vec::new();
+        coded.for_each(|i, text| seen.push((i, String::from_utf8(text.to_vec()).unwrap())));
+        assert_eq!(seen, texts.into_iter().enumerate().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn restores_snapshots() {
+        let texts: Vec<String> = (0..20).map(|i| format!("a/b/{}", i)).collect();
+        let coded: FrontCoded = texts.iter().collect();
+        let mut snapshot = Vec::new();
+        coded.save(&mut snapshot).unwrap();
+        let mut loaded = FrontCoded::load(&snapshot[..]).unwrap();
+        loaded.push(b"a/b/20");
+        for (i, text) in texts
+            .iter()
+            .chain(["a/b/20".to_string()].iter())
+            .enumerate()
+        {
+            assert_eq!(loaded.get(i).unwrap(), text.as_bytes());
+        }
+        assert!(FrontCoded::load(&snapshot[..snapshot.len() - 1]).is_err());
+        let last = snapshot.len() - 2;
+        snapshot[last] = 0xff;
+        assert!(FrontCoded::load(&snapshot[..]).is_err());
+        assert!(FrontCoded::load(&b"nope"[..]).is_err());
+    }
+
+    #[test]
+    fn ranks_like_plain_candidates() {
+        let texts = [
+            "app/models/user.rb",
+            "app/models/post.rb",
+            "app/views/users",
+            "lib/user.rb",
+        ];
+        let coded: FrontCoded = texts.iter().collect();
+        assert_eq!(
+            coded.rank(&Algorithm::Fzy, b"user"),
+            rank(&Algorithm::Fzy, b"user", &texts)
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! fzf's algorithms: v1 scores the shortest window around the first greedy
+//! match, v2 finds the best scoring alignment with fzf's bonus table.
+
+use crate::Score;
+
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+const BONUS_BOUNDARY: i32 = SCORE_MATCH / 2;
+const BONUS_NON_WORD: i32 = SCORE_MATCH / 2;
+const BONUS_CAMEL_123: i32 = BONUS_BOUNDARY + SCORE_GAP_EXTENSION;
+const BONUS_CONSECUTIVE: i32 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    NonWord,
+    Lower,
+    Upper,
+    Number,
+}
+
+fn char_class(c: u8) -> CharClass {
+    match c {
+        b'a'..=b'z' => CharClass::Lower,
+        b'A'..=b'Z' => CharClass::Upper,
+        b'0'..=b'9' => CharClass::Number,
+        _ => CharClass::NonWord,
+    }
+}
+
+fn bonus_for(prev: CharClass, class: CharClass) -> i32 {
+    if prev == CharClass::NonWord && class != CharClass::NonWord {
+        BONUS_BOUNDARY
+    } else if (prev == CharClass::Lower && class == CharClass::Upper)
+        || (prev != CharClass::Number && class == CharClass::Number)
+    {
+        BONUS_CAMEL_123
+    } else if class == CharClass::NonWord {
+        BONUS_NON_WORD
+    } else {
+        0
+    }
+}
+
+fn calculate_score(pat: &[u8], text: &[u8], start: usize, end: usize) -> (Score, Vec<usize>) {
+    let mut positions = Vec::with_capacity(pat.len());
+    let mut pi = 0;
+    let mut score = 0;
+    let mut in_gap = false;
+    let mut consecutive = 0;
+    let mut first_bonus = 0;
+    let mut prev_class = if start == 0 {
+        CharClass::NonWord
+    } else {
+        char_class(text[start - 1])
+    };
+    for (ti, tc) in text.iter().enumerate().take(end).skip(start) {
+        let class = char_class(*tc);
+        if pi < pat.len() && tc.eq_ignore_ascii_case(&pat[pi]) {
+            positions.push(ti);
+            score += SCORE_MATCH;
+            let mut bonus = bonus_for(prev_class, class);
+            if consecutive == 0 {
+                first_bonus = bonus;
+            } else {
+                // A boundary in the middle of a chunk starts a new chunk.
+                if bonus == BONUS_BOUNDARY {
+                    first_bonus = bonus;
+                }
+                bonus = bonus.max(first_bonus).max(BONUS_CONSECUTIVE);
+            }
+            score += if pi == 0 {
+                bonus * BONUS_FIRST_CHAR_MULTIPLIER
+            } else {
+                bonus
+            };
+            in_gap = false;
+            consecutive += 1;
+            pi += 1;
+        } else {
+            score += if in_gap {
+                SCORE_GAP_EXTENSION
+            } else {
+                SCORE_GAP_START
+            };
+            in_gap = true;
+            consecutive = 0;
+            first_bonus = 0;
+        }
+        prev_class = class;
+    }
+    (Score::from(score), positions)
+}
+
+/// Case-insensitively matches `pat` against `text`, returning the score and
+/// matched positions.
+pub(crate) fn match_v1(pat: &[u8], text: &[u8]) -> Option<(Score, Vec<usize>)> {
+    if pat.is_empty() {
+        return None;
+    }
+
+    let mut pi = 0;
+    let mut start = None;
+    let mut end = None;
+    for (ti, tc) in text.iter().enumerate() {
+        if tc.eq_ignore_ascii_case(&pat[pi]) {
+            start.get_or_insert(ti);
+            pi += 1;
+            if pi == pat.len() {
+                end = Some(ti + 1);
+                break;
+            }
+        }
+    }
+    let (mut start, end) = (start?, end?);
+
+    // Walk back from the end of the match for the shortest window.
+    pi = pat.len() - 1;
+    for ti in (start..end).rev() {
+        if text[ti].eq_ignore_ascii_case(&pat[pi]) {
+            if pi == 0 {
+                start = ti;
+                break;
+            }
+            pi -= 1;
+        }
+    }
+    Some(calculate_score(pat, text, start, end))
+}
+
+/// fzf's v2 algorithm, a Smith-Waterman style search for the highest
+/// scoring alignment. Case-insensitive like [`match_v1`], and scored with the
+/// same bonuses so the two agree whenever there is only one alignment.
+pub(crate) fn match_v2(pat: &[u8], text: &[u8]) -> Option<(Score, Vec<usize>)> {
+    if pat.is_empty() {
+        return None;
+    }
+    let pat = pat.to_ascii_lowercase();
+    let m = pat.len();
+    let idx = text.iter().position(|c| c.eq_ignore_ascii_case(&pat[0]))?;
+
+    // First row and bonuses, along with the first occurrence of each pattern
+    // byte in order, which bounds where each row of the matrix can start.
+    let n = text.len();
+    let mut lower = vec![0; n];
+    let mut bonuses = vec![0; n];
+    let mut h0 = vec![0; n];
+    let mut c0 = vec![0; n];
+    let mut first = Vec::with_capacity(m);
+    let mut last = 0;
+    let mut max_score = 0;
+    let mut max_pos = 0;
+    let mut prev_h0 = 0;
+    let mut prev_class = if idx == 0 {
+        CharClass::NonWord
+    } else {
+        char_class(text[idx - 1])
+    };
+    let mut in_gap = false;
+    for ti in idx..n {
+        let class = char_class(text[ti]);
+        let c = text[ti].to_ascii_lowercase();
+        let bonus = bonus_for(prev_class, class);
+        lower[ti] = c;
+        bonuses[ti] = bonus;
+        prev_class = class;
+        if c == pat[first.len().min(m - 1)] {
+            if first.len() < m {
+                first.push(ti);
+            }
+            last = ti;
+        }
+        if c == pat[0] {
+            h0[ti] = SCORE_MATCH + bonus * BONUS_FIRST_CHAR_MULTIPLIER;
+            c0[ti] = 1;
+            if m == 1 && h0[ti] > max_score {
+                max_score = h0[ti];
+                max_pos = ti;
+                if bonus >= BONUS_BOUNDARY {
+                    break;
+                }
+            }
+            in_gap = false;
+        } else {
+            let gap = if in_gap {
+                SCORE_GAP_EXTENSION
+            } else {
+                SCORE_GAP_START
+            };
+            h0[ti] = (prev_h0 + gap).max(0);
+            c0[ti] = 0;
+            in_gap = true;
+        }
+        prev_h0 = h0[ti];
+    }
+    if first.len() != m {
+        return None;
+    }
+    if m == 1 {
+        return Some((Score::from(max_score), vec![max_pos]));
+    }
+
+    // The score matrix, restricted to the columns between the first
+    // occurrence of the first byte and the last occurrence of the last.
+    let f0 = first[0];
+    let width = last - f0 + 1;
+    let mut h = vec![0; width * m];
+    let mut consecutive_run = vec![0; width * m];
+    h[..width].copy_from_slice(&h0[f0..=last]);
+    consecutive_run[..width].copy_from_slice(&c0[f0..=last]);
+    for pi in 1..m {
+        let row = pi * width;
+        let mut in_gap = false;
+        for col in first[pi]..=last {
+            let j = row + col - f0;
+            let gap = if in_gap {
+                SCORE_GAP_EXTENSION
+            } else {
+                SCORE_GAP_START
+            };
+            let s2 = h[j - 1] + gap;
+            let mut s1 = 0;
+            let mut consecutive = 0;
+            if lower[col] == pat[pi] {
+                s1 = h[j - width - 1] + SCORE_MATCH;
+                let mut bonus = bonuses[col];
+                consecutive = consecutive_run[j - width - 1] + 1;
+                if consecutive > 1 {
+                    let first_bonus = bonuses[col + 1 - consecutive];
+                    if bonus >= BONUS_BOUNDARY && bonus > first_bonus {
+                        consecutive = 1;
+                    } else {
+                        bonus = bonus.max(BONUS_CONSECUTIVE).max(first_bonus);
+                    }
+                }
+                if s1 + bonus < s2 {
+                    s1 += bonuses[col];
+                    consecutive = 0;
+                } else {
+                    s1 += bonus;
+                }
+            }
+            consecutive_run[j] = consecutive;
+            in_gap = s1 < s2;
+            let score = s1.max(s2).max(0);
+            if pi == m - 1 && score > max_score {
+                max_score = score;
+                max_pos = col;
+            }
+            h[j] = score;
+        }
+    }
+
+    // Trace back from the best cell, preferring to keep runs together.
+    let mut positions = Vec::with_capacity(m);
+    let mut pi = m - 1;
+    let mut col = max_pos;
+    let mut prefer_match = true;
+    loop {
+        let row = pi * width;
+        let j = row + col - f0;
+        let s = h[j];
+        let s1 = if pi > 0 && col >= first[pi] {
+            h[j - width - 1]
+        } else {
+            0
+        };
+        let s2 = if col > first[pi] { h[j - 1] } else { 0 };
+        if s > s1 && (s > s2 || s == s2 && prefer_match) {
+            positions.push(col);
+            if pi == 0 {
+                break;
+            }
+            pi -= 1;
+        }
+        prefer_match =
+            consecutive_run[j] > 1 || consecutive_run.get(j + width + 1).is_some_and(|c| *c > 0);
+        col -= 1;
+    }
+    positions.reverse();
+    Some((Score::from(max_score), positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_bonuses() {
+        assert_eq!(match_v1(b"ab", b"ab"), Some((56.0, vec![0, 1])));
+        assert_eq!(match_v1(b"ab", b"xab"), Some((36.0, vec![1, 2])));
+        assert_eq!(match_v1(b"AB", b"xab"), Some((36.0, vec![1, 2])));
+        assert_eq!(match_v1(b"ab", b"ba"), None);
+        assert_eq!(match_v1(b"", b"ab"), None);
+    }
+
+    #[test]
+    fn picks_shortest_window() {
+        // The greedy scan starts at the first 'a', the backward pass narrows
+        // the window to the last one.
+        let (_, positions) = match_v1(b"ab", b"a_a_ab").unwrap();
+        assert_eq!(positions, vec![4, 5]);
+    }
+
+    #[test]
+    fn v2_agrees_with_v1_on_single_alignment() {
+        for (pat, text) in [
+            (&b"ab"[..], &b"ab"[..]),
+            (b"ab", b"xab"),
+            (b"AB", b"xab"),
+            (b"amo", b"app/models/foo"),
+        ] {
+            assert_eq!(match_v2(pat, text), match_v1(pat, text));
+        }
+        assert_eq!(match_v2(b"ab", b"ba"), None);
+        assert_eq!(match_v2(b"", b"ab"), None);
+    }
+
+    #[test]
+    fn v2_finds_best_alignment() {
+        // v1 settles for the first window, v2 prefers the word boundaries.
+        assert_eq!(match_v1(b"ab", b"xab_a_b"), Some((36.0, vec![1, 2])));
+        assert_eq!(match_v2(b"ab", b"xab_a_b"), Some((53.0, vec![4, 6])));
+        assert_eq!(match_v2(b"b", b"ab_b"), Some((32.0, vec![3])));
+    }
+}
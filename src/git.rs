@@ -0,0 +1,178 @@
+//! Boosting files changed in the working tree or in recent commits, which
+//! are the likeliest to be opened next.
+
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "git")]
+use std::io;
+#[cfg(feature = "git")]
+use std::path::Path;
+#[cfg(feature = "git")]
+use std::process::{Command, Stdio};
+
+use crate::{Boost, Candidate, Score};
+
+/// Paths changed in a git repository, boosted when they are candidates.
+#[derive(Debug, Clone)]
+pub struct GitBoost {
+    changed: HashSet<Vec<u8>>,
+    /// How many commits ago each path was last changed.
+    recent: HashMap<Vec<u8>, usize>,
+    commits: usize,
+    /// Added to the score of files with uncommitted changes, 1 by default.
+    pub changed_boost: Score,
+    /// Added in full to files changed in the latest commit, and less the
+    /// further back the commit is, 0.5 by default.
+    pub recent_boost: Score,
+}
+
+impl Default for GitBoost {
+    fn default() -> GitBoost {
+        GitBoost {
+            changed: HashSet::new(),
+            recent: HashMap::new(),
+            commits: 0,
+            changed_boost: 1.0,
+            recent_boost: 0.5,
+        }
+    }
+}
+
+impl GitBoost {
+    pub fn new() -> GitBoost {
+        GitBoost::default()
+    }
+
+    /// Marks a path as having uncommitted changes.
+    pub fn add_changed(&mut self, path: &[u8]) {
+        self.changed.insert(path.to_vec());
+    }
+
+    /// Adds the paths changed by a commit older than any added before.
+    pub fn add_commit<I, P>(&mut self, paths: I)
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        for path in paths {
+            let path = path.as_ref();
+            if !path.is_empty() {
+                self.recent.entry(path.to_vec()).or_insert(self.commits);
+            }
+        }
+        self.commits += 1;
+    }
+
+    /// The changed files of the repository `dir` is in and those of its last
+    /// `commits` commits, as paths relative to `dir`.
+    #[cfg(feature = "git")]
+    pub fn from_repo(dir: &Path, commits: usize) -> io::Result<GitBoost> {
+        let mut boost = GitBoost::new();
+        let changed = git(dir, &["ls-files", "-z", "-m", "-o", "--exclude-standard"])?;
+        let staged = git(
+            dir,
+            &["diff", "-z", "--cached", "--name-only", "--relative"],
+        )?;
+        for path in changed.split(|b| *b == 0).chain(staged.split(|b| *b == 0)) {
+            if !path.is_empty() {
+                boost.add_changed(path);
+            }
+        }
+        // Each commit's NUL terminated paths follow a \x01 marker, itself
+        // followed by a NUL and a newline.
+        let count = format!("-n{}", commits);
+        let log = git(
+            dir,
+            &[
+                "log",
+                "-z",
+                &count,
+                "--name-only",
+                "--relative",
+                "--format=%x01",
+            ],
+        )?;
+        for commit in log.split(|b| *b == 1).skip(1) {
+            let commit = commit.strip_prefix(b"\0\n").unwrap_or(commit);
+            boost.add_commit(commit.split(|b| *b == 0));
+        }
+        Ok(boost)
+    }
+}
+
+impl Boost for GitBoost {
+    fn boost(&self, candidate: &Candidate) -> Score {
+        let mut boost = 0.0;
+        if self.changed.contains(candidate.text) {
+            boost += self.changed_boost;
+        }
+        if let Some(&age) = self.recent.get(candidate.text) {
+            boost += self.recent_boost * (self.commits - age) as Score / self.commits as Score;
+        }
+        boost
+    }
+}
+
+#[cfg(feature = "git")]
+fn git(dir: &Path, args: &[&str]) -> io::Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("git {} failed", args[0])));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boost(git: &GitBoost, text: &[u8]) -> Score {
+        git.boost(&Candidate { index: 0, text })
+    }
+
+    #[test]
+    fn boosts_changed_paths() {
+        let mut git = GitBoost::new();
+        git.add_changed(b"src/lib.rs");
+        git.add_commit(["src/main.rs", "src/lib.rs"]);
+        git.add_commit(["README.md", "src/main.rs"]);
+        assert_eq!(boost(&git, b"src/lib.rs"), 1.5);
+        assert_eq!(boost(&git, b"src/main.rs"), 0.5);
+        assert_eq!(boost(&git, b"README.md"), 0.25);
+        assert_eq!(boost(&git, b"Cargo.toml"), 0.0);
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn reads_repository() {
+        let dir = std::env::temp_dir().join(format!("fzy-rs-git-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let run = |args: &[&str]| git(&dir, args).unwrap();
+        run(&["init", "-q"]);
+        std::fs::write(dir.join("old"), "").unwrap();
+        std::fs::write(dir.join("new"), "").unwrap();
+        run(&["add", "old", "new"]);
+        run(&[
+            "-c",
+            "user.name=t",
+            "-c",
+            "user.email=t@t",
+            "commit",
+            "-qm",
+            "a",
+        ]);
+        std::fs::write(dir.join("new"), "x").unwrap();
+        std::fs::write(dir.join("untracked"), "").unwrap();
+
+        let git = GitBoost::from_repo(&dir, 5).unwrap();
+        assert_eq!(boost(&git, b"new"), 1.5);
+        assert_eq!(boost(&git, b"untracked"), 1.0);
+        assert_eq!(boost(&git, b"old"), 0.5);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
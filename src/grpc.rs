@@ -0,0 +1,192 @@
+//! The gRPC service of `proto/fzy.proto`, served with tonic. Corpora are
+//! [`Daemon`] sessions, so they share its memory limit and are visible to
+//! clients of its socket under the same names.
+
+// tonic's `Status` is large, and it's what every service method returns.
+#![allow(clippy::result_large_err)]
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::algorithm::rank;
+use crate::daemon::{Daemon, Ranking};
+use crate::{Algorithm, FuzzyAlgorithm};
+
+/// The messages and server generated from `proto/fzy.proto`.
+pub mod proto {
+    tonic::include_proto!("fzy");
+}
+
+use proto::fzy_server::{Fzy, FzyServer};
+use proto::{LoadCorpusRequest, LoadCorpusResponse, MatchRequest, MatchResponse, MatchResult};
+
+#[derive(Debug, Clone)]
+pub struct FzyService {
+    daemon: Arc<Daemon>,
+}
+
+impl FzyService {
+    pub fn new(daemon: Arc<Daemon>) -> FzyService {
+        FzyService { daemon }
+    }
+
+    /// Ranks the request's items if it has any, and its corpus otherwise,
+    /// making the query that corpus's active query.
+    fn answer(&self, request: &MatchRequest) -> Result<MatchResponse, Status> {
+        let matches = if request.items.is_empty() {
+            let query = Some(&request.query[..]);
+            let ranked = self
+                .daemon
+                .ranking(&request.corpus, query, |ranking| results(ranking, request));
+            ranked.map_err(Status::not_found)?.0
+        } else {
+            let algorithm = algorithm(&request.algorithm)?.unwrap_or(self.daemon.algorithm());
+            let matches = rank(&algorithm, &request.query, &request.items);
+            results(
+                Ranking {
+                    algorithm: &algorithm,
+                    query: &request.query,
+                    items: &request.items,
+                    matches: &matches,
+                },
+                request,
+            )
+        };
+        Ok(MatchResponse {
+            query: request.query.clone(),
+            matches,
+        })
+    }
+}
+
+/// The algorithm a request names, `None` for the default.
+fn algorithm(name: &str) -> Result<Option<Algorithm>, Status> {
+    match name {
+        "" => Ok(None),
+        name => name.parse().map(Some).map_err(Status::invalid_argument),
+    }
+}
+
+/// The best matches of `ranking`, as many as `request` asks for.
+fn results(ranking: Ranking, request: &MatchRequest) -> Vec<MatchResult> {
+    let len = match request.limit {
+        0 => ranking.matches.len(),
+        limit => ranking.matches.len().min(limit as usize),
+    };
+    (ranking.matches[..len].iter())
+        .map(|m| {
+            let text = &ranking.items[m.index];
+            let mut positions = Vec::new();
+            if request.positions {
+                let found = ranking.algorithm.positions(ranking.query, text);
+                positions.extend(found.unwrap_or_default().into_iter().map(|p| p as u32));
+            }
+            MatchResult {
+                text: text.clone(),
+                index: m.index as u64,
+                score: m.score,
+                positions,
+            }
+        })
+        .collect()
+}
+
+type MatchStream = Pin<Box<dyn Stream<Item = Result<MatchResponse, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Fzy for FzyService {
+    async fn load_corpus(
+        &self,
+        request: Request<LoadCorpusRequest>,
+    ) -> Result<Response<LoadCorpusResponse>, Status> {
+        let request = request.into_inner();
+        let algorithm = algorithm(&request.algorithm)?;
+        let len = (self.daemon)
+            .load(&request.corpus, request.items, algorithm, true)
+            .map_err(Status::resource_exhausted)?;
+        Ok(Response::new(LoadCorpusResponse { len: len as u64 }))
+    }
+
+    async fn r#match(
+        &self,
+        request: Request<MatchRequest>,
+    ) -> Result<Response<MatchResponse>, Status> {
+        self.answer(request.get_ref()).map(Response::new)
+    }
+
+    type MatchStreamStream = MatchStream;
+
+    async fn match_stream(
+        &self,
+        request: Request<Streaming<MatchRequest>>,
+    ) -> Result<Response<MatchStream>, Status> {
+        let service = self.clone();
+        let responses = (request.into_inner()).map(move |request| service.answer(&request?));
+        Ok(Response::new(Box::pin(responses)))
+    }
+}
+
+/// Serves `daemon`'s sessions over gRPC on `addr` forever.
+pub fn serve(daemon: Arc<Daemon>, addr: SocketAddr) -> io::Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        tonic::transport::Server::builder()
+            .add_service(FzyServer::new(FzyService::new(daemon)))
+            .serve(addr)
+            .await
+            .map_err(io::Error::other)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(query: &str, corpus: &str, items: &[&str]) -> MatchRequest {
+        MatchRequest {
+            query: query.into(),
+            corpus: corpus.into(),
+            items: items.iter().map(|item| item.as_bytes().to_vec()).collect(),
+            limit: 1,
+            positions: true,
+            ..MatchRequest::default()
+        }
+    }
+
+    #[test]
+    fn serves_daemon_sessions() {
+        let daemon = Arc::new(Daemon::new(Algorithm::Fzy));
+        let service = FzyService::new(Arc::clone(&daemon));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let loaded = runtime.block_on(service.load_corpus(Request::new(LoadCorpusRequest {
+            corpus: "files".into(),
+            items: vec![b"src/lib.rs".to_vec(), b"src/main.rs".to_vec()],
+            algorithm: String::new(),
+        })));
+        assert_eq!(loaded.unwrap().into_inner().len, 2);
+
+        let response = service.answer(&request("main", "files", &[])).unwrap();
+        assert_eq!(response.matches.len(), 1);
+        assert_eq!(response.matches[0].text, b"src/main.rs");
+        assert_eq!(response.matches[0].positions, vec![4, 5, 6, 7]);
+        // The corpus is a daemon session with the query made active.
+        let json = daemon.respond(r#"{"op":"match","session":"files"}"#);
+        assert!(json.starts_with(b"[{\"text\":\"src/main.rs\""));
+
+        let response = service.answer(&request("b", "", &["a", "b"])).unwrap();
+        assert_eq!(response.matches[0].index, 1);
+        let missing = service.answer(&request("a", "nope", &[])).unwrap_err();
+        assert_eq!(missing.code(), tonic::Code::NotFound);
+        let mut unknown = request("a", "", &["a"]);
+        unknown.algorithm = "nope".into();
+        assert_eq!(
+            service.answer(&unknown).unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Queries entered and items accepted in the picker, persisted per
+//! invocation context.
+//!
+//! A query history holds one `context \t query` line per accepted query,
+//! oldest first, with backslash escapes for tabs, newlines and backslashes.
+//! A selection history holds `context \t seconds \t item` lines, `seconds`
+//! being when the item was accepted, since the Unix epoch.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::SelectionBoost;
+
+/// Only this many of the latest queries are recalled per context.
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct QueryHistory {
+    path: PathBuf,
+    context: Vec<u8>,
+}
+
+impl QueryHistory {
+    pub fn new<P: Into<PathBuf>>(path: P, context: &[u8]) -> QueryHistory {
+        QueryHistory {
+            path: path.into(),
+            context: context.to_vec(),
+        }
+    }
+
+    /// Earlier queries of this context, oldest first, without repeats.
+    pub fn load(&self) -> io::Result<Vec<Vec<u8>>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut queries: Vec<Vec<u8>> = Vec::new();
+        for line in data.split(|b| *b == b'\n') {
+            let mut fields = line.splitn(2, |b| *b == b'\t');
+            let (context, query) = match (fields.next(), fields.next()) {
+                (Some(context), Some(query)) => (unescape(context), unescape(query)),
+                _ => continue,
+            };
+            if context == self.context && !query.is_empty() {
+                queries.retain(|q| *q != query);
+                queries.push(query);
+            }
+        }
+        let skip = queries.len().saturating_sub(MAX_ENTRIES);
+        queries.drain(..skip);
+        Ok(queries)
+    }
+
+    /// Records an entered query.
+    pub fn append(&self, query: &[u8]) -> io::Result<()> {
+        if query.is_empty() {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut line = escape(&self.context);
+        line.push(b'\t');
+        line.extend(escape(query));
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)
+    }
+}
+
+/// Items accepted in the picker, loaded as a [`SelectionBoost`] to rank them
+/// higher next time.
+#[derive(Debug, Clone)]
+pub struct SelectionHistory {
+    path: PathBuf,
+    context: Vec<u8>,
+}
+
+impl SelectionHistory {
+    pub fn new<P: Into<PathBuf>>(path: P, context: &[u8]) -> SelectionHistory {
+        SelectionHistory {
+            path: path.into(),
+            context: context.to_vec(),
+        }
+    }
+
+    /// The items accepted in this context, boosted as of `now`.
+    pub fn load(&self, now: SystemTime) -> io::Result<SelectionBoost> {
+        let mut boost = SelectionBoost::new(now);
+        for selection in read_selections(&self.path)? {
+            if selection.context == self.context {
+                boost.insert(&selection.item, selection.picked);
+            }
+        }
+        Ok(boost)
+    }
+
+    /// Records an item accepted at `picked`.
+    pub fn append(&self, item: &[u8], picked: SystemTime) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let line = Selection {
+            context: self.context.clone(),
+            picked,
+            item: item.to_vec(),
+        }
+        .line();
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)
+    }
+
+    /// Drops the items of every context accepted before `before` from the
+    /// history at `path`, returning how many were dropped.
+    pub fn prune<P: AsRef<Path>>(path: P, before: SystemTime) -> io::Result<usize> {
+        let path = path.as_ref();
+        let selections = read_selections(path)?;
+        let mut kept = Vec::new();
+        let mut dropped = 0;
+        for selection in selections {
+            if selection.picked < before {
+                dropped += 1;
+            } else {
+                kept.extend(selection.line());
+            }
+        }
+        if dropped > 0 {
+            // Renamed into place so that a crash can't leave half a history.
+            let mut tmp = path.as_os_str().to_owned();
+            tmp.push(".tmp");
+            fs::write(&tmp, kept)?;
+            fs::rename(&tmp, path)?;
+        }
+        Ok(dropped)
+    }
+}
+
+/// An entry of a selection history.
+struct Selection {
+    context: Vec<u8>,
+    picked: SystemTime,
+    item: Vec<u8>,
+}
+
+impl Selection {
+    fn line(&self) -> Vec<u8> {
+        let seconds = (self.picked.duration_since(SystemTime::UNIX_EPOCH))
+            .unwrap_or_default()
+            .as_secs();
+        let mut line = escape(&self.context);
+        line.extend(format!("\t{}\t", seconds).bytes());
+        line.extend(escape(&self.item));
+        line.push(b'\n');
+        line
+    }
+}
+
+/// The entries of a selection history, skipping malformed lines.
+fn read_selections(path: &Path) -> io::Result<Vec<Selection>> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut selections = Vec::new();
+    for line in data.split(|b| *b == b'\n') {
+        let mut fields = line.splitn(3, |b| *b == b'\t');
+        let (context, seconds, item) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(context), Some(seconds), Some(item)) => (context, seconds, item),
+            _ => continue,
+        };
+        // A time out of range is as corrupt as one that doesn't parse.
+        let picked = std::str::from_utf8(seconds)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .and_then(|s| SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(s)));
+        if let Some(picked) = picked {
+            selections.push(Selection {
+                context: unescape(context),
+                picked,
+                item: unescape(item),
+            });
+        }
+    }
+    Ok(selections)
+}
+
+pub(crate) fn escape(s: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(s.len());
+    for b in s {
+        match b {
+            b'\\' => out.extend_from_slice(b"\\\\"),
+            b'\t' => out.extend_from_slice(b"\\t"),
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b => out.push(*b),
+        }
+    }
+    out
+}
+
+pub(crate) fn unescape(s: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.iter();
+    while let Some(b) = bytes.next() {
+        if *b != b'\\' {
+            out.push(*b);
+            continue;
+        }
+        match bytes.next() {
+            Some(b't') => out.push(b'\t'),
+            Some(b'n') => out.push(b'\n'),
+            Some(b) => out.push(*b),
+            None => out.push(b'\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Boost, Candidate};
+    use std::{env, process};
+
+    #[test]
+    fn persists_per_context() {
+        let path = env::temp_dir().join(format!("fzy-rs-history-{}/queries", process::id()));
+        let _ = fs::remove_file(&path);
+        let files = QueryHistory::new(&path, b"/src\t--walk .");
+        let other = QueryHistory::new(&path, b"/tmp");
+        files.append(b"main").unwrap();
+        other.append(b"log").unwrap();
+        files.append(b"a\tb\\c\nd").unwrap();
+        files.append(b"main").unwrap();
+        files.append(b"").unwrap();
+        assert_eq!(
+            files.load().unwrap(),
+            vec![b"a\tb\\c\nd".to_vec(), b"main".to_vec()]
+        );
+        assert_eq!(other.load().unwrap(), vec![b"log".to_vec()]);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn boosts_and_prunes_selections() {
+        let dir = env::temp_dir().join(format!("fzy-rs-selections-{}", process::id()));
+        let path = dir.join("selections");
+        let _ = fs::remove_file(&path);
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::UNIX_EPOCH + 100 * day;
+        let here = SelectionHistory::new(&path, b"/src");
+        let there = SelectionHistory::new(&path, b"/tmp");
+        here.append(b"src/lib.rs", now - 40 * day).unwrap();
+        here.append(b"a\tb", now).unwrap();
+        there.append(b"log", now).unwrap();
+
+        let candidate = |text| Candidate { index: 0, text };
+        let boost = here.load(now).unwrap();
+        assert_eq!(boost.boost(&candidate(b"a\tb")), 0.5);
+        assert!(boost.boost(&candidate(b"src/lib.rs")) > 0.0);
+        assert_eq!(boost.boost(&candidate(b"log")), 0.0);
+
+        assert_eq!(SelectionHistory::prune(&path, now - 30 * day).unwrap(), 1);
+        let boost = here.load(now).unwrap();
+        assert_eq!(boost.boost(&candidate(b"src/lib.rs")), 0.0);
+        assert_eq!(there.load(now).unwrap().boost(&candidate(b"log")), 0.5);
+
+        // Times past what SystemTime holds are skipped like any corrupt line.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"/src\t18446744073709551615\tbig\n")
+            .unwrap();
+        let boost = here.load(now).unwrap();
+        assert_eq!(boost.boost(&candidate(b"big")), 0.0);
+        assert_eq!(boost.boost(&candidate(b"a\tb")), 0.5);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+//! A small HTTP/1.1 server answering `POST /match`, for tools that would
+//! rather speak HTTP than link against the library.
+//!
+//! The request body is a JSON object with a `query` string, an optional
+//! `items` array of strings to match instead of the preloaded corpus, and an
+//! optional `limit`. The response is the array written by
+//! [`write_json`](crate::write_json).
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::algorithm::rank;
+use crate::json::{self, Value};
+use crate::output::json_error;
+use crate::{write_json, FuzzyAlgorithm};
+
+/// Requests with a larger body are refused.
+const MAX_BODY: usize = 64 << 20;
+/// Requests whose request line and headers together are larger are refused.
+const MAX_HEAD: usize = 64 << 10;
+/// Connections beyond this many are refused until others close.
+const MAX_CONNECTIONS: usize = 256;
+/// Connections idle for longer are closed.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a refused request may keep sending before its connection closes.
+const LINGER: Duration = Duration::from_secs(1);
+
+/// Serves requests from `listener` forever, one thread per connection.
+pub fn serve<A>(listener: TcpListener, algorithm: A, corpus: Vec<Vec<u8>>) -> io::Result<()>
+where
+    A: FuzzyAlgorithm + Send + Sync + 'static,
+{
+    let state = Arc::new((algorithm, corpus));
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            let _ = respond(
+                &stream,
+                "503 Service Unavailable",
+                &json_error("too many connections"),
+            );
+            continue;
+        }
+        let state = Arc::clone(&state);
+        let connections = Arc::clone(&connections);
+        thread::spawn(move || {
+            let (algorithm, corpus) = &*state;
+            let _ = handle(stream, algorithm, corpus);
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+fn handle<A>(stream: TcpStream, algorithm: &A, corpus: &[Vec<u8>]) -> io::Result<()>
+where
+    A: FuzzyAlgorithm,
+{
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    loop {
+        let mut head_left = MAX_HEAD;
+        let head_too_large = "431 Request Header Fields Too Large";
+        let request_line = match head_line(&mut reader, &mut head_left)? {
+            Some(line) if line.is_empty() => return Ok(()),
+            Some(line) => line,
+            None => return refuse(&stream, reader, head_too_large, "request head too large"),
+        };
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+        let mut content_length = Some(0);
+        let mut close = false;
+        loop {
+            let header = match head_line(&mut reader, &mut head_left)? {
+                Some(header) => header,
+                None => return refuse(&stream, reader, head_too_large, "request head too large"),
+            };
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                let value = value.trim();
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.parse().ok();
+                } else if name.eq_ignore_ascii_case("connection") {
+                    close = value.eq_ignore_ascii_case("close");
+                }
+            }
+        }
+        // Without a length, where the next request starts is unknown.
+        let Some(content_length) = content_length else {
+            return refuse(&stream, reader, "400 Bad Request", "invalid Content-Length");
+        };
+        if content_length > MAX_BODY {
+            return refuse(&stream, reader, "413 Payload Too Large", "body too large");
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+
+        match (method, path) {
+            ("POST", "/match") => match handle_match(algorithm, corpus, &body) {
+                Ok(response) => respond(&stream, "200 OK", &response)?,
+                Err(err) => respond(&stream, "400 Bad Request", &json_error(&err))?,
+            },
+            (_, "/match") => respond(&stream, "405 Method Not Allowed", &json_error("use POST"))?,
+            _ => respond(&stream, "404 Not Found", &json_error("not found"))?,
+        }
+        if close {
+            return Ok(());
+        }
+    }
+}
+
+/// Answers a request that isn't read to its end and closes the connection.
+/// What the client sent meanwhile is read first, as closing with it unread
+/// would reset the connection, losing the answer.
+fn refuse<R: Read>(stream: &TcpStream, reader: R, status: &str, message: &str) -> io::Result<()> {
+    respond(stream, status, &json_error(message))?;
+    stream.shutdown(Shutdown::Write)?;
+    stream.set_read_timeout(Some(LINGER))?;
+    io::copy(&mut reader.take(MAX_BODY as u64), &mut io::sink()).map(|_| ())
+}
+
+/// Reads a line of a request head, which has `left` bytes left to take up,
+/// or `None` when the line would take up more. The line is empty at the end
+/// of the stream.
+fn head_line<R: BufRead>(reader: &mut R, left: &mut usize) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    (reader.by_ref().take(*left as u64)).read_until(b'\n', &mut line)?;
+    if line.len() == *left && !line.ends_with(b"\n") {
+        return Ok(None);
+    }
+    *left -= line.len();
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+fn handle_match<A>(algorithm: &A, corpus: &[Vec<u8>], body: &[u8]) -> Result<Vec<u8>, String>
+where
+    A: FuzzyAlgorithm,
+{
+    let body = std::str::from_utf8(body).map_err(|_| "body is not UTF-8")?;
+    let request = json::parse(body)?;
+    let query = (request.get("query").and_then(Value::as_str))
+        .ok_or("missing string field: query")?
+        .as_bytes();
+    let limit = match request.get("limit") {
+        None | Some(Value::Null) => None,
+        Some(limit) => Some(limit.as_usize().ok_or("limit must be a count")?),
+    };
+    let items: Vec<&[u8]> = match request.get("items") {
+        None | Some(Value::Null) => corpus.iter().map(|c| &c[..]).collect(),
+        Some(items) => (items.as_array().ok_or("items must be an array")?)
+            .iter()
+            .map(|item| item.as_str().map(str::as_bytes))
+            .collect::<Option<_>>()
+            .ok_or("items must be strings")?,
+    };
+
+    let mut matches = rank(algorithm, query, &items);
+    if let Some(limit) = limit {
+        matches.truncate(limit);
+    }
+    let mut out = Vec::new();
+    write_json(&mut out, algorithm, query, &items, &matches).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+fn respond(mut stream: &TcpStream, status: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+        status,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    fn request(path: &str, body: &str) -> (String, String) {
+        send(&format!(
+            "POST {} HTTP/1.1\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            body.len(),
+            body
+        ))
+    }
+
+    /// Sends a raw request, returning the status line and body answering it.
+    fn send(request: &str) -> (String, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let corpus = vec![b"app/m/foo".to_vec(), b"app/models/foo".to_vec()];
+        thread::spawn(move || serve(listener, Algorithm::Fzy, corpus));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[test]
+    fn matches_corpus() {
+        let (status, body) = request("/match", r#"{"query":"amo","limit":1}"#);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(
+            body,
+            "[{\"text\":\"app/models/foo\",\"score\":2.730000000000001,\"index\":1,\"positions\":[0,4,5]}]\n"
+        );
+    }
+
+    #[test]
+    fn matches_items() {
+        let (_, body) = request("/match", r#"{"query":"b","items":["a","b"]}"#);
+        assert_eq!(
+            body,
+            "[{\"text\":\"b\",\"score\":null,\"index\":1,\"positions\":[0]}]\n"
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let (status, body) = request("/match", r#"{"items":[]}"#);
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert_eq!(body, "{\"error\":\"missing string field: query\"}\n");
+        let (status, body) = request("/match", &"[".repeat(100_000));
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert!(body.contains("nested more than"));
+        assert_eq!(request("/", "").0, "HTTP/1.1 404 Not Found");
+
+        let (status, body) = send("POST /match HTTP/1.1\r\nContent-Length: 1x\r\n\r\n");
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert_eq!(body, "{\"error\":\"invalid Content-Length\"}\n");
+        let header = format!("X-Padding: {}\r\n", "a".repeat(MAX_HEAD));
+        let (status, _) = send(&format!("POST /match HTTP/1.1\r\n{}\r\n", header));
+        assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+    }
+}
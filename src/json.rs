@@ -0,0 +1,275 @@
+//! Just enough JSON to read requests sent to the server modes.
+
+use std::str::Chars;
+
+/// How deeply arrays and objects may nest, so that a request can't exhaust
+/// the stack of the thread parsing it.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The value of `key` if this is an object containing it.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// The value as an index or count, if it is a non-negative integer.
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse(s: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: s.chars(),
+        peeked: None,
+        depth: 0,
+    };
+    let value = parser.value()?;
+    match parser.skip_whitespace() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected {:?} after value", c)),
+    }
+}
+
+struct Parser<'a> {
+    chars: Chars<'a>,
+    peeked: Option<char>,
+    /// Arrays and objects open around the current value.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<char> {
+        self.peeked.take().or_else(|| self.chars.next())
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        if self.peeked.is_none() {
+            self.peeked = self.chars.next();
+        }
+        self.peeked
+    }
+
+    fn skip_whitespace(&mut self) -> Option<char> {
+        while self
+            .peek()
+            .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.next();
+        }
+        self.peek()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected {:?}, found {:?}", expected, c)),
+            None => Err(format!("expected {:?}, found end of input", expected)),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for c in word.chars() {
+            self.expect(c)?;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.skip_whitespace() {
+            Some('[' | '{') if self.depth == MAX_DEPTH => {
+                Err(format!("nested more than {} levels deep", MAX_DEPTH))
+            }
+            Some('[' | '{') => {
+                self.depth += 1;
+                let value = self.container();
+                self.depth -= 1;
+                value
+            }
+            Some('n') => self.literal("null", Value::Null),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('"') => self.string().map(Value::String),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("unexpected {:?}", c)),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    /// An array or object, the next character being its opening bracket.
+    fn container(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some('[') => {
+                let mut values = Vec::new();
+                if self.skip_whitespace() == Some(']') {
+                    self.next();
+                    return Ok(Value::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    match self.skip_whitespace() {
+                        Some(',') => self.next(),
+                        _ => break,
+                    };
+                }
+                self.expect(']')?;
+                Ok(Value::Array(values))
+            }
+            _ => {
+                let mut fields = Vec::new();
+                if self.skip_whitespace() == Some('}') {
+                    self.next();
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    match self.skip_whitespace() {
+                        Some(',') => self.next(),
+                        _ => break,
+                    };
+                }
+                self.expect('}')?;
+                Ok(Value::Object(fields))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let mut s = String::new();
+        while let Some(c) = self.peek() {
+            if !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E') {
+                break;
+            }
+            s.push(c);
+            self.next();
+        }
+        s.parse()
+            .map(Value::Number)
+            .map_err(|_| format!("invalid number: {}", s))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let mut code = self.hex4()?;
+                        if (0xd800..0xdc00).contains(&code) {
+                            self.expect('\\')?;
+                            self.expect('u')?;
+                            let low = self.hex4()?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err("invalid surrogate pair".to_string());
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        s.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    _ => return Err("invalid escape".to_string()),
+                },
+                Some(c) => s.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or("invalid \\u escape")?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let value = parse(r#" {"query": "a\"b", "items": ["x", "é😀"], "limit": 3, "n": null, "t": [true, false, -1.5e2]} "#)
+            .unwrap();
+        assert_eq!(value.get("query").and_then(Value::as_str), Some("a\"b"));
+        assert_eq!(
+            value.get("items").and_then(Value::as_array),
+            Some(&[Value::String("x".into()), Value::String("é😀".into())][..])
+        );
+        assert_eq!(value.get("limit").and_then(Value::as_usize), Some(3));
+        assert_eq!(value.get("n"), Some(&Value::Null));
+        assert_eq!(
+            value.get("t"),
+            Some(&Value::Array(vec![
+                Value::Bool(true),
+                Value::Bool(false),
+                Value::Number(-150.0)
+            ]))
+        );
+        assert_eq!(parse("[]"), Ok(Value::Array(vec![])));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert!(parse("").is_err());
+        assert!(parse("{\"a\" 1}").is_err());
+        assert!(parse("[1,]").is_err());
+        assert!(parse("\"abc").is_err());
+        assert!(parse("1 2").is_err());
+        assert!(parse(r#""\ud800\u0000""#).is_err());
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            parse(&nested(100_000)),
+            Err(format!("nested more than {} levels deep", MAX_DEPTH))
+        );
+    }
+}
@@ -1,4 +1,97 @@
-use std::mem::swap;
+use std::cmp::Ordering;
+
+mod algorithm;
+mod ansi;
+mod bits;
+mod boost;
+mod case;
+#[cfg(unix)]
+pub mod client;
+pub mod compat;
+mod completion;
+#[cfg(unix)]
+pub mod daemon;
+mod delta;
+mod exclude;
+mod explain;
+pub mod ffi;
+mod fields;
+mod filter;
+mod flx;
+mod fold;
+mod front;
+mod fzf;
+pub mod git;
+#[cfg(all(unix, feature = "grpc"))]
+pub mod grpc;
+pub mod history;
+pub mod http;
+mod json;
+pub mod lsp;
+mod matcher;
+mod matrix;
+mod metrics;
+mod output;
+pub mod palette;
+pub mod picker;
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+mod progress;
+mod project;
+mod query;
+mod radix;
+mod ranked;
+#[cfg(feature = "readline")]
+pub mod readline;
+pub mod record;
+mod rewrite;
+mod sanitize;
+mod session;
+mod shared;
+#[cfg(unix)]
+pub mod source;
+mod sublime;
+mod suggest;
+pub mod tags;
+mod trace;
+mod transform;
+#[cfg(unix)]
+pub mod tty;
+mod width;
+
+pub use algorithm::{Algorithm, FuzzyAlgorithm};
+pub use ansi::{raw_positions, strip_ansi, AnsiMatching};
+pub use bits::BitVec;
+pub use boost::{Boost, Boosted, Decay, RecencyBoost, SelectionBoost};
+pub use case::{Case, Cased};
+pub use delta::{Delta, PathIndex};
+pub use exclude::{has_extension, Exclude};
+pub use explain::{
+    alignment, explain, explain_comparison, Breakdown, Comparison, Contribution, Explanation,
+};
+pub use fields::{FieldMatching, FieldRange, Fields};
+pub use filter::{
+    filter_lines, filter_lines_observed, filter_lines_partial, filter_reader,
+    filter_reader_observed, filter_reader_partial, parent_dir, read_candidates, Candidate,
+    FilterOptions, Filtered, Group,
+};
+pub use front::FrontCoded;
+pub use matcher::{Matcher, MatcherPool, PooledMatcher};
+pub use matrix::{Alignments, ScoreMatrix};
+pub use metrics::Metrics;
+pub use output::{write_grouped, write_json, write_terminated, write_tsv};
+pub use progress::{Observer, Progress};
+pub use project::{project_score, unproject_score, SortKey, PROJECTED_MAX};
+pub use query::{Query, Term, TermKind};
+pub use radix::RadixTree;
+pub use ranked::{RankedResults, RankedSet};
+pub use rewrite::Rewriter;
+pub use sanitize::{sanitize, sanitized_chars, Control, Sanitize};
+pub use session::QuerySession;
+pub use shared::{SharedResults, Snapshot};
+pub use suggest::did_you_mean;
+pub use transform::{Step, Transform};
+pub use width::{char_width, display_width, truncate_to_width, Truncated};
 
 pub type Score = f64;
 
@@ -13,6 +106,23 @@ const SCORE_MATCH_WORD: Score = 0.8;
 const SCORE_MATCH_CAPITAL: Score = 0.7;
 const SCORE_MATCH_DOT: Score = 0.6;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Match {
+    pub index: usize,
+    pub score: Score,
+}
+
+impl Match {
+    /// Orders by descending score, then by ascending index.
+    pub fn rank_cmp(&self, other: &Match) -> Ordering {
+        other
+            .score
+            .partial_cmp(&self.score)
+            .unwrap_or(Ordering::Equal)
+            .then(self.index.cmp(&other.index))
+    }
+}
+
 #[inline]
 fn max(f1: Score, f2: Score) -> Score {
     if f1 > f2 {
@@ -54,7 +164,8 @@ fn compute_bonuses(text: &[u8]) -> Vec<Score> {
     bonuses
 }
 
-pub fn has_match(pat: &[u8], text: &[u8]) -> bool {
+pub fn has_match<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> bool {
+    let (pat, text) = (pat.as_ref(), text.as_ref());
     if pat.is_empty() {
         return true;
     }
@@ -68,64 +179,170 @@ pub fn has_match(pat: &[u8], text: &[u8]) -> bool {
             return true;
         }
     }
-    return pi == pat.len();
+    pi == pat.len()
+}
+
+/// Which candidates [`has_match`] accepts, as a bitset indexed like
+/// `candidates`. Rejecting a candidate allocates nothing; as the query grows,
+/// narrow the result with [`BitVec::retain`] rather than checking every
+/// candidate again.
+pub fn has_match_all<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, candidates: &[T]) -> BitVec {
+    let pat = pat.as_ref();
+    let mut matched = BitVec::new(candidates.len());
+    for (index, text) in candidates.iter().enumerate() {
+        let text = text.as_ref();
+        if text.len() >= pat.len() && has_match(pat, text) {
+            matched.set(index, true);
+        }
+    }
+    matched
+}
+
+#[inline]
+fn match_row(
+    pi: usize,
+    pc: u8,
+    gap_score: Score,
+    text: &[u8],
+    bonuses: &[Score],
+    (prev_d, prev_m): (&[Score], &[Score]),
+    (cur_d, cur_m): (&mut [Score], &mut [Score]),
+) {
+    let mut prev_score = SCORE_MIN;
+    for (ti, tc) in text.iter().enumerate() {
+        if pc == *tc {
+            let score = if pi == 0 {
+                (ti as Score) * SCORE_GAP_LEADING + bonuses[ti]
+            } else if ti > 0 {
+                max(
+                    prev_m[ti - 1] + bonuses[ti],
+                    prev_d[ti - 1] + SCORE_MATCH_CONSECUTIVE,
+                )
+            } else {
+                SCORE_MIN
+            };
+            cur_d[ti] = score;
+            prev_score = max(score, prev_score + gap_score);
+            cur_m[ti] = prev_score;
+        } else {
+            cur_d[ti] = SCORE_MIN;
+            prev_score += gap_score;
+            cur_m[ti] = prev_score;
+        }
+    }
+}
+
+#[inline]
+fn gap_score(pi: usize, pat_len: usize) -> Score {
+    if pi == pat_len - 1 {
+        SCORE_GAP_TRAILING
+    } else {
+        SCORE_GAP_INNER
+    }
 }
 
-pub fn score(pat: &[u8], text: &[u8]) -> Score {
-    if pat.len() == 0 || pat.len() > text.len() {
+pub fn score<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> Score {
+    let (pat, text) = (pat.as_ref(), text.as_ref());
+    if pat.is_empty() || pat.len() > text.len() {
         return SCORE_MIN;
     }
-    if pat.len() == text.len() {
+    if is_exact_match(pat, text) {
         return SCORE_MAX;
     }
+    score_dp(pat, text)
+}
 
-    let bonuses = compute_bonuses(text);
+/// Whether `text` is `pat` up to ASCII case, which [`score`] ranks above
+/// any other match.
+pub fn is_exact_match<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> bool {
+    pat.as_ref().eq_ignore_ascii_case(text.as_ref())
+}
 
-    let mut prev_d = vec![0.0; text.len()];
-    let mut cur_d = vec![0.0; text.len()];
-    let mut prev_m = vec![0.0; text.len()];
-    let mut cur_m = vec![0.0; text.len()];
+/// The full DP pass behind `score`, for `0 < pat.len() <= text.len()`.
+fn score_dp(pat: &[u8], text: &[u8]) -> Score {
+    Matcher::new().score_dp(pat, text)
+}
 
-    for (pi, pc) in pat.to_ascii_lowercase().iter().enumerate() {
-        let mut prev_score = SCORE_MIN;
-        let gap_score = if pi == pat.len() - 1 {
-            SCORE_GAP_TRAILING
-        } else {
-            SCORE_GAP_INNER
-        };
-
-        for (ti, tc) in text.to_ascii_lowercase().iter().enumerate() {
-            if pc == tc {
-                let score = if pi == 0 {
-                    (ti as Score) * SCORE_GAP_LEADING + bonuses[ti]
-                } else if ti > 0 {
-                    max(
-                        prev_m[ti - 1] + bonuses[ti],
-                        prev_d[ti - 1] + SCORE_MATCH_CONSECUTIVE,
-                    )
-                } else {
-                    SCORE_MIN
-                };
-                cur_d[ti] = score;
-                prev_score = max(score, prev_score + gap_score);
-                cur_m[ti] = prev_score;
-            } else {
-                cur_d[ti] = SCORE_MIN;
-                prev_score += gap_score;
-                cur_m[ti] = prev_score;
-            }
-        }
+/// Returns the indices into `text` of the optimal alignment of `pat`, or
+/// `None` if `pat` doesn't match case-insensitively.
+pub fn positions<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> Option<Vec<usize>> {
+    positions_folding(pat.as_ref(), text.as_ref(), true)
+}
 
-        swap(&mut cur_d, &mut prev_d);
-        swap(&mut cur_m, &mut prev_m);
+/// The index of the candidate `pat` matches best, the first on ties, or
+/// `None` if none match. Cheaper than ranking every candidate for picking one.
+pub fn choose_best<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, candidates: &[T]) -> Option<usize> {
+    let pat = pat.as_ref();
+    (candidates.iter().enumerate())
+        .filter_map(|(index, text)| {
+            algorithm::match_candidate(&Algorithm::Fzy, pat, text.as_ref(), index)
+        })
+        .min_by(Match::rank_cmp)
+        .map(|m| m.index)
+}
+
+/// [`score`] with bytes only matching their own case.
+pub(crate) fn score_respecting_case(pat: &[u8], text: &[u8]) -> Score {
+    if pat.is_empty() || pat.len() > text.len() {
+        return SCORE_MIN;
+    }
+    if pat == text {
+        return SCORE_MAX;
     }
-    *prev_m.last().unwrap()
+    Matcher::new().score_dp_folding(pat, text, false)
+}
+
+/// [`positions`] with bytes only matching their own case.
+pub(crate) fn positions_respecting_case(pat: &[u8], text: &[u8]) -> Option<Vec<usize>> {
+    positions_folding(pat, text, false)
+}
+
+fn positions_folding(pat: &[u8], text: &[u8], fold: bool) -> Option<Vec<usize>> {
+    if pat.is_empty() {
+        return Some(Vec::new());
+    }
+    ScoreMatrix::folding(pat, text, fold)?.traceback()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn chooses_best_candidate() {
+        let profiles = ["production", "staging", "prod-eu", "dev"];
+        assert_eq!(choose_best(b"prod", &profiles), Some(2));
+        assert_eq!(choose_best(b"stg", &profiles), Some(1));
+        assert_eq!(choose_best(b"", &profiles), Some(0));
+        assert_eq!(choose_best(b"qa", &profiles), None);
+        assert_eq!(choose_best("a", &Vec::<String>::new()), None);
+    }
+
+    #[test]
+    fn matches_all_candidates() {
+        let candidates = ["app/models/foo", "app/m/foo", "bar", "ap"];
+        let mut matched = has_match_all("amo", &candidates);
+        assert_eq!(matched.ones().collect::<Vec<_>>(), vec![0, 1]);
+        matched.retain(|i| has_match("amod", candidates[i]));
+        assert_eq!(matched.ones().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(has_match_all("", &candidates).count_ones(), 4);
+    }
+
+    #[test]
+    fn accepts_any_bytes() {
+        use std::borrow::Cow;
+        let text = String::from("app/models/order");
+        let expected = score(b"amor", text.as_bytes());
+        assert_eq!(score("amor", &text), expected);
+        assert_eq!(
+            score(Cow::from(&b"amor"[..]), text.clone().into_bytes()),
+            expected
+        );
+        assert!(has_match(String::from("amor"), text.clone()));
+        assert_eq!(positions("ao", "a/o"), Some(vec![0, 2]));
+        assert_eq!(choose_best("ord", &[text]), Some(0));
+    }
+
     #[test]
     fn should_prefer_starts_of_words() {
         // App/Models/Order is better than App/MOdels/zRder
@@ -167,6 +384,17 @@ mod tests {
         // Exact fzy_score is SCORE_MAX
         assert_eq!(SCORE_MAX, score(b"abc", b"abc"));
         assert_eq!(SCORE_MAX, score(b"aBc", b"abC"));
+        assert!(is_exact_match("aBc", "abC"));
+    }
+
+    #[test]
+    fn score_equal_length_mismatch() {
+        // Equal lengths alone used to score SCORE_MAX.
+        assert_eq!(SCORE_MIN, score(b"abc", b"xyz"));
+        assert_eq!(SCORE_MIN, score(b"abc", b"acb"));
+        assert!(!is_exact_match("abc", "xyz"));
+        assert_eq!(SCORE_MIN, score_respecting_case(b"abc", b"abC"));
+        assert_eq!(SCORE_MAX, score_respecting_case(b"abC", b"abC"));
     }
 
     #[test]
@@ -261,8 +489,8 @@ mod tests {
     #[test]
     fn score_long_string() {
         let string: [u8; 4096] = [b'a'; 4096];
-        assert_eq!(SCORE_MIN, score(&string, b"aa"));
-        assert_eq!(SCORE_MAX, score(&string, &string));
+        assert_eq!(SCORE_MIN, score(string, b"aa"));
+        assert_eq!(SCORE_MAX, score(string, string));
     }
 
     #[test]
@@ -287,4 +515,18 @@ mod tests {
         assert!(!has_match(b"a", b"b"));
         assert!(!has_match(b"ass", b"tags"));
     }
+
+    #[test]
+    fn positions_of_matches() {
+        assert_eq!(Some(vec![0, 4, 5]), positions(b"amo", b"app/models/foo"));
+        assert_eq!(
+            Some(vec![0, 4, 11, 12]),
+            positions(b"amor", b"app/models/order")
+        );
+        assert_eq!(Some(vec![0, 2, 4]), positions(b"abc", b"a/b/c"));
+        assert_eq!(Some(vec![0, 1, 2]), positions(b"ABC", b"abc"));
+        assert_eq!(Some(vec![]), positions(b"", b"abc"));
+        assert_eq!(None, positions(b"abc", b"acb"));
+        assert_eq!(None, positions(b"abcd", b"abc"));
+    }
 }
@@ -0,0 +1,240 @@
+//! Helpers for language servers ranking completions and symbols.
+
+use std::collections::HashMap;
+
+use crate::algorithm::match_candidate;
+use crate::{project_score, Algorithm, FuzzyAlgorithm, Match, Score, PROJECTED_MAX};
+
+/// `sortText` values putting `matches`, best first as ranked, in the same
+/// order when compared as strings, as LSP clients do. Ranks are zero padded
+/// to the same width so that `10` doesn't sort before `9`.
+pub fn sort_texts(matches: &[Match]) -> Vec<String> {
+    let width = matches.len().saturating_sub(1).to_string().len();
+    (0..matches.len())
+        .map(|rank| format!("{:0width$}", rank, width = width))
+        .collect()
+}
+
+/// A `sortText` for one score on its own, for results ranked separately
+/// such as across incremental responses: higher scores give smaller texts,
+/// equal ones the same text. Scores closer than the resolution of
+/// [`project_score`] may tie.
+pub fn score_sort_text(score: Score) -> String {
+    let width = PROJECTED_MAX.to_string().len();
+    let inverted = PROJECTED_MAX - project_score(score);
+    format!("{:0width$}", inverted, width = width)
+}
+
+/// The kinds of LSP's `SymbolKind`, with the same numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    File = 1,
+    Module,
+    Namespace,
+    Package,
+    Class,
+    Method,
+    Property,
+    Field,
+    Constructor,
+    Enum,
+    Interface,
+    Function,
+    Variable,
+    Constant,
+    String,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Key,
+    Null,
+    EnumMember,
+    Struct,
+    Event,
+    Operator,
+    TypeParameter,
+}
+
+/// A workspace symbol, with whatever locates it in `location`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol<T> {
+    pub name: String,
+    /// The name of the enclosing type or module, if any.
+    pub container: Option<String>,
+    pub kind: SymbolKind,
+    pub location: T,
+}
+
+/// A matching symbol as returned by [`SymbolSearch::search`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolMatch<'a, T> {
+    pub symbol: &'a Symbol<T>,
+    pub score: Score,
+    /// Matched bytes of the name, for highlighting; empty when only the
+    /// container matched.
+    pub positions: Vec<usize>,
+}
+
+/// Ranks symbols for `workspace/symbol`: by their name foremost, a little
+/// by their container, and by their kind.
+#[derive(Debug, Clone)]
+pub struct SymbolSearch<T, A = Algorithm> {
+    algorithm: A,
+    symbols: Vec<Symbol<T>>,
+    name_weight: Score,
+    container_weight: Score,
+    kind_boosts: HashMap<SymbolKind, Score>,
+}
+
+impl<T> SymbolSearch<T> {
+    pub fn new() -> SymbolSearch<T> {
+        SymbolSearch::with_algorithm(Algorithm::Fzy)
+    }
+}
+
+impl<T> Default for SymbolSearch<T> {
+    fn default() -> SymbolSearch<T> {
+        SymbolSearch::new()
+    }
+}
+
+impl<T, A: FuzzyAlgorithm> SymbolSearch<T, A> {
+    pub fn with_algorithm(algorithm: A) -> SymbolSearch<T, A> {
+        SymbolSearch {
+            algorithm,
+            symbols: Vec::new(),
+            name_weight: 1.0,
+            container_weight: 0.1,
+            kind_boosts: HashMap::new(),
+        }
+    }
+
+    /// How much the scores of the name and the container count, 1 and 0.1
+    /// by default. A symbol whose name doesn't match is found through its
+    /// container alone.
+    pub fn set_weights(&mut self, name: Score, container: Score) {
+        self.name_weight = name;
+        self.container_weight = container;
+    }
+
+    /// Added to the score of matching symbols of `kind`, e.g. to rank types
+    /// above variables.
+    pub fn set_kind_boost(&mut self, kind: SymbolKind, boost: Score) {
+        self.kind_boosts.insert(kind, boost);
+    }
+
+    /// Adds a symbol and returns its index.
+    pub fn add(&mut self, symbol: Symbol<T>) -> usize {
+        self.symbols.push(symbol);
+        self.symbols.len() - 1
+    }
+
+    pub fn symbols(&self) -> &[Symbol<T>] {
+        &self.symbols
+    }
+
+    /// Matching symbols, best first.
+    pub fn search(&self, query: &str) -> Vec<SymbolMatch<'_, T>> {
+        let query = query.as_bytes();
+        let mut matches: Vec<Match> = (self.symbols.iter().enumerate())
+            .filter_map(|(index, symbol)| self.score(query, symbol, index))
+            .collect();
+        matches.sort_by(Match::rank_cmp);
+        (matches.into_iter())
+            .map(|m| {
+                let symbol = &self.symbols[m.index];
+                let positions = (self.algorithm)
+                    .positions(query, symbol.name.as_bytes())
+                    .unwrap_or_default();
+                SymbolMatch {
+                    symbol,
+                    score: m.score,
+                    positions,
+                }
+            })
+            .collect()
+    }
+
+    fn score(&self, query: &[u8], symbol: &Symbol<T>, index: usize) -> Option<Match> {
+        let weighted = |text: &str, weight: Score| {
+            let m = match_candidate(&self.algorithm, query, text.as_bytes(), index)?;
+            // An unweighted infinite score would otherwise make a NaN.
+            Some(if weight == 0.0 { 0.0 } else { m.score * weight })
+        };
+        let name = weighted(&symbol.name, self.name_weight);
+        let container = (symbol.container.as_deref())
+            .and_then(|container| weighted(container, self.container_weight));
+        if name.is_none() && container.is_none() {
+            return None;
+        }
+        let boost = self.kind_boosts.get(&symbol.kind).copied().unwrap_or(0.0);
+        let score = name.unwrap_or(0.0) + container.unwrap_or(0.0) + boost;
+        Some(Match { index, score })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::rank;
+    use crate::{Algorithm, SCORE_MIN};
+
+    #[test]
+    fn sorts_as_ranked() {
+        let candidates: Vec<String> = (0..12)
+            .map(|i| format!("{}x{}", "a".repeat(i), i))
+            .collect();
+        let matches = rank(&Algorithm::Fzy, b"x", &candidates);
+        let texts = sort_texts(&matches);
+        assert_eq!(texts[0], "00");
+        assert_eq!(texts[11], "11");
+        let mut sorted = texts.clone();
+        sorted.sort();
+        assert_eq!(sorted, texts);
+        assert!(sort_texts(&[]).is_empty());
+        assert_eq!(sort_texts(&matches[..1]), vec!["0"]);
+    }
+
+    #[test]
+    fn sorts_by_score() {
+        assert_eq!(score_sort_text(Score::INFINITY), "00000");
+        assert_eq!(score_sort_text(SCORE_MIN), "10000");
+        assert!(score_sort_text(2.5) < score_sort_text(0.3));
+        assert!(score_sort_text(0.3) < score_sort_text(-1.0));
+    }
+
+    fn symbol(name: &str, container: Option<&str>, kind: SymbolKind) -> Symbol<u32> {
+        Symbol {
+            name: name.to_string(),
+            container: container.map(str::to_string),
+            kind,
+            location: 0,
+        }
+    }
+
+    #[test]
+    fn searches_symbols() {
+        let mut search = SymbolSearch::new();
+        search.add(symbol("parse_args", Some("main"), SymbolKind::Function));
+        search.add(symbol("Parser", Some("parse"), SymbolKind::Struct));
+        search.add(symbol("run", Some("parser"), SymbolKind::Method));
+        search.add(symbol("x", None, SymbolKind::Variable));
+        let names = |search: &SymbolSearch<u32>, query| -> Vec<String> {
+            (search.search(query).iter())
+                .map(|m| m.symbol.name.clone())
+                .collect()
+        };
+        assert_eq!(names(&search, "ars"), vec!["Parser", "parse_args", "run"]);
+
+        let found = search.search("ars");
+        assert_eq!(found[0].positions, vec![1, 2, 3]);
+        assert!(found[2].positions.is_empty());
+        assert!(found[2].score < found[1].score);
+
+        search.set_kind_boost(SymbolKind::Function, 1.0);
+        assert_eq!(names(&search, "ars")[0], "parse_args");
+        search.set_weights(1.0, 0.0);
+        assert!(search.search("ars").iter().all(|m| m.score.is_finite()));
+    }
+}
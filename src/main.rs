@@ -0,0 +1,13 @@
+#[cfg(unix)]
+mod cli;
+
+#[cfg(unix)]
+fn main() {
+    cli::main();
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("fzy-rs: the command line tool only runs on unix");
+    std::process::exit(2);
+}
@@ -0,0 +1,244 @@
+use std::mem::swap;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use crate::{
+    compute_bonus, fold, gap_score, has_match, is_exact_match, match_row, Match, Score, SCORE_MAX,
+    SCORE_MIN,
+};
+
+/// fzy's scoring with its scratch buffers kept between calls, so scoring many
+/// candidates doesn't allocate per candidate.
+#[derive(Debug, Default)]
+pub struct Matcher {
+    lower: Vec<u8>,
+    bonuses: Vec<Score>,
+    rows: [Vec<Score>; 4],
+}
+
+impl Matcher {
+    pub fn new() -> Matcher {
+        Matcher::default()
+    }
+
+    /// A matcher whose buffers already fit candidates of up to `len` bytes.
+    pub fn with_capacity(len: usize) -> Matcher {
+        let mut matcher = Matcher::new();
+        matcher.reserve(len);
+        matcher
+    }
+
+    fn reserve(&mut self, len: usize) {
+        self.lower.resize(len, 0);
+        self.bonuses.resize(len, 0.0);
+        for row in &mut self.rows {
+            row.resize(len, 0.0);
+        }
+    }
+
+    /// Same as [`score`](crate::score).
+    pub fn score<P: AsRef<[u8]>, T: AsRef<[u8]>>(&mut self, pat: P, text: T) -> Score {
+        let (pat, text) = (pat.as_ref(), text.as_ref());
+        if pat.is_empty() || pat.len() > text.len() {
+            return SCORE_MIN;
+        }
+        if is_exact_match(pat, text) {
+            return SCORE_MAX;
+        }
+        self.score_dp(pat, text)
+    }
+
+    pub(crate) fn score_dp(&mut self, pat: &[u8], text: &[u8]) -> Score {
+        self.score_dp_folding(pat, text, true)
+    }
+
+    /// The DP pass, matching bytes case-insensitively only with `fold`.
+    pub(crate) fn score_dp_folding(&mut self, pat: &[u8], text: &[u8], fold: bool) -> Score {
+        let n = text.len();
+        if self.lower.len() < n {
+            self.reserve(n);
+        }
+        let lower = &mut self.lower[..n];
+        let bonuses = &mut self.bonuses[..n];
+        if fold {
+            fold::fold_into(text, lower);
+        } else {
+            lower.copy_from_slice(text);
+        }
+        let mut prev = b'/';
+        for (bonus, cur) in bonuses.iter_mut().zip(text) {
+            *bonus = compute_bonus(*cur, prev);
+            prev = *cur;
+        }
+
+        let [prev_d, prev_m, cur_d, cur_m] = &mut self.rows;
+        let (mut prev_d, mut prev_m) = (&mut prev_d[..n], &mut prev_m[..n]);
+        let (mut cur_d, mut cur_m) = (&mut cur_d[..n], &mut cur_m[..n]);
+        for (pi, pc) in pat.iter().enumerate() {
+            match_row(
+                pi,
+                if fold { pc.to_ascii_lowercase() } else { *pc },
+                gap_score(pi, pat.len()),
+                lower,
+                bonuses,
+                (prev_d, prev_m),
+                (cur_d, cur_m),
+            );
+            swap(&mut cur_d, &mut prev_d);
+            swap(&mut cur_m, &mut prev_m);
+        }
+        prev_m[n - 1]
+    }
+
+    /// Matches `query` against every candidate, best first.
+    pub fn rank<Q, T>(&mut self, query: Q, candidates: &[T]) -> Vec<Match>
+    where
+        Q: AsRef<[u8]>,
+        T: AsRef<[u8]>,
+    {
+        let mut matches = Vec::new();
+        self.rank_into(query, candidates, &mut matches);
+        matches
+    }
+
+    /// [`rank`](Matcher::rank) into `matches`, which is cleared first. Once
+    /// both the matcher and `matches` have grown to fit, ranking allocates
+    /// nothing.
+    pub fn rank_into<Q, T>(&mut self, query: Q, candidates: &[T], matches: &mut Vec<Match>)
+    where
+        Q: AsRef<[u8]>,
+        T: AsRef<[u8]>,
+    {
+        let query = query.as_ref();
+        matches.clear();
+        for (index, text) in candidates.iter().enumerate() {
+            let text = text.as_ref();
+            if has_match(query, text) {
+                let score = self.score(query, text);
+                matches.push(Match { index, score });
+            }
+        }
+        // Indices break ties, so an unstable sort gives the same order
+        // without a merge buffer.
+        matches.sort_unstable_by(Match::rank_cmp);
+    }
+}
+
+/// Hands out [`Matcher`]s to worker threads and takes them back when the
+/// returned guard is dropped, so concurrent requests neither share a matcher
+/// nor allocate their buffers from scratch.
+#[derive(Debug)]
+pub struct MatcherPool {
+    idle: Mutex<Vec<Matcher>>,
+    capacity: usize,
+}
+
+impl MatcherPool {
+    /// A pool of `matchers` matchers pre-sized for candidates of up to
+    /// `capacity` bytes. More are created on demand if they run out.
+    pub fn new(matchers: usize, capacity: usize) -> MatcherPool {
+        MatcherPool {
+            idle: Mutex::new(
+                (0..matchers)
+                    .map(|_| Matcher::with_capacity(capacity))
+                    .collect(),
+            ),
+            capacity,
+        }
+    }
+
+    pub fn get(&self) -> PooledMatcher<'_> {
+        let matcher = self.idle.lock().unwrap().pop();
+        PooledMatcher {
+            pool: self,
+            matcher: Some(matcher.unwrap_or_else(|| Matcher::with_capacity(self.capacity))),
+        }
+    }
+
+    /// Number of matchers waiting in the pool.
+    pub fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+}
+
+/// A matcher borrowed from a [`MatcherPool`], returned to it on drop.
+#[derive(Debug)]
+pub struct PooledMatcher<'a> {
+    pool: &'a MatcherPool,
+    matcher: Option<Matcher>,
+}
+
+impl Deref for PooledMatcher<'_> {
+    type Target = Matcher;
+
+    fn deref(&self) -> &Matcher {
+        self.matcher.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledMatcher<'_> {
+    fn deref_mut(&mut self) -> &mut Matcher {
+        self.matcher.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledMatcher<'_> {
+    fn drop(&mut self) {
+        if let Some(matcher) = self.matcher.take() {
+            if let Ok(mut idle) = self.pool.idle.lock() {
+                idle.push(matcher);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score;
+    use std::thread;
+
+    #[test]
+    fn reuses_buffers() {
+        let mut matcher = Matcher::with_capacity(4);
+        for (pat, text) in [
+            (&b"amor"[..], &b"app/models/order"[..]),
+            (b"amo", b"app/m/foo"),
+            (b"ab", b"Ab"),
+            (b"AM", b"xAxM"),
+            (b"", b"abc"),
+            (b"abc", b"xyz"),
+        ] {
+            assert_eq!(matcher.score(pat, text), score(pat, text));
+        }
+    }
+
+    #[test]
+    fn ranks_into_buffer() {
+        let mut matcher = Matcher::new();
+        let candidates = ["app/m/foo", "app/models/foo", "bar"];
+        let mut matches = Vec::with_capacity(candidates.len());
+        let buffer = matches.as_ptr();
+        for query in [&b"amo"[..], b"a", b"", b"zz"] {
+            matcher.rank_into(query, &candidates, &mut matches);
+            assert_eq!(matches, matcher.rank(query, &candidates));
+            assert_eq!(matches.as_ptr(), buffer);
+        }
+    }
+
+    #[test]
+    fn pool_across_threads() {
+        let pool = MatcherPool::new(2, 64);
+        let candidates = ["app/m/foo", "app/models/foo", "bar"];
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let ranked = pool.get().rank(b"amo", &candidates);
+                    let ids: Vec<usize> = ranked.iter().map(|m| m.index).collect();
+                    assert_eq!(ids, vec![1, 0]);
+                });
+            }
+        });
+        assert!(pool.idle() >= 2);
+    }
+}
@@ -0,0 +1,244 @@
+//! fzy's full score matrices, for inspecting how a candidate scores and for
+//! tracing back alignments other than the one [`positions`](crate::positions)
+//! picks.
+
+use std::collections::BinaryHeap;
+
+use crate::{
+    compute_bonuses, fold, gap_score, match_row, Score, SortKey, SCORE_GAP_INNER,
+    SCORE_GAP_LEADING, SCORE_GAP_TRAILING, SCORE_MATCH_CONSECUTIVE, SCORE_MIN,
+};
+
+/// The D and M matrices of fzy's DP for one pattern and candidate.
+///
+/// `d(pi, ti)` is the best score of `pat[..=pi]` with `pat[pi]` matched at
+/// `text[ti]`, and `m(pi, ti)` the best with `pat[pi]` matched anywhere up to
+/// `ti`. As with [`explain`](crate::explain), the matrices don't know about
+/// the special cases [`score`](crate::score) returns without them: an empty
+/// pattern or one equal to the candidate.
+#[derive(Debug, Clone)]
+pub struct ScoreMatrix {
+    pat: Vec<u8>,
+    text: Vec<u8>,
+    bonuses: Vec<Score>,
+    d: Vec<Score>,
+    m: Vec<Score>,
+}
+
+impl ScoreMatrix {
+    /// Fills the matrices, matching case-insensitively like
+    /// [`positions`](crate::positions); `None` if `pat` is empty or longer
+    /// than `text`.
+    pub fn new<P: AsRef<[u8]>, T: AsRef<[u8]>>(pat: P, text: T) -> Option<ScoreMatrix> {
+        ScoreMatrix::folding(pat.as_ref(), text.as_ref(), true)
+    }
+
+    pub(crate) fn folding(pat: &[u8], text: &[u8], fold: bool) -> Option<ScoreMatrix> {
+        if pat.is_empty() || pat.len() > text.len() {
+            return None;
+        }
+        let (pat, folded) = if fold {
+            (pat.to_ascii_lowercase(), fold::to_ascii_lowercase(text))
+        } else {
+            (pat.to_vec(), text.to_vec())
+        };
+        let n = text.len();
+        let mut matrix = ScoreMatrix {
+            bonuses: compute_bonuses(text),
+            d: vec![0.0; pat.len() * n],
+            m: vec![0.0; pat.len() * n],
+            pat,
+            text: text.to_vec(),
+        };
+        for pi in 0..matrix.pat.len() {
+            let (prev_d, cur_d) = matrix.d.split_at_mut(pi * n);
+            let (prev_m, cur_m) = matrix.m.split_at_mut(pi * n);
+            let start = pi.saturating_sub(1) * n;
+            match_row(
+                pi,
+                matrix.pat[pi],
+                gap_score(pi, matrix.pat.len()),
+                &folded,
+                &matrix.bonuses,
+                (&prev_d[start..], &prev_m[start..]),
+                (&mut cur_d[..n], &mut cur_m[..n]),
+            );
+        }
+        Some(matrix)
+    }
+
+    /// The pattern, lowercased if it was matched ignoring case.
+    pub fn pattern(&self) -> &[u8] {
+        &self.pat
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    pub fn d(&self, pi: usize, ti: usize) -> Score {
+        self.d[pi * self.text.len() + ti]
+    }
+
+    pub fn m(&self, pi: usize, ti: usize) -> Score {
+        self.m[pi * self.text.len() + ti]
+    }
+
+    /// The bonus for matching at `ti`.
+    pub fn bonus(&self, ti: usize) -> Score {
+        self.bonuses[ti]
+    }
+
+    /// The best score, `SCORE_MIN` if the pattern doesn't match.
+    pub fn score(&self) -> Score {
+        self.m(self.pat.len() - 1, self.text.len() - 1)
+    }
+
+    /// The alignment [`positions`](crate::positions) reports: among optimal
+    /// ones, the one matching each byte as late as possible.
+    pub fn traceback(&self) -> Option<Vec<usize>> {
+        if self.score() == SCORE_MIN {
+            return None;
+        }
+        let mut positions = vec![0; self.pat.len()];
+        let mut match_required = false;
+        let mut ti = self.text.len();
+        for pi in (0..self.pat.len()).rev() {
+            while ti > 0 {
+                ti -= 1;
+                let d = self.d(pi, ti);
+                if d != SCORE_MIN && (match_required || d == self.m(pi, ti)) {
+                    match_required = pi > 0
+                        && ti > 0
+                        && self.m(pi, ti) == self.d(pi - 1, ti - 1) + SCORE_MATCH_CONSECUTIVE;
+                    positions[pi] = ti;
+                    break;
+                }
+            }
+        }
+        Some(positions)
+    }
+
+    /// What matching `pat[pi]` at `ti` adds after `pat[pi - 1]` at `prev`.
+    fn step(&self, prev: usize, ti: usize) -> Score {
+        if prev + 1 == ti {
+            self.bonuses[ti].max(SCORE_MATCH_CONSECUTIVE)
+        } else {
+            (ti - prev - 1) as Score * SCORE_GAP_INNER + self.bonuses[ti]
+        }
+    }
+
+    /// The score of matching the pattern at `positions`, as the DP would
+    /// score that alignment up to rounding.
+    pub fn alignment_score(&self, positions: &[usize]) -> Score {
+        let (Some(&first), Some(&last)) = (positions.first(), positions.last()) else {
+            return SCORE_MIN;
+        };
+        let start = first as Score * SCORE_GAP_LEADING + self.bonuses[first];
+        let inner: Score = positions.windows(2).map(|w| self.step(w[0], w[1])).sum();
+        let trailing = (self.text.len() - last - 1) as Score * SCORE_GAP_TRAILING;
+        start + inner + trailing
+    }
+
+    /// Every alignment of the pattern, best first, with its score. Each is
+    /// found by a best-first search bounded by the D matrix, so taking the
+    /// first few is cheap even when there are very many.
+    pub fn alignments(&self) -> Alignments<'_> {
+        let last = self.pat.len() - 1;
+        let mut alignments = Alignments {
+            matrix: self,
+            heap: BinaryHeap::new(),
+        };
+        for ti in 0..self.text.len() {
+            let trailing = (self.text.len() - ti - 1) as Score * SCORE_GAP_TRAILING;
+            alignments.push(last, ti, trailing, Vec::new());
+        }
+        alignments
+    }
+
+    /// The `k` best alignments, best first.
+    pub fn k_best(&self, k: usize) -> Vec<(Score, Vec<usize>)> {
+        self.alignments().take(k).collect()
+    }
+
+    /// Every alignment scoring the optimum, up to rounding.
+    pub fn optimal(&self) -> Vec<Vec<usize>> {
+        let best = self.score();
+        (self.alignments())
+            .take_while(|(score, _)| (best - score).abs() <= 1e-9 * best.abs().max(1.0))
+            .map(|(_, positions)| positions)
+            .collect()
+    }
+}
+
+/// Alignments of a [`ScoreMatrix`], best first; see
+/// [`ScoreMatrix::alignments`].
+#[derive(Debug)]
+pub struct Alignments<'a> {
+    matrix: &'a ScoreMatrix,
+    /// Partial alignments by the best score they can still reach: `pat[pi]`
+    /// at the first position, the rest matched with `suffix` as their score.
+    heap: BinaryHeap<(u64, usize, Vec<usize>, u64)>,
+}
+
+impl Alignments<'_> {
+    fn push(&mut self, pi: usize, ti: usize, suffix: Score, mut rest: Vec<usize>) {
+        let d = self.matrix.d(pi, ti);
+        if d != SCORE_MIN {
+            rest.insert(0, ti);
+            let bound = d + suffix;
+            self.heap
+                .push((bound.sort_key(), pi, rest, suffix.to_bits()));
+        }
+    }
+}
+
+impl Iterator for Alignments<'_> {
+    type Item = (Score, Vec<usize>);
+
+    fn next(&mut self) -> Option<(Score, Vec<usize>)> {
+        while let Some((_, pi, positions, suffix)) = self.heap.pop() {
+            if pi == 0 {
+                let score = self.matrix.alignment_score(&positions);
+                return Some((score, positions));
+            }
+            let (ti, suffix) = (positions[0], Score::from_bits(suffix));
+            for prev in pi - 1..ti {
+                let step = self.matrix.step(prev, ti);
+                self.push(pi - 1, prev, suffix + step, positions.clone());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{positions, score};
+
+    #[test]
+    fn traces_back_alignments() {
+        for (pat, text) in [
+            (&b"amor"[..], &b"app/models/order"[..]),
+            (b"ab", b"Ab/ab"),
+            (b"aa", b"aaaa"),
+        ] {
+            let matrix = ScoreMatrix::new(pat, text).unwrap();
+            assert_eq!(matrix.score(), score(pat, text));
+            let best = matrix.traceback();
+            assert_eq!(best, positions(pat, text));
+            assert!((matrix.alignment_score(&best.unwrap()) - matrix.score()).abs() < 1e-9);
+        }
+
+        let matrix = ScoreMatrix::new("ab", "a/b-ab").unwrap();
+        let ranked = matrix.k_best(10);
+        assert_eq!(ranked.len(), 3);
+        assert!(ranked.windows(2).all(|w| w[0].0 >= w[1].0));
+        assert_eq!(ranked[0], (matrix.score(), vec![4, 5]));
+        assert_eq!(matrix.optimal(), vec![vec![4, 5]]);
+        assert_eq!(ScoreMatrix::new("aa", "a-a-a").unwrap().optimal().len(), 1);
+        assert!(ScoreMatrix::new("", "a").is_none());
+        assert_eq!(ScoreMatrix::new("ba", "ab").unwrap().traceback(), None);
+    }
+}
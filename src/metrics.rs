@@ -0,0 +1,27 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What one ranking pass did, as reported to a metrics sink such as
+/// [`FilterOptions::metrics`](crate::FilterOptions::metrics).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Candidates looked at.
+    pub scanned: usize,
+    /// Candidates left out by exclusion patterns or extensions.
+    pub excluded: usize,
+    /// Candidates the query isn't a subsequence of.
+    pub unmatched: usize,
+    /// Candidates that matched and were scored.
+    pub scored: usize,
+    /// Scored candidates that were too long to score in full.
+    pub degraded: usize,
+    /// Time spent reading candidates, not counting matching them.
+    pub read_time: Duration,
+    /// Time spent matching and scoring.
+    pub match_time: Duration,
+    /// Time spent adjusting scores, sorting and truncating.
+    pub sort_time: Duration,
+}
+
+/// Where the [`Metrics`] of each ranking pass are sent.
+pub(crate) type MetricsSink = Arc<dyn Fn(&Metrics) + Send + Sync>;
@@ -0,0 +1,217 @@
+use std::io::{self, Write};
+
+use crate::trace::span;
+use crate::{FuzzyAlgorithm, Group, Match};
+
+/// Writes each selection followed by `terminator`, `b'\0'` producing input
+/// suitable for `xargs -0`.
+pub fn write_terminated<W, I, T>(mut out: W, selections: I, terminator: u8) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let _span = span!("emit");
+    for selection in selections {
+        out.write_all(selection.as_ref())?;
+        out.write_all(&[terminator])?;
+    }
+    out.flush()
+}
+
+/// Writes `score \t index \t candidate` for each match, `index` being the
+/// match's position in `candidates`.
+pub fn write_tsv<W, T>(
+    mut out: W,
+    candidates: &[T],
+    matches: &[Match],
+    terminator: u8,
+) -> io::Result<()>
+where
+    W: Write,
+    T: AsRef<[u8]>,
+{
+    let _span = span!("emit");
+    for m in matches {
+        write!(out, "{}\t{}\t", m.score, m.index)?;
+        out.write_all(candidates[m.index].as_ref())?;
+        out.write_all(&[terminator])?;
+    }
+    out.flush()
+}
+
+/// Writes each group's key as a header, followed by its matches indented by
+/// two spaces with the key and a `/` stripped from the front, as produced by
+/// grouping with [`parent_dir`](crate::parent_dir).
+pub fn write_grouped<W, T>(
+    mut out: W,
+    candidates: &[T],
+    groups: &[Group<&[u8]>],
+    terminator: u8,
+) -> io::Result<()>
+where
+    W: Write,
+    T: AsRef<[u8]>,
+{
+    let _span = span!("emit");
+    for group in groups {
+        if group.key.is_empty() {
+            out.write_all(b".")?;
+        } else {
+            out.write_all(group.key)?;
+        }
+        out.write_all(b"/")?;
+        out.write_all(&[terminator])?;
+        for m in &group.matches {
+            let text = candidates[m.index].as_ref();
+            let name = (text.strip_prefix(group.key))
+                .and_then(|rest| rest.strip_prefix(b"/"))
+                .unwrap_or(text);
+            out.write_all(b"  ")?;
+            out.write_all(name)?;
+            out.write_all(&[terminator])?;
+        }
+    }
+    out.flush()
+}
+
+pub(crate) fn write_json_str<W: Write>(out: &mut W, s: &str) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_all(b"\\\"")?,
+            '\\' => out.write_all(b"\\\\")?,
+            '\n' => out.write_all(b"\\n")?,
+            '\r' => out.write_all(b"\\r")?,
+            '\t' => out.write_all(b"\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => write!(out, "{}", c)?,
+        }
+    }
+    out.write_all(b"\"")
+}
+
+/// A JSON `{"error": message}` object on a line, how the servers answer a
+/// request they can't.
+pub(crate) fn json_error(message: &str) -> Vec<u8> {
+    let mut out = b"{\"error\":".to_vec();
+    let _ = write_json_str(&mut out, message);
+    out.extend_from_slice(b"}\n");
+    out
+}
+
+/// Writes the matches as a JSON array of `{text, score, index, positions}`
+/// objects, positions being those found by `algorithm`. Scores without a JSON
+/// representation (`SCORE_MIN` for an empty query, `SCORE_MAX` for an exact
+/// match) are written as `null`.
+pub fn write_json<W, A, T>(
+    mut out: W,
+    algorithm: &A,
+    query: &[u8],
+    candidates: &[T],
+    matches: &[Match],
+) -> io::Result<()>
+where
+    W: Write,
+    A: FuzzyAlgorithm + ?Sized,
+    T: AsRef<[u8]>,
+{
+    let _span = span!("emit");
+    out.write_all(b"[")?;
+    for (i, m) in matches.iter().enumerate() {
+        let text = candidates[m.index].as_ref();
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        out.write_all(b"{\"text\":")?;
+        write_json_str(&mut out, &String::from_utf8_lossy(text))?;
+        if m.score.is_finite() {
+            write!(out, ",\"score\":{}", m.score)?;
+        } else {
+            out.write_all(b",\"score\":null")?;
+        }
+        write!(out, ",\"index\":{},\"positions\":[", m.index)?;
+        let positions = algorithm.positions(query, text).unwrap_or_default();
+        for (j, pos) in positions.iter().enumerate() {
+            if j > 0 {
+                out.write_all(b",")?;
+            }
+            write!(out, "{}", pos)?;
+        }
+        out.write_all(b"]}")?;
+    }
+    out.write_all(b"]\n")?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    #[test]
+    fn writes_nul_terminated() {
+        let mut out = Vec::new();
+        write_terminated(&mut out, ["a\nb", "c"], b'\0').unwrap();
+        assert_eq!(out, b"a\nb\0c\0");
+    }
+
+    #[test]
+    fn writes_tsv() {
+        let mut out = Vec::new();
+        let matches = [
+            Match {
+                index: 1,
+                score: 0.5,
+            },
+            Match {
+                index: 0,
+                score: -1.0,
+            },
+        ];
+        write_tsv(&mut out, &["a\tb", "c"], &matches, b'\n').unwrap();
+        assert_eq!(out, b"0.5\t1\tc\n-1\t0\ta\tb\n");
+    }
+
+    #[test]
+    fn writes_grouped() {
+        let mut out = Vec::new();
+        let m = |index| Match { index, score: 0.0 };
+        let groups = [
+            Group {
+                key: &b"src"[..],
+                matches: vec![m(1), m(0)],
+            },
+            Group {
+                key: &b""[..],
+                matches: vec![m(2)],
+            },
+        ];
+        write_grouped(&mut out, &["src/a", "src/b", "c"], &groups, b'\n').unwrap();
+        assert_eq!(out, b"src/\n  b\n  a\n./\n  c\n");
+    }
+
+    #[test]
+    fn writes_json() {
+        let mut out = Vec::new();
+        let matches = [
+            Match {
+                index: 1,
+                score: 0.5,
+            },
+            Match {
+                index: 0,
+                score: f64::INFINITY,
+            },
+        ];
+        write_json(&mut out, &Algorithm::Fzy, b"ab", &["ab", "a\"/b"], &matches).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"[{"text":"a\"/b","score":0.5,"index":1,"positions":[0,3]},"#,
+                r#"{"text":"ab","score":null,"index":0,"positions":[0,1]}]"#,
+                "\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Ranking for command palettes, where each entry carries a payload and
+//! may also be found by its keywords.
+
+use crate::algorithm::match_candidate;
+use crate::{Algorithm, FuzzyAlgorithm, Match, Score};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry<T> {
+    /// What the palette shows and what the query is matched against first.
+    pub text: String,
+    /// Aliases that find the entry when its text doesn't match, e.g. `exit`
+    /// for "Quit".
+    pub keywords: Vec<String>,
+    pub payload: T,
+}
+
+#[derive(Debug, Clone)]
+pub struct Palette<T, A = Algorithm> {
+    algorithm: A,
+    entries: Vec<Entry<T>>,
+    keyword_penalty: Score,
+}
+
+impl<T> Palette<T> {
+    pub fn new() -> Palette<T> {
+        Palette::with_algorithm(Algorithm::Fzy)
+    }
+}
+
+impl<T> Default for Palette<T> {
+    fn default() -> Palette<T> {
+        Palette::new()
+    }
+}
+
+impl<T, A: FuzzyAlgorithm> Palette<T, A> {
+    pub fn with_algorithm(algorithm: A) -> Palette<T, A> {
+        Palette {
+            algorithm,
+            entries: Vec::new(),
+            keyword_penalty: 1.0,
+        }
+    }
+
+    /// Subtracted from the score of matches found through a keyword, 1 by
+    /// default.
+    pub fn set_keyword_penalty(&mut self, penalty: Score) {
+        self.keyword_penalty = penalty;
+    }
+
+    /// Adds an entry and returns its index.
+    pub fn add<K: AsRef<str>>(&mut self, text: &str, keywords: &[K], payload: T) -> usize {
+        self.entries.push(Entry {
+            text: text.to_string(),
+            keywords: keywords.iter().map(|k| k.as_ref().to_string()).collect(),
+            payload,
+        });
+        self.entries.len() - 1
+    }
+
+    pub fn entries(&self) -> &[Entry<T>] {
+        &self.entries
+    }
+
+    /// Matching entries best first, `index` pointing into
+    /// [`entries`](Palette::entries). An entry whose text matches is scored
+    /// by its text, otherwise by its best matching keyword less the keyword
+    /// penalty.
+    pub fn matches<Q: AsRef<str>>(&self, query: Q) -> Vec<Match> {
+        let query = query.as_ref().as_bytes();
+        let mut matches: Vec<Match> = (self.entries.iter().enumerate())
+            .filter_map(|(index, entry)| {
+                match_candidate(&self.algorithm, query, entry.text.as_bytes(), index).or_else(
+                    || {
+                        (entry.keywords.iter())
+                            .filter_map(|k| {
+                                match_candidate(&self.algorithm, query, k.as_bytes(), index)
+                            })
+                            .max_by(|a, b| b.rank_cmp(a))
+                            .map(|m| Match {
+                                index,
+                                score: m.score - self.keyword_penalty,
+                            })
+                    },
+                )
+            })
+            .collect();
+        matches.sort_by(Match::rank_cmp);
+        matches
+    }
+
+    /// Payloads of the matching entries, best first.
+    pub fn rank<Q: AsRef<str>>(&self, query: Q) -> Vec<&T> {
+        (self.matches(query).iter())
+            .map(|m| &self.entries[m.index].payload)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Command {
+        Quit,
+        Save,
+        SaveAs,
+    }
+
+    #[test]
+    fn ranks_payloads() {
+        let mut palette = Palette::new();
+        palette.add("Quit", &["exit", "close"], Command::Quit);
+        palette.add("Save", &["write"], Command::Save);
+        palette.add("Save As", &["write"], Command::SaveAs);
+        assert_eq!(palette.rank("Sa"), vec![&Command::Save, &Command::SaveAs]);
+        assert_eq!(palette.rank("exi"), vec![&Command::Quit]);
+        assert_eq!(palette.rank("zz"), Vec::<&Command>::new());
+        assert_eq!(palette.rank("").len(), 3);
+
+        let matches = palette.matches("wr");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].score, crate::score(b"wr", b"write") - 1.0);
+    }
+}
@@ -0,0 +1,666 @@
+use std::io;
+use std::ops::Range;
+
+use crate::record::Recorder;
+use crate::{Algorithm, FuzzyAlgorithm, Match, QuerySession, RankedResults, RankedSet};
+
+/// Input understood by the picker, independent of how a frontend reads it.
+///
+/// Rows are relative to the visible window, `0` being the first visible
+/// result.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Insert(char),
+    Backspace,
+    DeleteWord,
+    ClearQuery,
+    /// Moves the cursor to the next (lower ranked) result.
+    Next,
+    /// Moves the cursor to the previous (higher ranked) result.
+    Prev,
+    /// Replaces the query with the previous entry of the query history.
+    HistoryPrev,
+    /// Replaces the query with the next entry of the query history, or the
+    /// query being typed before recalling any.
+    HistoryNext,
+    /// Scrolls the window by this many results without accepting.
+    Scroll(isize),
+    /// Scrolls the highlighted result sideways by this many characters.
+    ScrollHorizontal(isize),
+    Click(usize),
+    DoubleClick(usize),
+    /// Marks or unmarks the highlighted result with multi-select on, then
+    /// moves to the next one.
+    ToggleMark,
+    Accept,
+    /// Accepts like `Accept`, reporting which of several accept keys, by
+    /// index, was used.
+    AcceptKey(usize),
+    Abort,
+}
+
+/// The candidate a picker opens with highlighted, see
+/// [`Picker::preselect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Preselect {
+    /// The first candidate with this text.
+    Text(Vec<u8>),
+    /// The candidate with this id, i.e. input index.
+    Id(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// The id of the accepted candidate. With multi-select, the marked
+    /// candidates, if any, are what was accepted, see [`Picker::marked`].
+    Accept(usize),
+    /// The accepted candidate and accept key of [`Event::AcceptKey`].
+    AcceptKey {
+        id: usize,
+        key: usize,
+    },
+    Abort,
+}
+
+/// Where a picker's candidates are ranked.
+#[derive(Debug)]
+enum Ranking<A> {
+    Set(RankedSet<A>),
+    /// The [`FuzzyAlgorithm::session`] of an algorithm ranking like fzy,
+    /// which extends each candidate's match as the query grows. The
+    /// algorithm itself still finds the positions to highlight.
+    Session(QuerySession, A),
+}
+
+impl<A: FuzzyAlgorithm> Ranking<A> {
+    fn extend<T: AsRef<[u8]>>(&mut self, texts: &[T]) -> Range<usize> {
+        match self {
+            Ranking::Set(set) => set.extend(texts),
+            Ranking::Session(session, _) => session.extend(texts),
+        }
+    }
+
+    fn algorithm(&self) -> &A {
+        match self {
+            Ranking::Set(set) => set.algorithm(),
+            Ranking::Session(_, algorithm) => algorithm,
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Ranking::Set(set) => set.clear(),
+            Ranking::Session(session, _) => session.clear(),
+        }
+    }
+
+    fn get(&self, id: usize) -> Option<&[u8]> {
+        match self {
+            Ranking::Set(set) => set.get(id),
+            Ranking::Session(session, _) => session.get(id),
+        }
+    }
+
+    fn set_query(&mut self, query: &[u8]) {
+        match self {
+            Ranking::Set(set) => set.set_query(query),
+            Ranking::Session(session, _) => session.set_query(query),
+        }
+    }
+
+    fn results(&self) -> &RankedResults {
+        match self {
+            Ranking::Set(set) => set.results(),
+            Ranking::Session(session, _) => session.results(),
+        }
+    }
+
+    fn total(&self) -> usize {
+        match self {
+            Ranking::Set(set) => set.total(),
+            Ranking::Session(session, _) => session.total(),
+        }
+    }
+}
+
+/// The state of an interactive picker: a query, the ranked candidates and a
+/// cursor within a scrolling window of `height` results.
+#[derive(Debug)]
+pub struct Picker<A = Algorithm> {
+    set: Ranking<A>,
+    query: Vec<u8>,
+    cursor: usize,
+    offset: usize,
+    height: usize,
+    hscroll: isize,
+    history: Vec<Vec<u8>>,
+    /// The recalled history entry and the query typed before recalling it.
+    recalled: Option<(usize, Vec<u8>)>,
+    recorder: Option<Recorder>,
+    preselect: Option<Preselect>,
+    /// The match of the preselected candidate, once it arrived.
+    preselected: Option<Match>,
+    multi: bool,
+    mark_limit: Option<usize>,
+    marked: Vec<usize>,
+}
+
+impl Default for Picker {
+    fn default() -> Picker {
+        Picker::new()
+    }
+}
+
+impl Picker {
+    pub fn new() -> Picker {
+        Picker::with_algorithm(Algorithm::Fzy)
+    }
+}
+
+impl<A: FuzzyAlgorithm> Picker<A> {
+    /// A picker ranking with `algorithm`, through its
+    /// [session](FuzzyAlgorithm::session) if it has one, which only scores
+    /// the new query byte of each candidate as the query grows.
+    pub fn with_algorithm(algorithm: A) -> Picker<A> {
+        let set = match algorithm.session() {
+            Some(session) => Ranking::Session(session, algorithm),
+            None => Ranking::Set(RankedSet::with_algorithm(b"", algorithm)),
+        };
+        Picker {
+            set,
+            query: Vec::new(),
+            cursor: 0,
+            offset: 0,
+            height: 1,
+            hscroll: 0,
+            history: Vec::new(),
+            recalled: None,
+            recorder: None,
+            preselect: None,
+            preselected: None,
+            multi: false,
+            mark_limit: None,
+            marked: Vec::new(),
+        }
+    }
+
+    /// Lets [`Event::ToggleMark`] mark up to `limit` candidates, or any
+    /// number without a limit.
+    pub fn set_multi(&mut self, limit: Option<usize>) {
+        self.multi = true;
+        self.mark_limit = limit;
+        if let Some(limit) = limit {
+            self.marked.truncate(limit);
+        }
+    }
+
+    pub fn is_multi(&self) -> bool {
+        self.multi
+    }
+
+    pub fn mark_limit(&self) -> Option<usize> {
+        self.mark_limit
+    }
+
+    /// Ids of the marked candidates, in the order they were marked.
+    pub fn marked(&self) -> &[usize] {
+        &self.marked
+    }
+
+    pub fn is_marked(&self, id: usize) -> bool {
+        self.marked.contains(&id)
+    }
+
+    /// Whether no more candidates can be marked.
+    pub fn at_mark_limit(&self) -> bool {
+        self.mark_limit
+            .is_some_and(|limit| self.marked.len() >= limit)
+    }
+
+    /// Marks or unmarks a candidate, returning whether it's marked now. A
+    /// candidate is left unmarked past the mark limit or without
+    /// multi-select.
+    pub fn toggle_mark(&mut self, id: usize) -> bool {
+        if let Some(pos) = self.marked.iter().position(|m| *m == id) {
+            self.marked.remove(pos);
+            return false;
+        }
+        if !self.multi || self.at_mark_limit() || self.get(id).is_none() {
+            return false;
+        }
+        self.marked.push(id);
+        true
+    }
+
+    /// Adds a candidate and returns its id.
+    pub fn push(&mut self, text: &[u8]) -> usize {
+        self.extend(&[text]).start
+    }
+
+    /// Adds candidates and returns their ids. A batch is ranked in one pass,
+    /// which is much cheaper than pushing its candidates one at a time.
+    pub fn extend<T: AsRef<[u8]>>(&mut self, texts: &[T]) -> Range<usize> {
+        for text in texts {
+            self.recording(|r| r.candidate(text.as_ref()));
+        }
+        let ids = self.set.extend(texts);
+        if self.preselected.is_none() {
+            let preselect = &self.preselect;
+            let matches = self.set.results().as_slice();
+            self.preselected = (texts.iter().zip(ids.clone()))
+                .filter(|(text, id)| match preselect {
+                    Some(Preselect::Text(wanted)) => wanted[..] == *text.as_ref(),
+                    Some(Preselect::Id(wanted)) => wanted == id,
+                    None => false,
+                })
+                .find_map(|(_, id)| matches.iter().find(|m| m.index == id).copied());
+        }
+        if let Some(preselected) = self.preselected {
+            let matches = self.matches();
+            self.cursor = matches.partition_point(|m| m.rank_cmp(&preselected).is_lt());
+            self.scroll_to_cursor();
+        }
+        ids
+    }
+
+    /// Highlights a candidate as soon as it's pushed, keeping it highlighted
+    /// as more arrive until the first event or query change.
+    pub fn preselect(&mut self, preselect: Preselect) {
+        self.preselect = Some(preselect);
+        self.preselected = None;
+    }
+
+    /// Records every later candidate, query change and clear. Recording
+    /// stops at the first write error rather than failing the picker.
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn algorithm(&self) -> &A {
+        self.set.algorithm()
+    }
+
+    /// Earlier queries, oldest first, for `HistoryPrev` and `HistoryNext`.
+    pub fn set_history(&mut self, history: Vec<Vec<u8>>) {
+        self.history = history;
+        self.recalled = None;
+    }
+
+    pub fn has_history(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    /// Drops every candidate, keeping the query.
+    pub fn clear(&mut self) {
+        self.recording(Recorder::clear);
+        self.set.clear();
+        self.preselected = None;
+        self.marked.clear();
+        self.cursor = 0;
+        self.offset = 0;
+        self.hscroll = 0;
+    }
+
+    pub fn get(&self, id: usize) -> Option<&[u8]> {
+        self.set.get(id)
+    }
+
+    pub fn query(&self) -> &[u8] {
+        &self.query
+    }
+
+    pub fn set_query(&mut self, query: &[u8]) {
+        self.recording(|r| r.query(query));
+        self.preselect = None;
+        self.preselected = None;
+        self.query = query.to_vec();
+        self.set.set_query(query);
+        self.cursor = 0;
+        self.offset = 0;
+        self.hscroll = 0;
+    }
+
+    fn recording<F: FnOnce(&mut Recorder) -> io::Result<()>>(&mut self, f: F) {
+        if self.recorder.as_mut().is_some_and(|r| f(r).is_err()) {
+            self.recorder = None;
+        }
+    }
+
+    pub fn matches(&self) -> &[Match] {
+        self.set.results().as_slice()
+    }
+
+    pub fn results(&self) -> &RankedResults {
+        self.set.results()
+    }
+
+    /// Number of candidates, matching or not.
+    pub fn total(&self) -> usize {
+        self.set.total()
+    }
+
+    /// Index of the highlighted result within `matches()`.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Index within `matches()` of the first visible result.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Characters the highlighted result is scrolled by, relative to where a
+    /// frontend would show it by default.
+    pub fn hscroll(&self) -> isize {
+        self.hscroll
+    }
+
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height.max(1);
+        self.scroll_to_cursor();
+    }
+
+    /// The visible results as `(index into matches(), match)` pairs.
+    pub fn visible(&self) -> impl Iterator<Item = (usize, &Match)> + '_ {
+        let page = self.results().page(self.offset, self.height);
+        (self.offset..).zip(page)
+    }
+
+    /// The id of the highlighted candidate.
+    pub fn selected(&self) -> Option<usize> {
+        self.results().get(self.cursor).map(|m| m.index)
+    }
+
+    pub fn handle(&mut self, event: Event) -> Option<Outcome> {
+        self.preselect = None;
+        self.preselected = None;
+        if let Event::Insert(_) | Event::Backspace | Event::DeleteWord | Event::ClearQuery = event {
+            self.recalled = None;
+        }
+        match event {
+            Event::Insert(c) => {
+                let mut query = self.query.clone();
+                query.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                self.set_query(&query);
+            }
+            Event::Backspace => {
+                let mut query = self.query.clone();
+                while let Some(b) = query.pop() {
+                    if b & 0xc0 != 0x80 {
+                        break;
+                    }
+                }
+                self.set_query(&query);
+            }
+            Event::DeleteWord => {
+                let mut query = self.query.clone();
+                while query.last() == Some(&b' ') {
+                    query.pop();
+                }
+                while query.last().is_some_and(|b| *b != b' ') {
+                    query.pop();
+                }
+                self.set_query(&query);
+            }
+            Event::ClearQuery => self.set_query(b""),
+            Event::HistoryPrev => {
+                let (pos, draft) = match self.recalled.take() {
+                    Some((pos, draft)) => (pos.saturating_sub(1), draft),
+                    None if self.history.is_empty() => return None,
+                    None => (self.history.len() - 1, self.query.clone()),
+                };
+                let query = self.history[pos].clone();
+                self.set_query(&query);
+                self.recalled = Some((pos, draft));
+            }
+            Event::HistoryNext => {
+                if let Some((pos, draft)) = self.recalled.take() {
+                    match self.history.get(pos + 1).cloned() {
+                        Some(query) => {
+                            self.set_query(&query);
+                            self.recalled = Some((pos + 1, draft));
+                        }
+                        None => self.set_query(&draft),
+                    }
+                }
+            }
+            Event::Next => self.move_cursor(1),
+            Event::Prev => self.move_cursor(-1),
+            Event::Scroll(delta) => {
+                let max_offset = self.results().len().saturating_sub(self.height);
+                self.offset = add_clamped(self.offset, delta, max_offset);
+                self.cursor = self
+                    .cursor
+                    .clamp(self.offset, self.offset + self.height - 1)
+                    .min(self.results().len().saturating_sub(1));
+            }
+            Event::ScrollHorizontal(delta) => self.hscroll += delta,
+            Event::Click(row) => {
+                if self.offset + row < self.results().len() {
+                    self.cursor = self.offset + row;
+                    self.hscroll = 0;
+                }
+            }
+            Event::DoubleClick(row) => {
+                self.handle(Event::Click(row));
+                if self.cursor == self.offset + row {
+                    return self.handle(Event::Accept);
+                }
+            }
+            Event::ToggleMark if self.multi => {
+                if let Some(id) = self.selected() {
+                    self.toggle_mark(id);
+                    self.move_cursor(1);
+                }
+            }
+            Event::ToggleMark => {}
+            Event::Accept => return self.selected().map(Outcome::Accept),
+            Event::AcceptKey(key) => {
+                return self.selected().map(|id| Outcome::AcceptKey { id, key });
+            }
+            Event::Abort => return Some(Outcome::Abort),
+        }
+        None
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let last = self.results().len().saturating_sub(1);
+        self.cursor = add_clamped(self.cursor, delta, last);
+        self.hscroll = 0;
+        self.scroll_to_cursor();
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + self.height {
+            self.offset = self.cursor + 1 - self.height;
+        }
+    }
+}
+
+fn add_clamped(n: usize, delta: isize, max: usize) -> usize {
+    if delta < 0 {
+        n.saturating_sub(delta.unsigned_abs())
+    } else {
+        n.saturating_add(delta as usize).min(max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnsiMatching, Case, Cased};
+
+    fn picker(candidates: &[&str], height: usize) -> Picker {
+        let mut picker = Picker::new();
+        for c in candidates {
+            picker.push(c.as_bytes());
+        }
+        picker.set_height(height);
+        picker
+    }
+
+    #[test]
+    fn typing_filters() {
+        let mut picker = picker(&["foo", "bar", "baz"], 10);
+        picker.handle(Event::Insert('b'));
+        picker.handle(Event::Insert('z'));
+        assert_eq!(picker.query(), b"bz");
+        assert_eq!(picker.handle(Event::Accept), Some(Outcome::Accept(2)));
+        assert_eq!(
+            picker.handle(Event::AcceptKey(1)),
+            Some(Outcome::AcceptKey { id: 2, key: 1 })
+        );
+        picker.handle(Event::Backspace);
+        assert_eq!(picker.matches().len(), 2);
+        picker.handle(Event::ClearQuery);
+        assert_eq!(picker.matches().len(), 3);
+    }
+
+    #[test]
+    fn recalls_history() {
+        let mut picker = picker(&["foo", "bar"], 10);
+        picker.set_history(vec![b"f".to_vec(), b"ba".to_vec()]);
+        picker.handle(Event::Insert('o'));
+        picker.handle(Event::HistoryPrev);
+        assert_eq!(picker.query(), b"ba");
+        assert_eq!(picker.selected(), Some(1));
+        picker.handle(Event::HistoryPrev);
+        picker.handle(Event::HistoryPrev);
+        assert_eq!(picker.query(), b"f");
+        picker.handle(Event::HistoryNext);
+        assert_eq!(picker.query(), b"ba");
+        picker.handle(Event::HistoryNext);
+        assert_eq!(picker.query(), b"o");
+        picker.handle(Event::HistoryPrev);
+        picker.handle(Event::Insert('r'));
+        picker.handle(Event::HistoryNext);
+        assert_eq!(picker.query(), b"bar");
+    }
+
+    #[test]
+    fn clear_keeps_query() {
+        let mut picker = picker(&["foo", "bar", "baz"], 10);
+        picker.handle(Event::Insert('b'));
+        picker.handle(Event::Next);
+        picker.clear();
+        assert_eq!(picker.total(), 0);
+        assert_eq!(picker.cursor(), 0);
+        picker.push(b"ab");
+        picker.push(b"cd");
+        assert_eq!(picker.query(), b"b");
+        assert_eq!(picker.selected(), Some(0));
+    }
+
+    #[test]
+    fn session_ranks_like_the_set() {
+        let candidates = ["app/models/order", "Gemfile", "amor", "src/main.rs"];
+        let cased = Cased {
+            algorithm: Algorithm::Fzy,
+            case: Case::Smart,
+        };
+        let mut session = Picker::with_algorithm(cased);
+        assert!(matches!(session.set, Ranking::Session(..)));
+        // Without ANSI codes this ranks like `cased`, but has no session.
+        let mut set = Picker::with_algorithm(AnsiMatching { algorithm: cased });
+        assert!(matches!(set.set, Ranking::Set(_)));
+        for c in candidates {
+            session.push(c.as_bytes());
+            set.push(c.as_bytes());
+        }
+        for event in [
+            Event::Insert('a'),
+            Event::Insert('m'),
+            Event::Insert('o'),
+            Event::Backspace,
+            Event::Insert('r'),
+            Event::ClearQuery,
+            Event::Insert('g'),
+            Event::Insert('E'),
+            Event::Backspace,
+            Event::Insert('e'),
+        ] {
+            session.handle(event);
+            set.handle(event);
+            assert_eq!(session.matches(), set.matches(), "{:?}", event);
+        }
+        session.clear();
+        session.push(b"gre");
+        assert_eq!((session.total(), session.selected()), (1, Some(0)));
+    }
+
+    #[test]
+    fn cursor_scrolls_window() {
+        let mut picker = picker(&["a", "b", "c", "d"], 2);
+        picker.handle(Event::Next);
+        picker.handle(Event::Next);
+        assert_eq!(picker.cursor(), 2);
+        assert_eq!(picker.offset(), 1);
+        picker.handle(Event::Next);
+        picker.handle(Event::Next);
+        assert_eq!(picker.cursor(), 3);
+        picker.handle(Event::Prev);
+        picker.handle(Event::Prev);
+        picker.handle(Event::Prev);
+        assert_eq!(picker.offset(), 0);
+    }
+
+    #[test]
+    fn preselects_candidate() {
+        let mut picker = picker(&["a", "b"], 2);
+        picker.preselect(Preselect::Text(b"c".to_vec()));
+        picker.push(b"c");
+        picker.push(b"d");
+        assert_eq!(picker.selected(), Some(2));
+        assert_eq!(picker.offset(), 1);
+        picker.clear();
+        picker.push(b"x");
+        picker.push(b"c");
+        assert_eq!(picker.selected(), Some(1));
+        picker.handle(Event::Prev);
+        picker.push(b"c");
+        assert_eq!(picker.selected(), Some(0));
+
+        let mut by_id = Picker::new();
+        by_id.preselect(Preselect::Id(1));
+        by_id.push(b"a");
+        by_id.push(b"b");
+        assert_eq!(by_id.selected(), Some(1));
+    }
+
+    #[test]
+    fn marks_up_to_limit() {
+        let mut picker = picker(&["a", "b", "c"], 10);
+        picker.handle(Event::ToggleMark);
+        assert!(picker.marked().is_empty());
+        picker.set_multi(Some(2));
+        picker.handle(Event::ToggleMark);
+        picker.handle(Event::ToggleMark);
+        assert!(picker.at_mark_limit());
+        picker.handle(Event::ToggleMark);
+        assert_eq!(picker.marked(), &[0, 1]);
+        assert_eq!(picker.cursor(), 2);
+        assert!(!picker.toggle_mark(0));
+        assert!(picker.toggle_mark(2));
+        assert_eq!(picker.marked(), &[1, 2]);
+        picker.clear();
+        assert!(picker.marked().is_empty());
+    }
+
+    #[test]
+    fn mouse_events() {
+        let mut picker = picker(&["a", "b", "c", "d"], 2);
+        picker.handle(Event::Scroll(5));
+        assert_eq!(picker.offset(), 2);
+        assert_eq!(picker.cursor(), 2);
+        picker.handle(Event::Click(1));
+        assert_eq!(picker.selected(), Some(3));
+        picker.handle(Event::Click(5));
+        assert_eq!(picker.selected(), Some(3));
+        assert_eq!(
+            picker.handle(Event::DoubleClick(0)),
+            Some(Outcome::Accept(2))
+        );
+    }
+}
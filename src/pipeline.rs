@@ -0,0 +1,259 @@
+//! A ready-made threaded ranking pipeline: candidates go in through a
+//! [`Producer`], a pool of workers scores them a batch at a time, and ranked
+//! batches come out of a channel.
+//!
+//! Both channels are bounded, so a producer outpacing the workers, or
+//! workers outpacing the consumer, blocks rather than buffering everything.
+
+#[cfg(all(unix, feature = "walk"))]
+use std::path::Path;
+#[cfg(all(unix, feature = "walk"))]
+use std::sync::Mutex;
+use std::thread;
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+
+use crate::algorithm::match_candidate;
+use crate::ranked::merge as merge_sorted;
+#[cfg(all(unix, feature = "walk"))]
+use crate::source::{walk_parallel, WalkOptions};
+use crate::{FuzzyAlgorithm, Match};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// Scorer threads.
+    pub workers: usize,
+    /// Candidates scored together and sent on as one [`Ranked`] batch.
+    pub batch_size: usize,
+    /// Batches each channel holds before its sender blocks.
+    pub capacity: usize,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            workers: thread::available_parallelism().map_or(1, usize::from),
+            batch_size: 1024,
+            capacity: 4,
+        }
+    }
+}
+
+/// Candidates waiting to be scored, numbered from `start`.
+#[derive(Debug)]
+struct Chunk {
+    start: usize,
+    texts: Vec<Vec<u8>>,
+}
+
+/// One scored batch: its candidates, numbered from `start`, and the matches
+/// among them, best first. Match indices count from the first candidate
+/// pushed, not from `start`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ranked {
+    pub start: usize,
+    pub texts: Vec<Vec<u8>>,
+    pub matches: Vec<Match>,
+}
+
+impl Ranked {
+    /// The text of the candidate a match of this batch points to.
+    pub fn text(&self, m: &Match) -> &[u8] {
+        &self.texts[m.index - self.start]
+    }
+}
+
+/// The input end of a pipeline. Dropping it flushes the last partial batch
+/// and, once the workers are done, closes the output channel.
+#[derive(Debug)]
+pub struct Producer {
+    tx: Sender<Chunk>,
+    pending: Vec<Vec<u8>>,
+    start: usize,
+    batch_size: usize,
+}
+
+impl Producer {
+    /// Queues a candidate, blocking while every worker is busy and the
+    /// channel is full. Returns its index, or `None` when the workers are gone.
+    pub fn push(&mut self, text: &[u8]) -> Option<usize> {
+        let index = self.start + self.pending.len();
+        self.pending.push(text.to_vec());
+        if self.pending.len() >= self.batch_size && !self.flush() {
+            return None;
+        }
+        Some(index)
+    }
+
+    /// Sends the candidates queued so far without waiting for a full batch.
+    /// Returns `false` when the workers are gone.
+    pub fn flush(&mut self) -> bool {
+        if self.pending.is_empty() {
+            return true;
+        }
+        let texts = std::mem::take(&mut self.pending);
+        let start = self.start;
+        self.start += texts.len();
+        self.tx.send(Chunk { start, texts }).is_ok()
+    }
+}
+
+impl Drop for Producer {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Starts `opts.workers` threads ranking candidates against `query`, and
+/// returns the end to push candidates into and the end ranked batches come
+/// out of, in no particular order.
+pub fn pipeline<A>(query: &[u8], algorithm: A, opts: &Options) -> (Producer, Receiver<Ranked>)
+where
+    A: FuzzyAlgorithm + Clone + Send + 'static,
+{
+    let (chunk_tx, chunk_rx) = bounded::<Chunk>(opts.capacity);
+    let (ranked_tx, ranked_rx) = bounded(opts.capacity);
+    for _ in 0..opts.workers.max(1) {
+        let (chunks, ranked) = (chunk_rx.clone(), ranked_tx.clone());
+        let (query, algorithm) = (query.to_vec(), algorithm.clone());
+        thread::spawn(move || score_chunks(&query, &algorithm, chunks, ranked));
+    }
+    let producer = Producer {
+        tx: chunk_tx,
+        pending: Vec::new(),
+        start: 0,
+        batch_size: opts.batch_size.max(1),
+    };
+    (producer, ranked_rx)
+}
+
+fn score_chunks<A: FuzzyAlgorithm>(
+    query: &[u8],
+    algorithm: &A,
+    chunks: Receiver<Chunk>,
+    ranked: Sender<Ranked>,
+) {
+    for Chunk { start, texts } in chunks {
+        let mut matches: Vec<Match> = (texts.iter().enumerate())
+            .filter_map(|(i, text)| match_candidate(algorithm, query, text, start + i))
+            .collect();
+        matches.sort_by(Match::rank_cmp);
+        let batch = Ranked {
+            start,
+            texts,
+            matches,
+        };
+        if ranked.send(batch).is_err() {
+            return;
+        }
+    }
+}
+
+/// Walks `root` on several threads, feeding the files found to a new
+/// pipeline as they are found, so that ranked batches come out before the
+/// walk is done. Unreadable entries are skipped.
+#[cfg(all(unix, feature = "walk"))]
+pub fn walk_pipeline<A>(
+    root: &Path,
+    walk: &WalkOptions,
+    query: &[u8],
+    algorithm: A,
+    opts: &Options,
+) -> Receiver<Ranked>
+where
+    A: FuzzyAlgorithm + Clone + Send + 'static,
+{
+    let (producer, ranked) = pipeline(query, algorithm, opts);
+    let (root, walk) = (root.to_path_buf(), walk.clone());
+    thread::spawn(move || {
+        // Walker threads take turns filling the batch; a full pipeline
+        // blocks them all.
+        let producer = Mutex::new(producer);
+        walk_parallel(&root, &walk, |entry| {
+            if let Ok(entry) = entry {
+                producer.lock().unwrap().push(entry.text());
+            }
+        });
+    });
+    ranked
+}
+
+/// Merges ranked batches into one ranking, keeping the best `limit` matches
+/// and their texts.
+///
+/// With a limit, each batch's best matches are merged into the best so far,
+/// which never grows past `limit`. Without one, everything is collected and
+/// sorted once at the end.
+pub fn merge<I>(batches: I, limit: Option<usize>) -> Vec<(Match, Vec<u8>)>
+where
+    I: IntoIterator<Item = Ranked>,
+{
+    let with_text = |batch: &Ranked, m: &Match| (*m, batch.text(m).to_vec());
+    let cmp = |a: &(Match, Vec<u8>), b: &(Match, Vec<u8>)| a.0.rank_cmp(&b.0);
+    let mut merged: Vec<(Match, Vec<u8>)> = Vec::new();
+    match limit {
+        Some(limit) => {
+            for batch in batches {
+                let best = &batch.matches[..batch.matches.len().min(limit)];
+                let best = best.iter().map(|m| with_text(&batch, m)).collect();
+                merge_sorted(&mut merged, best, cmp);
+                merged.truncate(limit);
+            }
+        }
+        None => {
+            for batch in batches {
+                merged.extend(batch.matches.iter().map(|m| with_text(&batch, m)));
+            }
+            merged.sort_by(cmp);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::rank;
+    use crate::Algorithm;
+
+    #[test]
+    fn ranks_across_workers() {
+        let opts = Options {
+            workers: 3,
+            batch_size: 2,
+            capacity: 1,
+        };
+        let (mut producer, ranked) = pipeline(b"ab", Algorithm::Fzy, &opts);
+        let texts: Vec<String> = (0..50).map(|i| format!("{}a{}b", i, i % 7)).collect();
+        let consumer = thread::spawn(move || merge(ranked, Some(5)));
+        for (i, text) in texts.iter().enumerate() {
+            assert_eq!(producer.push(text.as_bytes()), Some(i));
+        }
+        drop(producer);
+
+        let merged = consumer.join().unwrap();
+        let expected = &rank(&Algorithm::Fzy, b"ab", &texts)[..5];
+        let matches: Vec<Match> = merged.iter().map(|(m, _)| *m).collect();
+        assert_eq!(matches, expected);
+        assert_eq!(merged[0].1, texts[expected[0].index].as_bytes());
+    }
+
+    #[cfg(all(unix, feature = "walk"))]
+    #[test]
+    fn ranks_walked_files() {
+        let dir = std::env::temp_dir().join(format!("fzy-rs-pipeline-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src/bin")).unwrap();
+        for file in ["src/main.rs", "src/lib.rs", "src/bin/tool.rs", "README"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let opts = Options {
+            batch_size: 1,
+            ..Options::default()
+        };
+        let ranked = walk_pipeline(&dir, &WalkOptions::default(), b"srs", Algorithm::Fzy, &opts);
+        let merged = merge(ranked, None);
+        assert_eq!(merged.len(), 3);
+        assert!(merged[0].1.ends_with(b"src/lib.rs"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,33 @@
+/// How far a ranking pass has got, as told to an [`Observer`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Candidates looked at so far, excluded ones included.
+    pub processed: usize,
+    /// How many candidates the pass has in all, when known up front.
+    pub total: Option<usize>,
+    /// Candidates matched so far.
+    pub matched: usize,
+}
+
+impl Progress {
+    /// How much of the pass is done, from `0.0` to `1.0`, when the total is
+    /// known.
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.map(|total| match total {
+            0 => 1.0,
+            total => self.processed as f64 / total as f64,
+        })
+    }
+}
+
+/// Told about the progress of a ranking pass after every candidate, so it
+/// should be cheap; a progress bar would redraw only now and then.
+pub trait Observer {
+    fn progress(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> Observer for F {
+    fn progress(&mut self, progress: &Progress) {
+        self(progress)
+    }
+}
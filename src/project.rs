@@ -0,0 +1,123 @@
+//! Scores as bounded integers, for protocols that can't carry floats or
+//! infinities.
+
+use std::f64::consts::PI;
+
+use crate::Score;
+
+/// The largest projected score, that of exact matches.
+pub const PROJECTED_MAX: u32 = 10_000;
+
+/// Scores this far from 0 land a quarter of the way from the middle of the
+/// range to either end; typical fzy scores are within a few units of 0.
+const SPREAD: Score = 4.0;
+
+/// Maps `score` into `0..=PROJECTED_MAX`.
+///
+/// The mapping is monotonic: a higher score never projects lower, though
+/// scores closer together than the resolution (about 0.001 near 0, coarser
+/// further out) may project to the same integer. `-inf` and NaN project to
+/// 0, `+inf` to [`PROJECTED_MAX`], and 0 to the middle of the range. The
+/// mapping is part of the crate's stable interface.
+pub fn project_score(score: Score) -> u32 {
+    if score.is_nan() {
+        return 0;
+    }
+    let unit = 0.5 + (score / SPREAD).atan() / PI;
+    (unit * PROJECTED_MAX as Score).round() as u32
+}
+
+/// Scores as integers ordered exactly like the scores, for sorting by key,
+/// ordered maps, and comparing across FFI.
+pub trait SortKey {
+    /// Maps a score to a `u64` such that a higher score always gives a higher
+    /// key and equal scores, including `-0.0` and `0.0`, equal keys. NaN
+    /// gives 0, below every other score.
+    fn sort_key(&self) -> u64;
+}
+
+impl SortKey for Score {
+    fn sort_key(&self) -> u64 {
+        if self.is_nan() {
+            return 0;
+        }
+        // Zero the sign of -0.0, then flip negative scores entirely and
+        // positive ones' sign bit so that unsigned order is numeric order.
+        let bits = (self + 0.0).to_bits();
+        if bits >> 63 == 1 {
+            !bits
+        } else {
+            bits | 1 << 63
+        }
+    }
+}
+
+/// The score at the middle of what projects to `projected`, for debugging;
+/// 0 gives `-inf` and [`PROJECTED_MAX`] and above `+inf`.
+pub fn unproject_score(projected: u32) -> Score {
+    if projected == 0 {
+        return Score::NEG_INFINITY;
+    }
+    if projected >= PROJECTED_MAX {
+        return Score::INFINITY;
+    }
+    let unit = projected as Score / PROJECTED_MAX as Score;
+    SPREAD * (PI * (unit - 0.5)).tan()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score;
+
+    #[test]
+    fn projects_monotonically() {
+        assert_eq!(project_score(Score::NEG_INFINITY), 0);
+        assert_eq!(project_score(Score::NAN), 0);
+        assert_eq!(project_score(0.0), PROJECTED_MAX / 2);
+        assert_eq!(project_score(Score::INFINITY), PROJECTED_MAX);
+
+        let mut last = 0;
+        for i in -2000..=2000 {
+            let projected = project_score(i as Score / 50.0);
+            assert!(projected >= last);
+            last = projected;
+        }
+        assert!(
+            project_score(score(b"amo", b"app/models/foo"))
+                > project_score(score(b"amo", b"app/m/foo"))
+        );
+    }
+
+    #[test]
+    fn orders_sort_keys() {
+        let scores = [
+            Score::NAN,
+            Score::NEG_INFINITY,
+            -3.5,
+            -Score::MIN_POSITIVE,
+            0.0,
+            Score::MIN_POSITIVE,
+            0.25,
+            score(b"amo", b"app/models/foo"),
+            Score::INFINITY,
+        ];
+        for pair in scores.windows(2) {
+            assert!(pair[0].sort_key() < pair[1].sort_key(), "{:?}", pair);
+        }
+        assert_eq!((-0.0).sort_key(), 0.0.sort_key());
+    }
+
+    #[test]
+    fn inverts_projection() {
+        assert_eq!(unproject_score(0), Score::NEG_INFINITY);
+        assert_eq!(unproject_score(PROJECTED_MAX), Score::INFINITY);
+        for s in [-3.0, -0.2, 0.0, 0.5, 2.73, 10.0] {
+            assert_eq!(
+                project_score(unproject_score(project_score(s))),
+                project_score(s)
+            );
+            assert!((unproject_score(project_score(s)) - s).abs() < 0.01 * (1.0 + s * s));
+        }
+    }
+}
@@ -0,0 +1,480 @@
+//! fzf's extended search syntax as a value: space separated terms that must
+//! all match, `|` between alternatives, `'` for exact, `^` and `$` anchors,
+//! `!` negation and `^N` weights.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Case, FuzzyAlgorithm, Match, Score, SCORE_MIN};
+
+/// How a [`Term`]'s text is looked for in a candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermKind {
+    /// Matched by the ranking algorithm, as a plain query: `foo`.
+    Fuzzy,
+    /// A substring: `'foo`.
+    Exact,
+    /// A prefix of the candidate: `^foo`.
+    Prefix,
+    /// A suffix of the candidate: `foo$`.
+    Suffix,
+    /// The whole candidate: `^foo$`.
+    Equal,
+}
+
+/// One term of a [`Query`]. Terms other than fuzzy ones ignore case unless
+/// their text has an uppercase letter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub text: Vec<u8>,
+    pub kind: TermKind,
+    /// Matches candidates the term otherwise wouldn't. As in fzf, a negated
+    /// fuzzy term is looked for exactly, so `!foo` drops candidates
+    /// containing `foo`.
+    pub negated: bool,
+    weight: Weight,
+}
+
+/// A term weight, positive and finite so that terms can stay `Eq`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Weight(Score);
+
+impl Eq for Weight {}
+
+impl Term {
+    pub fn new(kind: TermKind, text: &[u8]) -> Term {
+        Term {
+            text: text.to_vec(),
+            kind,
+            negated: false,
+            weight: Weight(1.0),
+        }
+    }
+
+    pub fn fuzzy(text: &[u8]) -> Term {
+        Term::new(TermKind::Fuzzy, text)
+    }
+
+    pub fn exact(text: &[u8]) -> Term {
+        Term::new(TermKind::Exact, text)
+    }
+
+    pub fn prefix(text: &[u8]) -> Term {
+        Term::new(TermKind::Prefix, text)
+    }
+
+    pub fn suffix(text: &[u8]) -> Term {
+        Term::new(TermKind::Suffix, text)
+    }
+
+    pub fn equal(text: &[u8]) -> Term {
+        Term::new(TermKind::Equal, text)
+    }
+
+    /// The term matching exactly the candidates this one doesn't.
+    pub fn negate(mut self) -> Term {
+        self.negated = !self.negated;
+        self
+    }
+
+    /// The term with its fuzzy score scaled by `weight`, written last as in
+    /// `name^2` or `^lib$^0.5`. Positive scores are multiplied by it and
+    /// negative ones divided, so a heavier term always counts for more: `^2`
+    /// doubles a good match's score and halves a poor match's penalty. Exact
+    /// and negated terms don't score, so their weight has no effect.
+    ///
+    /// # Panics
+    ///
+    /// If `weight` isn't positive and finite.
+    pub fn weighted(mut self, weight: Score) -> Term {
+        assert!(
+            weight > 0.0 && weight.is_finite(),
+            "invalid term weight: {}",
+            weight
+        );
+        self.weight = Weight(weight);
+        self
+    }
+
+    /// What the term's fuzzy score is scaled by; 1 by default.
+    pub fn weight(&self) -> Score {
+        self.weight.0
+    }
+
+    /// `score` scaled by the term's weight, see [`weighted`](Term::weighted).
+    fn weigh(&self, score: Score) -> Score {
+        if score < 0.0 {
+            score / self.weight()
+        } else {
+            score * self.weight()
+        }
+    }
+
+    /// Where the term's text is in `text`, ignoring negation.
+    fn find(&self, text: &[u8]) -> Option<usize> {
+        let needle = &self.text[..];
+        let fold = !Case::Smart.respects(needle);
+        let eq = |at: usize| {
+            let hay = &text[at..at + needle.len()];
+            if fold {
+                hay.eq_ignore_ascii_case(needle)
+            } else {
+                hay == needle
+            }
+        };
+        let last = text.len().checked_sub(needle.len())?;
+        match self.kind {
+            TermKind::Fuzzy | TermKind::Exact => (0..=last).find(|&at| eq(at)),
+            TermKind::Prefix => Some(0).filter(|&at| eq(at)),
+            TermKind::Suffix => Some(last).filter(|&at| eq(at)),
+            TermKind::Equal => Some(0).filter(|&at| last == 0 && eq(at)),
+        }
+    }
+
+    /// Whether `text` satisfies the term, with the fuzzy score and positions
+    /// it contributes.
+    fn matches<A>(&self, algorithm: &A, text: &[u8]) -> Option<(Option<Score>, Vec<usize>)>
+    where
+        A: FuzzyAlgorithm + ?Sized,
+    {
+        if self.negated {
+            return self.find(text).is_none().then(|| (None, Vec::new()));
+        }
+        if self.kind != TermKind::Fuzzy {
+            let at = self.find(text)?;
+            return Some((None, (at..at + self.text.len()).collect()));
+        }
+        if self.text.is_empty() {
+            return Some((None, Vec::new()));
+        }
+        if !algorithm.has_match(&self.text, text) {
+            return None;
+        }
+        let positions = algorithm.positions(&self.text, text).unwrap_or_default();
+        let score = self.weigh(algorithm.score(&self.text, text));
+        Some((Some(score), positions))
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = String::from_utf8_lossy(&self.text);
+        let mut escaped = String::new();
+        for (i, c) in text.char_indices() {
+            let special = match c {
+                ' ' | '\\' | '^' => true,
+                '!' | '\'' => i == 0,
+                '$' => i + 1 == text.len(),
+                '|' => text.len() == 1,
+                _ => false,
+            };
+            if special {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        if self.negated {
+            f.write_str("!")?;
+        }
+        match self.kind {
+            TermKind::Fuzzy => write!(f, "{}", escaped)?,
+            TermKind::Exact => write!(f, "'{}", escaped)?,
+            TermKind::Prefix => write!(f, "^{}", escaped)?,
+            TermKind::Suffix => write!(f, "{}$", escaped)?,
+            TermKind::Equal => write!(f, "^{}$", escaped)?,
+        }
+        if self.weight() != 1.0 {
+            write!(f, "^{}", self.weight())?;
+        }
+        Ok(())
+    }
+}
+
+/// A query in fzf's extended syntax: every group must have a matching term.
+///
+/// Parsing `src^2 !test 'mod | lib$` gives three groups, the first weighted
+/// and the last with two alternatives; a backslash makes the next byte
+/// literal, as in `foo\ bar`.
+/// Queries built with [`and`](Query::and) and [`and_any`](Query::and_any)
+/// print back in the same syntax.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pub groups: Vec<Vec<Term>>,
+}
+
+impl Query {
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    /// Parses the extended syntax; never fails, as anything unrecognized is a
+    /// fuzzy term.
+    pub fn parse(query: &[u8]) -> Query {
+        let mut groups: Vec<Vec<Term>> = Vec::new();
+        let mut or = false;
+        for word in words(query) {
+            if word == [(b'|', false)] {
+                or = !groups.is_empty();
+                continue;
+            }
+            let term = parse_term(&word);
+            match groups.last_mut() {
+                Some(group) if or => group.push(term),
+                _ => groups.push(vec![term]),
+            }
+            or = false;
+        }
+        Query { groups }
+    }
+
+    /// Adds a term every match must satisfy.
+    pub fn and(mut self, term: Term) -> Query {
+        self.groups.push(vec![term]);
+        self
+    }
+
+    /// Adds alternatives of which every match must satisfy at least one.
+    pub fn and_any<I: IntoIterator<Item = Term>>(mut self, terms: I) -> Query {
+        self.groups.push(terms.into_iter().collect());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Matches `text`, scoring it by the sum over groups of the best weighted
+    /// fuzzy score among the group's matching terms, so a term weighted 2
+    /// counts twice as much as one weighted 1 and its penalty half as much.
+    /// Without fuzzy terms the score
+    /// is `SCORE_MIN` so that input order is kept.
+    pub fn score<A: FuzzyAlgorithm + ?Sized>(&self, algorithm: &A, text: &[u8]) -> Option<Score> {
+        self.match_text(algorithm, text).map(|(score, _)| score)
+    }
+
+    /// Indices into `text` matched by the query's terms, or `None` if it
+    /// doesn't match.
+    pub fn positions<A>(&self, algorithm: &A, text: &[u8]) -> Option<Vec<usize>>
+    where
+        A: FuzzyAlgorithm + ?Sized,
+    {
+        self.match_text(algorithm, text)
+            .map(|(_, positions)| positions)
+    }
+
+    /// The candidates matching, best first.
+    pub fn rank<A, T>(&self, algorithm: &A, candidates: &[T]) -> Vec<Match>
+    where
+        A: FuzzyAlgorithm + ?Sized,
+        T: AsRef<[u8]>,
+    {
+        let mut matches: Vec<Match> = (candidates.iter().enumerate())
+            .filter_map(|(index, text)| {
+                let score = self.score(algorithm, text.as_ref())?;
+                Some(Match { index, score })
+            })
+            .collect();
+        matches.sort_by(Match::rank_cmp);
+        matches
+    }
+
+    fn match_text<A>(&self, algorithm: &A, text: &[u8]) -> Option<(Score, Vec<usize>)>
+    where
+        A: FuzzyAlgorithm + ?Sized,
+    {
+        let mut total: Option<Score> = None;
+        let mut positions = Vec::new();
+        for group in &self.groups {
+            let (score, found) = (group.iter())
+                .filter_map(|term| term.matches(algorithm, text))
+                .max_by(|(a, _), (b, _)| {
+                    let (a, b) = (a.unwrap_or(SCORE_MIN), b.unwrap_or(SCORE_MIN));
+                    a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+                })?;
+            if let Some(score) = score {
+                total = Some(total.unwrap_or(0.0) + score);
+            }
+            positions.extend(found);
+        }
+        positions.sort_unstable();
+        positions.dedup();
+        Some((total.unwrap_or(SCORE_MIN), positions))
+    }
+}
+
+impl FromStr for Query {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Query, Self::Err> {
+        Ok(Query::parse(s.as_bytes()))
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, group) in self.groups.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            for (j, term) in group.iter().enumerate() {
+                if j > 0 {
+                    f.write_str(" | ")?;
+                }
+                write!(f, "{}", term)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits on unescaped spaces, pairing each byte with whether it was escaped.
+fn words(query: &[u8]) -> Vec<Vec<(u8, bool)>> {
+    let mut words = Vec::new();
+    let mut word = Vec::new();
+    let mut bytes = query.iter().copied();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\\' => word.push((bytes.next().unwrap_or(b'\\'), true)),
+            b' ' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            b => word.push((b, false)),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn parse_term(mut word: &[(u8, bool)]) -> Term {
+    let mut strip_first = |c: u8| match word.first() {
+        Some(&(b, false)) if b == c => {
+            word = &word[1..];
+            true
+        }
+        _ => false,
+    };
+    let negated = strip_first(b'!');
+    let exact = strip_first(b'\'');
+    let prefix = !exact && strip_first(b'^');
+    let mut weight = 1.0;
+    if let Some(caret) = word.iter().rposition(|&c| c == (b'^', false)) {
+        let digits: String = word[caret + 1..].iter().map(|&(b, _)| b as char).collect();
+        let escaped = word[caret + 1..].iter().any(|&(_, escaped)| escaped);
+        match digits.parse::<Score>() {
+            Ok(w) if caret > 0 && !escaped && w > 0.0 && w.is_finite() => {
+                weight = w;
+                word = &word[..caret];
+            }
+            _ => {}
+        }
+    }
+    let suffix = !exact && matches!(word.last(), Some(&(b'$', false)));
+    if suffix {
+        word = &word[..word.len() - 1];
+    }
+    let kind = match (exact, prefix, suffix) {
+        (true, _, _) => TermKind::Exact,
+        (_, true, true) => TermKind::Equal,
+        (_, true, false) => TermKind::Prefix,
+        (_, false, true) => TermKind::Suffix,
+        _ => TermKind::Fuzzy,
+    };
+    let text: Vec<u8> = word.iter().map(|&(b, _)| b).collect();
+    Term {
+        text,
+        kind,
+        negated,
+        weight: Weight(weight),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    #[test]
+    fn parses_extended_syntax() {
+        let query: Query = r"src !test 'mod | lib$ ^a\ b$ \!x".parse().unwrap();
+        let expected = Query::new()
+            .and(Term::fuzzy(b"src"))
+            .and(Term::fuzzy(b"test").negate())
+            .and_any([Term::exact(b"mod"), Term::suffix(b"lib")])
+            .and(Term::equal(b"a b"))
+            .and(Term::fuzzy(b"!x"));
+        assert_eq!(query, expected);
+        assert_eq!(query.to_string(), r"src !test 'mod | lib$ ^a\ b$ \!x");
+        assert_eq!(Query::parse(query.to_string().as_bytes()), expected);
+        assert!(Query::parse(b" | ").is_empty());
+    }
+
+    #[test]
+    fn weights_terms() {
+        let query = Query::parse(b"name^2 tag a^b x\\^3 ^p$^0.5");
+        let weights: Vec<Score> = query.groups.iter().map(|g| g[0].weight()).collect();
+        assert_eq!(weights, vec![2.0, 1.0, 1.0, 1.0, 0.5]);
+        assert_eq!(query.groups[2][0].text, b"a^b");
+        assert_eq!(query.groups[3][0].text, b"x^3");
+        assert_eq!(query.groups[4][0], Term::equal(b"p").weighted(0.5));
+        assert_eq!(Query::parse(query.to_string().as_bytes()), query);
+
+        // The weighted term decides which candidate wins.
+        let alg = Algorithm::Fzy;
+        let (a, b) = (&b"nxxame/tag"[..], &b"name/t_a_g"[..]);
+        let plain = Query::new()
+            .and(Term::fuzzy(b"name"))
+            .and(Term::fuzzy(b"tag"));
+        let weighted = Query::new()
+            .and(Term::fuzzy(b"name"))
+            .and(Term::fuzzy(b"tag").weighted(3.0));
+        let sum = |q: &Query, text| q.score(&alg, text).unwrap();
+        assert!(sum(&plain, a) < sum(&plain, b));
+        assert!(sum(&weighted, a) > sum(&weighted, b));
+        assert_eq!(
+            sum(&weighted, a),
+            alg.score(b"name", a) + 3.0 * alg.score(b"tag", a)
+        );
+
+        // A poor match's penalty shrinks rather than grows with its weight.
+        let poor = &b"xxxxxxnxxxxxxaxxxxxxmxxxxxxe"[..];
+        assert!(alg.score(b"name", poor) < 0.0);
+        let score = |weight| {
+            let query = Query::new().and(Term::fuzzy(b"name").weighted(weight));
+            query.score(&alg, poor).unwrap()
+        };
+        assert!(score(0.5) < score(1.0) && score(1.0) < score(2.0));
+        assert_eq!(score(2.0), alg.score(b"name", poor) / 2.0);
+    }
+
+    #[test]
+    fn matches_all_groups() {
+        let query = Query::parse(b"src !test 'Mod | lib.rs$");
+        let alg = Algorithm::Fzy;
+        assert!(query.score(&alg, b"src/lib.rs").is_some());
+        assert!(query.score(&alg, b"src/Model.rs").is_some());
+        assert!(query.score(&alg, b"src/model.rs").is_none());
+        assert!(query.score(&alg, b"src/test/lib.rs").is_none());
+        assert_eq!(
+            query.positions(&alg, b"src/lib.rs"),
+            Some((0..3).chain(4..10).collect())
+        );
+        let candidates = ["lib.rs", "src/a/lib.rs", "src/lib.rs"];
+        let ids: Vec<usize> = query
+            .rank(&alg, &candidates)
+            .iter()
+            .map(|m| m.index)
+            .collect();
+        assert_eq!(ids, vec![2, 1]);
+        assert_eq!(
+            Query::parse(b"^lib").rank(&alg, &candidates),
+            vec![Match {
+                index: 0,
+                score: SCORE_MIN
+            }]
+        );
+    }
+}
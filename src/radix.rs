@@ -0,0 +1,216 @@
+//! A radix tree of candidates, matching a query against each shared prefix
+//! once rather than once per candidate under it.
+
+use crate::{score, Match, SCORE_MIN};
+
+/// The bytes occurring somewhere in a subtree.
+#[derive(Debug, Clone, Copy, Default)]
+struct ByteSet([u64; 4]);
+
+impl ByteSet {
+    fn extend(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0[b as usize / 64] |= 1 << (b % 64);
+        }
+    }
+
+    fn contains_all(&self, bytes: &[u8]) -> bool {
+        (bytes.iter()).all(|&b| self.0[b as usize / 64] & (1 << (b % 64)) != 0)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    label: Vec<u8>,
+    parent: usize,
+    children: Vec<usize>,
+    /// Candidates ending at this node.
+    ids: Vec<usize>,
+    bytes: ByteSet,
+}
+
+/// Candidates stored in a radix tree and ranked with fzy's matching.
+///
+/// A search walks the tree consuming the query along each edge once, so
+/// candidates under a common directory share the work of matching it, and
+/// skips subtrees lacking any byte the rest of the query needs.
+#[derive(Debug, Clone)]
+pub struct RadixTree {
+    /// The root is node 0 and has an empty label.
+    nodes: Vec<Node>,
+    /// The node each candidate ends at, by id.
+    ends: Vec<usize>,
+}
+
+impl Default for RadixTree {
+    fn default() -> RadixTree {
+        RadixTree::new()
+    }
+}
+
+impl RadixTree {
+    pub fn new() -> RadixTree {
+        RadixTree {
+            nodes: vec![Node::default()],
+            ends: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Adds a candidate and returns its id.
+    pub fn insert(&mut self, text: &[u8]) -> usize {
+        let id = self.ends.len();
+        let mut node = 0;
+        let mut rest = text;
+        loop {
+            self.nodes[node].bytes.extend(rest);
+            let Some(&first) = rest.first() else {
+                break;
+            };
+            let child = (self.nodes[node].children.iter())
+                .copied()
+                .find(|&c| self.nodes[c].label[0] == first);
+            let Some(child) = child else {
+                let leaf = self.nodes.len();
+                let mut bytes = ByteSet::default();
+                bytes.extend(rest);
+                self.nodes.push(Node {
+                    label: rest.to_vec(),
+                    parent: node,
+                    bytes,
+                    ..Node::default()
+                });
+                self.nodes[node].children.push(leaf);
+                node = leaf;
+                break;
+            };
+            let label = &self.nodes[child].label;
+            let common = (label.iter().zip(rest)).take_while(|(a, b)| a == b).count();
+            node = if common < label.len() {
+                self.split(child, common)
+            } else {
+                child
+            };
+            rest = &rest[common..];
+        }
+        self.nodes[node].ids.push(id);
+        self.ends.push(node);
+        id
+    }
+
+    /// Puts a new node holding the first `at` bytes of `node`'s label between
+    /// it and its parent, returning the new node.
+    fn split(&mut self, node: usize, at: usize) -> usize {
+        let new = self.nodes.len();
+        let parent = self.nodes[node].parent;
+        let label = self.nodes[node].label.drain(..at).collect();
+        self.nodes.push(Node {
+            label,
+            parent,
+            children: vec![node],
+            ids: Vec::new(),
+            bytes: self.nodes[node].bytes,
+        });
+        self.nodes[node].parent = new;
+        for child in &mut self.nodes[parent].children {
+            if *child == node {
+                *child = new;
+            }
+        }
+        new
+    }
+
+    /// The candidate with id `id`.
+    pub fn get(&self, id: usize) -> Option<Vec<u8>> {
+        let mut node = *self.ends.get(id)?;
+        let mut labels = Vec::new();
+        while node != 0 {
+            labels.push(&self.nodes[node].label[..]);
+            node = self.nodes[node].parent;
+        }
+        Some(labels.into_iter().rev().flatten().copied().collect())
+    }
+
+    /// The candidates matching `query`, best first, indexed by id.
+    pub fn search(&self, query: &[u8]) -> Vec<Match> {
+        let mut matches = Vec::new();
+        self.visit(0, query, 0, &mut Vec::new(), &mut matches);
+        matches.sort_by(Match::rank_cmp);
+        matches
+    }
+
+    /// Matches the subtree at `node`, `path` holding the text above it and
+    /// `matched` how much of `query` that text already matches.
+    fn visit(
+        &self,
+        node: usize,
+        query: &[u8],
+        mut matched: usize,
+        path: &mut Vec<u8>,
+        matches: &mut Vec<Match>,
+    ) {
+        let node = &self.nodes[node];
+        if !node.bytes.contains_all(&query[matched..]) {
+            return;
+        }
+        for &b in &node.label {
+            if matched < query.len() && b == query[matched] {
+                matched += 1;
+            }
+        }
+        path.extend_from_slice(&node.label);
+        if matched == query.len() {
+            let score = if query.is_empty() {
+                SCORE_MIN
+            } else {
+                score(query, &path[..])
+            };
+            matches.extend(node.ids.iter().map(|&index| Match { index, score }));
+        }
+        for &child in &node.children {
+            self.visit(child, query, matched, path, matches);
+        }
+        path.truncate(path.len() - node.label.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::rank;
+    use crate::Algorithm;
+
+    #[test]
+    fn searches_like_plain_candidates() {
+        let texts = [
+            "src/lib.rs",
+            "src/radix.rs",
+            "src/ranked.rs",
+            "src",
+            "src/lib.rs",
+            "tests/radix.rs",
+            "",
+            "README.md",
+        ];
+        let mut tree = RadixTree::new();
+        for text in &texts {
+            tree.insert(text.as_bytes());
+        }
+        assert_eq!(tree.len(), texts.len());
+        for (id, text) in texts.iter().enumerate() {
+            assert_eq!(tree.get(id).unwrap(), text.as_bytes());
+        }
+        assert_eq!(tree.get(texts.len()), None);
+        for query in ["", "rs", "radix", "srl", "R", "zz"] {
+            let expected = rank(&Algorithm::Fzy, query.as_bytes(), &texts);
+            assert_eq!(tree.search(query.as_bytes()), expected, "{:?}", query);
+        }
+    }
+}
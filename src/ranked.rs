@@ -0,0 +1,441 @@
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::Range;
+
+use crate::algorithm::match_candidate;
+use crate::{Algorithm, FuzzyAlgorithm, Match, Score};
+
+/// Matches sorted best first, read a page at a time so that a view over a
+/// huge result set only touches the rows it shows.
+#[derive(Debug, Clone, Default)]
+pub struct RankedResults {
+    pub(crate) matches: Vec<Match>,
+}
+
+impl RankedResults {
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    pub fn get(&self, rank: usize) -> Option<&Match> {
+        self.matches.get(rank)
+    }
+
+    /// Up to `len` matches starting at rank `start`.
+    pub fn page(&self, start: usize, len: usize) -> &[Match] {
+        let start = start.min(self.matches.len());
+        let end = start.saturating_add(len).min(self.matches.len());
+        &self.matches[start..end]
+    }
+
+    pub fn as_slice(&self) -> &[Match] {
+        &self.matches
+    }
+}
+
+/// A set of candidates kept sorted against a query as items come and go.
+///
+/// Inserting or removing a candidate only scores that candidate and finds its
+/// rank with a binary search; the full set is re-ranked only when the query
+/// changes.
+#[derive(Debug, Default)]
+pub struct RankedSet<A = Algorithm> {
+    algorithm: A,
+    query: Vec<u8>,
+    candidates: Vec<Option<Vec<u8>>>,
+    total: usize,
+    pinned: Vec<usize>,
+    ranked: RankedResults,
+}
+
+impl RankedSet {
+    pub fn new(query: &[u8]) -> RankedSet {
+        RankedSet::with_algorithm(query, Algorithm::Fzy)
+    }
+}
+
+impl<A: FuzzyAlgorithm> RankedSet<A> {
+    pub fn with_algorithm(query: &[u8], algorithm: A) -> RankedSet<A> {
+        RankedSet {
+            algorithm,
+            query: query.to_vec(),
+            candidates: Vec::new(),
+            total: 0,
+            pinned: Vec::new(),
+            ranked: RankedResults::default(),
+        }
+    }
+
+    pub fn algorithm(&self) -> &A {
+        &self.algorithm
+    }
+
+    pub fn query(&self) -> &[u8] {
+        &self.query
+    }
+
+    /// Adds a candidate and returns its id.
+    pub fn insert(&mut self, text: &[u8]) -> usize {
+        let id = self.candidates.len();
+        if let Some(m) = self.rank_one(id, text) {
+            self.place(m);
+        }
+        self.candidates.push(Some(text.to_vec()));
+        self.total += 1;
+        id
+    }
+
+    /// Adds candidates and returns their ids. Their matches are sorted among
+    /// themselves and merged in with one pass over the ranking, rather than
+    /// shifting it once for each.
+    pub fn extend<T: AsRef<[u8]>>(&mut self, texts: &[T]) -> Range<usize> {
+        let start = self.candidates.len();
+        let mut new: Vec<Match> = (texts.iter().enumerate())
+            .filter_map(|(i, text)| self.rank_one(start + i, text.as_ref()))
+            .collect();
+        (self.candidates).extend(texts.iter().map(|text| Some(text.as_ref().to_vec())));
+        self.total += texts.len();
+        let pinned = &self.pinned;
+        new.sort_by(|a, b| pinned_cmp(pinned, a, b));
+        merge(&mut self.ranked.matches, new, |a, b| {
+            pinned_cmp(pinned, a, b)
+        });
+        start..self.candidates.len()
+    }
+
+    /// Removes the candidate with the given id, returning its text.
+    pub fn remove(&mut self, id: usize) -> Option<Vec<u8>> {
+        let text = self.candidates.get_mut(id)?.take()?;
+        self.total -= 1;
+        self.unplace(id, &text);
+        self.pinned.retain(|pinned| *pinned != id);
+        Some(text)
+    }
+
+    /// Replaces the text of the candidate with the given id, keeping its id
+    /// and pin, and returns the old text.
+    pub fn replace(&mut self, id: usize, text: &[u8]) -> Option<Vec<u8>> {
+        let old = self.candidates.get_mut(id)?.replace(text.to_vec())?;
+        self.unplace(id, &old);
+        if let Some(m) = self.rank_one(id, text) {
+            self.place(m);
+        }
+        Some(old)
+    }
+
+    /// Replaces the query and re-ranks every candidate.
+    pub fn set_query(&mut self, query: &[u8]) {
+        self.query = query.to_vec();
+        let mut ranked = Vec::new();
+        for (id, text) in self.candidates.iter().enumerate() {
+            if let Some(m) = text.as_ref().and_then(|t| self.rank_one(id, t)) {
+                ranked.push(m);
+            }
+        }
+        ranked.sort_by(|a, b| pinned_cmp(&self.pinned, a, b));
+        self.ranked = RankedResults { matches: ranked };
+    }
+
+    /// Ranks the candidate with the given id above every unpinned one, after
+    /// those pinned before it, whenever it matches the query.
+    pub fn pin(&mut self, id: usize) {
+        if self.get(id).is_some() && !self.pinned.contains(&id) {
+            self.pinned.push(id);
+            self.resort();
+        }
+    }
+
+    /// Lets the candidate with the given id rank by score again.
+    pub fn unpin(&mut self, id: usize) {
+        let len = self.pinned.len();
+        self.pinned.retain(|pinned| *pinned != id);
+        if self.pinned.len() != len {
+            self.resort();
+        }
+    }
+
+    /// Ids of the pinned candidates, in pin order.
+    pub fn pinned(&self) -> &[usize] {
+        &self.pinned
+    }
+
+    /// Removes every candidate. Ids start again from zero.
+    pub fn clear(&mut self) {
+        self.candidates.clear();
+        self.total = 0;
+        self.pinned.clear();
+        self.ranked.matches.clear();
+    }
+
+    pub fn get(&self, id: usize) -> Option<&[u8]> {
+        self.candidates.get(id)?.as_deref()
+    }
+
+    /// Number of candidates matching the current query.
+    pub fn len(&self) -> usize {
+        self.ranked.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranked.is_empty()
+    }
+
+    /// Number of candidates in the set, matching or not.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Matching candidates, best first.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], Score)> + '_ {
+        self.ranked
+            .as_slice()
+            .iter()
+            .map(move |m| (self.get(m.index).unwrap(), m.score))
+    }
+
+    pub fn matches(&self) -> &[Match] {
+        self.ranked.as_slice()
+    }
+
+    pub fn results(&self) -> &RankedResults {
+        &self.ranked
+    }
+
+    fn rank_one(&self, id: usize, text: &[u8]) -> Option<Match> {
+        match_candidate(&self.algorithm, &self.query, text, id)
+    }
+
+    /// Inserts a match where it ranks.
+    fn place(&mut self, m: Match) {
+        let pinned = &self.pinned;
+        let ranked = &mut self.ranked.matches;
+        let pos = ranked.partition_point(|r| pinned_cmp(pinned, r, &m).is_lt());
+        ranked.insert(pos, m);
+    }
+
+    /// Removes the match of the candidate with the given id, which had
+    /// `text`. Scoring `text` again finds it with a binary search; only if the
+    /// algorithm now scores it differently is the ranking scanned for it.
+    fn unplace(&mut self, id: usize, text: &[u8]) {
+        let Some(m) = self.rank_one(id, text) else {
+            return;
+        };
+        let pinned = &self.pinned;
+        let ranked = &mut self.ranked.matches;
+        let pos = ranked.partition_point(|r| pinned_cmp(pinned, r, &m).is_lt());
+        let pos = match ranked.get(pos) {
+            Some(r) if r.index == id => Some(pos),
+            _ => ranked.iter().position(|r| r.index == id),
+        };
+        if let Some(pos) = pos {
+            ranked.remove(pos);
+        }
+    }
+
+    fn resort(&mut self) {
+        let pinned = &self.pinned;
+        self.ranked.matches.sort_by(|a, b| pinned_cmp(pinned, a, b));
+    }
+}
+
+/// Merges `new` into `ranked`, both sorted by `cmp`.
+pub(crate) fn merge<T, F>(ranked: &mut Vec<T>, new: Vec<T>, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    if new.is_empty() {
+        return;
+    }
+    let old = mem::replace(ranked, Vec::with_capacity(ranked.len() + new.len()));
+    let mut old = old.into_iter().peekable();
+    let mut new = new.into_iter().peekable();
+    loop {
+        let next = match (old.peek(), new.peek()) {
+            (Some(o), Some(n)) if cmp(n, o).is_lt() => new.next(),
+            (Some(_), _) => old.next(),
+            (None, _) => new.next(),
+        };
+        match next {
+            Some(m) => ranked.push(m),
+            None => break,
+        }
+    }
+}
+
+/// Pinned matches first, in pin order, then [`Match::rank_cmp`].
+fn pinned_cmp(pinned: &[usize], a: &Match, b: &Match) -> Ordering {
+    let pin = |m: &Match| pinned.iter().position(|id| *id == m.index);
+    match (pin(a), pin(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.rank_cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts<A: FuzzyAlgorithm>(set: &RankedSet<A>) -> Vec<&[u8]> {
+        set.iter().map(|(t, _)| t).collect()
+    }
+
+    #[test]
+    fn insert_keeps_order() {
+        let mut set = RankedSet::new(b"amo");
+        set.insert(b"app/m/foo");
+        set.insert(b"xyz");
+        set.insert(b"app/models/foo");
+        assert_eq!(texts(&set), vec![&b"app/models/foo"[..], &b"app/m/foo"[..]]);
+        assert_eq!(set.get(1), Some(&b"xyz"[..]));
+    }
+
+    #[test]
+    fn extend_merges_batch() {
+        let mut set = RankedSet::new(b"amo");
+        set.insert(b"app/m/foo");
+        let pinned = set.insert(b"a/m/o/x");
+        set.pin(pinned);
+        let ids = set.extend(&[&b"xyz"[..], b"amo", b"app/models/foo"]);
+        assert_eq!(ids, 2..5);
+        assert_eq!(
+            texts(&set),
+            vec![
+                &b"a/m/o/x"[..],
+                &b"amo"[..],
+                &b"app/models/foo"[..],
+                &b"app/m/foo"[..]
+            ]
+        );
+        assert_eq!(set.total(), 5);
+        let mut one_by_one = RankedSet::new(b"amo");
+        for text in ["app/m/foo", "a/m/o/x", "xyz", "amo", "app/models/foo"] {
+            one_by_one.insert(text.as_bytes());
+        }
+        one_by_one.pin(pinned);
+        assert_eq!(set.matches(), one_by_one.matches());
+    }
+
+    #[test]
+    fn remove_drops_candidate() {
+        let mut set = RankedSet::new(b"a");
+        let a = set.insert(b"a/b");
+        set.insert(b"ba");
+        assert_eq!(set.remove(a), Some(b"a/b".to_vec()));
+        assert_eq!(set.remove(a), None);
+        assert_eq!(set.total(), 1);
+        assert_eq!(texts(&set), vec![&b"ba"[..]]);
+    }
+
+    #[test]
+    fn remove_finds_ties_and_pins() {
+        let mut set = RankedSet::new(b"a");
+        let ids: Vec<usize> = (0..6).map(|_| set.insert(b"xa")).collect();
+        set.pin(ids[4]);
+        set.remove(ids[2]);
+        set.remove(ids[4]);
+        set.replace(ids[0], b"b");
+        let left: Vec<usize> = set.matches().iter().map(|m| m.index).collect();
+        assert_eq!(left, vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn replace_keeps_id() {
+        let mut set = RankedSet::new(b"b");
+        let a = set.insert(b"a");
+        set.insert(b"ab");
+        set.pin(a);
+        assert_eq!(set.replace(a, b"b"), Some(b"a".to_vec()));
+        assert_eq!(texts(&set), vec![&b"b"[..], &b"ab"[..]]);
+        assert_eq!(set.replace(a, b"c"), Some(b"b".to_vec()));
+        assert_eq!(texts(&set), vec![&b"ab"[..]]);
+        assert_eq!(set.replace(5, b"c"), None);
+    }
+
+    #[test]
+    fn clear_restarts_ids() {
+        let mut set = RankedSet::new(b"a");
+        set.insert(b"a");
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set.insert(b"ba"), 0);
+        assert_eq!(texts(&set), vec![&b"ba"[..]]);
+    }
+
+    #[test]
+    fn pinned_rank_first() {
+        let mut set = RankedSet::new(b"t");
+        set.insert(b"test");
+        let build = set.insert(b"build tests");
+        let lint = set.insert(b"lint");
+        set.pin(lint);
+        set.pin(build);
+        set.insert(b"t");
+        assert_eq!(
+            texts(&set),
+            vec![&b"lint"[..], &b"build tests"[..], &b"t"[..], &b"test"[..]]
+        );
+        set.set_query(b"te");
+        assert_eq!(texts(&set), vec![&b"build tests"[..], &b"test"[..]]);
+        set.unpin(build);
+        assert_eq!(texts(&set), vec![&b"test"[..], &b"build tests"[..]]);
+        set.remove(lint);
+        assert!(set.pinned().is_empty());
+    }
+
+    #[test]
+    fn set_query_reranks() {
+        let mut set = RankedSet::new(b"");
+        set.insert(b"tests");
+        set.insert(b"testing");
+        set.insert(b"other");
+        assert_eq!(set.len(), 3);
+        set.set_query(b"test");
+        assert_eq!(texts(&set), vec![&b"tests"[..], &b"testing"[..]]);
+    }
+
+    #[test]
+    fn pages_results() {
+        let mut set = RankedSet::new(b"");
+        for text in ["a", "b", "c"] {
+            set.insert(text.as_bytes());
+        }
+        let results = set.results();
+        assert_eq!(results.page(1, 5).len(), 2);
+        assert_eq!(results.page(1, 1)[0].index, 1);
+        assert!(results.page(5, 2).is_empty());
+        assert!(results.page(1, usize::MAX).len() == 2);
+    }
+
+    struct Substring;
+
+    impl FuzzyAlgorithm for Substring {
+        fn has_match(&self, pat: &[u8], text: &[u8]) -> bool {
+            self.positions(pat, text).is_some()
+        }
+
+        fn score(&self, _pat: &[u8], text: &[u8]) -> Score {
+            -(text.len() as Score)
+        }
+
+        fn positions(&self, pat: &[u8], text: &[u8]) -> Option<Vec<usize>> {
+            let start =
+                (0..=text.len().checked_sub(pat.len())?).find(|i| text[*i..].starts_with(pat))?;
+            Some((start..start + pat.len()).collect())
+        }
+    }
+
+    #[test]
+    fn custom_algorithm() {
+        let mut set = RankedSet::with_algorithm(b"ab", Substring);
+        set.insert(b"a_b");
+        set.insert(b"xxab");
+        set.insert(b"ab");
+        assert_eq!(texts(&set), vec![&b"ab"[..], &b"xxab"[..]]);
+    }
+}
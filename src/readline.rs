@@ -0,0 +1,183 @@
+//! Fuzzy tab completion for [rustyline] editors: commands at the start of
+//! the line and paths after it, best matches first.
+//!
+//! ```no_run
+//! use fzy_rs::readline::FuzzyCompleter;
+//! use rustyline::history::DefaultHistory;
+//! use rustyline::Editor;
+//!
+//! let mut editor: Editor<FuzzyCompleter, DefaultHistory> = Editor::new().unwrap();
+//! editor.set_helper(Some(FuzzyCompleter::new(["checkout", "commit"]).with_paths(true)));
+//! let line = editor.readline("> ");
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::algorithm::rank;
+use crate::{Cased, FuzzyAlgorithm};
+
+/// A rustyline helper completing the word before the cursor by fuzzy
+/// matching: the first word against the commands, later ones against the
+/// entries of the directory they name, with fzy's ranking by default.
+#[derive(Debug, Clone, Default)]
+pub struct FuzzyCompleter<A = Cased> {
+    algorithm: A,
+    commands: Vec<String>,
+    paths: bool,
+}
+
+impl FuzzyCompleter {
+    pub fn new<I, S>(commands: I) -> FuzzyCompleter
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        FuzzyCompleter::with_algorithm(commands, Cased::default())
+    }
+}
+
+impl<A: FuzzyAlgorithm> FuzzyCompleter<A> {
+    pub fn with_algorithm<I, S>(commands: I, algorithm: A) -> FuzzyCompleter<A>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        FuzzyCompleter {
+            algorithm,
+            commands: commands.into_iter().map(Into::into).collect(),
+            paths: false,
+        }
+    }
+
+    /// Whether words after the command, or every word without commands, are
+    /// completed as paths.
+    pub fn with_paths(mut self, paths: bool) -> FuzzyCompleter<A> {
+        self.paths = paths;
+        self
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    /// `candidates` matching `word`, best first, each replacing it with
+    /// `prefix` followed by the candidate.
+    fn ranked(&self, word: &str, prefix: &str, candidates: &[String]) -> Vec<Pair> {
+        (rank(&self.algorithm, word.as_bytes(), candidates).iter())
+            .map(|m| Pair {
+                display: candidates[m.index].clone(),
+                replacement: format!("{}{}", prefix, candidates[m.index]),
+            })
+            .collect()
+    }
+
+    /// Entries of the directory `word` names up to its last slash, matched
+    /// against the rest. Hidden entries are only offered for a query starting
+    /// with a dot.
+    fn complete_path(&self, word: &str) -> Vec<Pair> {
+        let (dir, query) = match word.rfind('/') {
+            Some(slash) => word.split_at(slash + 1),
+            None => ("", word),
+        };
+        let path = Path::new(if dir.is_empty() { "." } else { dir });
+        let Ok(entries) = fs::read_dir(path) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let mut name = entry.file_name().into_string().ok()?;
+                if name.starts_with('.') && !query.starts_with('.') {
+                    return None;
+                }
+                if entry.file_type().ok()?.is_dir() {
+                    name.push('/');
+                }
+                Some(name)
+            })
+            .collect();
+        names.sort();
+        self.ranked(query, dir, &names)
+    }
+}
+
+impl<A: FuzzyAlgorithm> Completer for FuzzyCompleter<A> {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = (before.char_indices().rev())
+            .find(|&(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let word = &before[start..];
+        let first = before[..start].trim().is_empty();
+        let candidates = if first && !self.commands.is_empty() {
+            self.ranked(word, "", &self.commands)
+        } else if self.paths {
+            self.complete_path(word)
+        } else {
+            Vec::new()
+        };
+        Ok((start, candidates))
+    }
+}
+
+impl<A> Hinter for FuzzyCompleter<A> {
+    type Hint = String;
+}
+
+impl<A> Highlighter for FuzzyCompleter<A> {}
+
+impl<A> Validator for FuzzyCompleter<A> {}
+
+impl<A: FuzzyAlgorithm> Helper for FuzzyCompleter<A> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::MemHistory;
+
+    fn complete(completer: &FuzzyCompleter, line: &str) -> (usize, Vec<String>) {
+        let history = MemHistory::new();
+        let (start, pairs) = completer
+            .complete(line, line.len(), &Context::new(&history))
+            .unwrap();
+        (start, pairs.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[test]
+    fn completes_commands_and_paths() {
+        let completer = FuzzyCompleter::new(["checkout", "cherry-pick", "commit"]).with_paths(true);
+        assert_eq!(
+            complete(&completer, "  chp"),
+            (2, vec!["cherry-pick".to_string()])
+        );
+        assert_eq!(complete(&completer, "co").1, vec!["commit", "checkout"]);
+        assert_eq!(complete(&completer, "").1.len(), 3);
+        assert_eq!(complete(&completer, "x\u{3000}chp").0, "x\u{3000}".len());
+
+        let dir = std::env::temp_dir().join(format!("fzy-readline-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        let line = format!("commit {}/sr", dir.display());
+        let (start, paths) = complete(&completer, &line);
+        assert_eq!(start, "commit ".len());
+        assert_eq!(paths, vec![format!("{}/src/", dir.display())]);
+        let (_, paths) = complete(&completer, &format!("x {}/", dir.display()));
+        assert_eq!(paths.len(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,316 @@
+//! Recordings of what a picker ranked, replayed to reproduce its rankings.
+//!
+//! A recording starts with the [`Config`] the picker ranked with, one
+//! setting per line: `algorithm NAME`, `case MODE`, then `delimiter STR` and
+//! `nth FIELDS` when set and `boost SCORE TEXT` for each boosted candidate,
+//! ending with an empty line. Then it has one step per line: `+ TEXT` for
+//! each candidate, `? QUERY` for each query change and `clear` when the
+//! candidates are dropped. Texts are escaped as in history files.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::sync::Arc;
+
+use crate::history::{escape, unescape};
+use crate::{Boost, Boosted, Candidate, Cased, FieldMatching, Fields, Match, RankedSet, Score};
+
+/// How a recorded picker ranked, so that its replay ranks the same way.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub matching: FieldMatching<Cased>,
+    /// What boosts added to the score of each boosted candidate, such as the
+    /// [`SelectionBoost::boosts`](crate::SelectionBoost::boosts) of the
+    /// picker.
+    pub boosts: Vec<(Vec<u8>, Score)>,
+}
+
+impl Config {
+    /// The algorithm of a picker configured like this.
+    pub fn algorithm(&self) -> Boosted<FieldMatching<Cased>> {
+        let boosts: HashMap<Vec<u8>, Score> = self.boosts.iter().cloned().collect();
+        Boosted {
+            algorithm: self.matching.clone(),
+            boosts: vec![Arc::new(Snapshot(boosts)) as Arc<dyn Boost>],
+        }
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let Cased { algorithm, case } = self.matching.algorithm;
+        writeln!(out, "algorithm {}", algorithm)?;
+        writeln!(out, "case {}", case)?;
+        let fields = &self.matching.fields;
+        if let Some(delimiter) = &fields.delimiter {
+            out.write_all(b"delimiter ")?;
+            out.write_all(&escape(delimiter))?;
+            out.write_all(b"\n")?;
+        }
+        if !fields.ranges.is_empty() {
+            let ranges: Vec<String> = fields.ranges.iter().map(ToString::to_string).collect();
+            writeln!(out, "nth {}", ranges.join(","))?;
+        }
+        for (text, boost) in &self.boosts {
+            write!(out, "boost {} ", boost)?;
+            out.write_all(&escape(text))?;
+            out.write_all(b"\n")?;
+        }
+        out.write_all(b"\n")
+    }
+
+    fn read<R: BufRead>(reader: &mut R) -> io::Result<Config> {
+        let mut config = Config::default();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Err(invalid("unterminated config".to_string()));
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            if line.is_empty() {
+                return Ok(config);
+            }
+            let (key, value) = match line.iter().position(|b| *b == b' ') {
+                Some(space) => (&line[..space], &line[space + 1..]),
+                None => (&line[..], &b""[..]),
+            };
+            let text = || String::from_utf8_lossy(value);
+            let matching = &mut config.matching;
+            match key {
+                b"algorithm" => matching.algorithm.algorithm = text().parse().map_err(invalid)?,
+                b"case" => matching.algorithm.case = text().parse().map_err(invalid)?,
+                b"delimiter" => matching.fields.delimiter = Some(unescape(value)),
+                b"nth" => {
+                    matching.fields.ranges = Fields::parse_ranges(&text()).map_err(invalid)?
+                }
+                b"boost" => {
+                    let space = value.iter().position(|b| *b == b' ').unwrap_or(value.len());
+                    let boost = String::from_utf8_lossy(&value[..space]);
+                    let boost = (boost.parse())
+                        .map_err(|_| invalid(format!("invalid boost: {}", boost)))?;
+                    let text = value.get(space + 1..).unwrap_or_default();
+                    config.boosts.push((unescape(text), boost));
+                }
+                _ => {
+                    let key = String::from_utf8_lossy(key);
+                    return Err(invalid(format!("unknown setting: {}", key)));
+                }
+            }
+        }
+    }
+}
+
+/// The boosts of a [`Config`], as they were when it was recorded.
+#[derive(Debug)]
+struct Snapshot(HashMap<Vec<u8>, Score>);
+
+impl Boost for Snapshot {
+    fn boost(&self, candidate: &Candidate) -> Score {
+        self.0.get(candidate.text).copied().unwrap_or(0.0)
+    }
+}
+
+pub struct Recorder {
+    out: BufWriter<Box<dyn Write + Send>>,
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recorder").finish_non_exhaustive()
+    }
+}
+
+impl Recorder {
+    pub fn new<W: Write + Send + 'static>(out: W, config: &Config) -> io::Result<Recorder> {
+        let mut out = BufWriter::new(Box::new(out) as Box<dyn Write + Send>);
+        config.write(&mut out)?;
+        Ok(Recorder { out })
+    }
+
+    pub fn candidate(&mut self, text: &[u8]) -> io::Result<()> {
+        self.step(b"+ ", text)
+    }
+
+    /// Records a query change, flushing so that a recording cut short by a
+    /// crash still has every query typed before it.
+    pub fn query(&mut self, query: &[u8]) -> io::Result<()> {
+        self.step(b"? ", query)?;
+        self.out.flush()
+    }
+
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.out.write_all(b"clear\n")
+    }
+
+    fn step(&mut self, kind: &[u8], text: &[u8]) -> io::Result<()> {
+        self.out.write_all(kind)?;
+        self.out.write_all(&escape(text))?;
+        self.out.write_all(b"\n")
+    }
+}
+
+/// The matches for one query of a recording, indices being candidate ids in
+/// [`Replay::set`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ranking {
+    pub query: Vec<u8>,
+    pub matches: Vec<Match>,
+}
+
+/// Replays a recording, yielding the ranking after each query change and,
+/// when candidates arrived after the last one, once more at the end.
+#[derive(Debug)]
+pub struct Replay<R> {
+    reader: R,
+    config: Config,
+    set: RankedSet<Boosted<FieldMatching<Cased>>>,
+    pending: bool,
+}
+
+impl<R: BufRead> Replay<R> {
+    pub fn new(mut reader: R) -> io::Result<Replay<R>> {
+        let config = Config::read(&mut reader)?;
+        Ok(Replay {
+            reader,
+            set: RankedSet::with_algorithm(b"", config.algorithm()),
+            config,
+            pending: false,
+        })
+    }
+
+    /// What the recorded picker ranked with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The candidates replayed so far.
+    pub fn set(&self) -> &RankedSet<Boosted<FieldMatching<Cased>>> {
+        &self.set
+    }
+
+    fn ranking(&mut self) -> Ranking {
+        self.pending = false;
+        Ranking {
+            query: self.set.query().to_vec(),
+            matches: self.set.matches().to_vec(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Replay<R> {
+    type Item = io::Result<Ranking>;
+
+    fn next(&mut self) -> Option<io::Result<Ranking>> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match self.reader.read_until(b'\n', &mut line) {
+                Ok(0) if self.pending => return Some(Ok(self.ranking())),
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(err) => return Some(Err(err)),
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            if let Some(text) = line.strip_prefix(b"+ ") {
+                self.set.insert(&unescape(text));
+                self.pending = true;
+            } else if let Some(query) = line.strip_prefix(b"? ") {
+                self.set.set_query(&unescape(query));
+                return Some(Ok(self.ranking()));
+            } else if line == b"clear" {
+                self.set.clear();
+                self.pending = true;
+            } else {
+                let step = String::from_utf8_lossy(&line);
+                return Some(Err(invalid(format!("unknown step: {}", step))));
+            }
+        }
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Algorithm, Case};
+    use std::sync::Mutex;
+
+    /// A writer whose output stays readable after the recorder owning it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn replays_rankings() {
+        let out = Shared::default();
+        let config = Config {
+            matching: FieldMatching {
+                algorithm: Cased {
+                    algorithm: Algorithm::FzfV1,
+                    case: Case::Respect,
+                },
+                fields: Fields {
+                    delimiter: Some(b":\t".to_vec()),
+                    ranges: Fields::parse_ranges("2,-1..").unwrap(),
+                },
+            },
+            boosts: vec![(b"y:\tab".to_vec(), 0.5)],
+        };
+        let mut recorder = Recorder::new(out.clone(), &config).unwrap();
+        let mut set = RankedSet::with_algorithm(b"", config.algorithm());
+        let mut expected = Vec::new();
+        let steps = [
+            "+x:\tab", "+y:\tab", "+z:\tAB", "+ab\nc", "?a", "?A", "+xab", "?b", "clear", "+b",
+        ];
+        for step in steps {
+            let (kind, text) = step.split_at(1);
+            match kind {
+                "+" => {
+                    recorder.candidate(text.as_bytes()).unwrap();
+                    set.insert(text.as_bytes());
+                }
+                "?" => {
+                    recorder.query(text.as_bytes()).unwrap();
+                    set.set_query(text.as_bytes());
+                    expected.push(set.matches().to_vec());
+                }
+                _ => {
+                    recorder.clear().unwrap();
+                    set.clear();
+                }
+            }
+        }
+        expected.push(set.matches().to_vec());
+        // Of two candidates matching alike, the boosted one ranks first.
+        assert_eq!(expected[0][0].index, 1);
+        drop(recorder);
+
+        let recording = out.0.lock().unwrap().clone();
+        let mut replay = Replay::new(&recording[..]).unwrap();
+        let replayed: Vec<Vec<Match>> = (&mut replay).map(|r| r.unwrap().matches).collect();
+        assert_eq!(replayed, expected);
+        assert_eq!(replay.config().matching.fields, config.matching.fields);
+        assert_eq!(
+            replay.config().matching.algorithm,
+            config.matching.algorithm
+        );
+        assert_eq!(replay.config().boosts, config.boosts);
+        assert!(Replay::new(&b"nope\n\n"[..]).is_err());
+        assert!(Replay::new(&b"algorithm fzy\n"[..]).is_err());
+    }
+}
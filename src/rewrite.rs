@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+
+use crate::explain::{explain, Explanation};
+
+/// Aliases expanded in a query before it is matched, such as `@t` for
+/// `tests/` or `~` for the home directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rewriter {
+    rules: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Rewriter {
+    pub fn new() -> Rewriter {
+        Rewriter::default()
+    }
+
+    /// Expands `alias` to `expansion` where it starts a space separated term.
+    /// Earlier rules win when several aliases start the same way.
+    pub fn add(&mut self, alias: &[u8], expansion: &[u8]) -> &mut Rewriter {
+        if !alias.is_empty() {
+            self.rules.push((alias.to_vec(), expansion.to_vec()));
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The query that is actually searched for.
+    pub fn rewrite<'a>(&self, query: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.is_empty() {
+            return Cow::Borrowed(query);
+        }
+        let mut out = Vec::with_capacity(query.len());
+        let mut i = 0;
+        while i < query.len() {
+            if i == 0 || query[i - 1] == b' ' {
+                let rule = (self.rules.iter()).find(|(alias, _)| query[i..].starts_with(alias));
+                if let Some((alias, expansion)) = rule {
+                    out.extend_from_slice(expansion);
+                    i += alias.len();
+                    continue;
+                }
+            }
+            out.push(query[i]);
+            i += 1;
+        }
+        Cow::Owned(out)
+    }
+
+    /// [`explain`] for the rewritten query, which the explanation records.
+    pub fn explain(&self, query: &[u8], text: &[u8]) -> Option<Explanation> {
+        explain(self.rewrite(query), text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_aliases() {
+        let mut rewriter = Rewriter::new();
+        rewriter.add(b"@t", b"tests/").add(b"~", b"/home/me/");
+        assert_eq!(&rewriter.rewrite(b"@t foo")[..], b"tests/ foo");
+        assert_eq!(&rewriter.rewrite(b"~cfg a@t")[..], b"/home/me/cfg a@t");
+        assert_eq!(&Rewriter::new().rewrite(b"@t")[..], b"@t");
+
+        let explanation = rewriter.explain(b"@tm", b"tests/main.rs").unwrap();
+        assert_eq!(explanation.query, b"tests/m".to_vec());
+        assert!(rewriter.explain(b"@tx", b"tests/main.rs").is_none());
+    }
+}
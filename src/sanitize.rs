@@ -0,0 +1,97 @@
+/// How [`sanitize`] shows control characters other than tabs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// In caret notation, `^A` for 0x01 and `^?` for DEL. Controls without
+    /// one, such as C1 controls, become U+FFFD.
+    Caret,
+    /// As the given character.
+    Replace(char),
+}
+
+/// Makes candidates safe to show on a terminal, where a raw tab or escape
+/// would throw off the layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sanitize {
+    /// Tabs are expanded to the next multiple of this many columns; with 0
+    /// they are shown like any other control character.
+    pub tab_width: usize,
+    pub control: Control,
+}
+
+impl Default for Sanitize {
+    fn default() -> Sanitize {
+        Sanitize {
+            tab_width: 8,
+            control: Control::Caret,
+        }
+    }
+}
+
+/// The characters to show for `text`, each with the offset of the byte in
+/// `text` it stands for, so matched positions can still be highlighted.
+/// Invalid UTF-8 is shown as U+FFFD.
+pub fn sanitized_chars(text: &[u8], opts: &Sanitize) -> Vec<(usize, char)> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut offset = 0;
+    for chunk in text.utf8_chunks() {
+        for (i, c) in chunk.valid().char_indices() {
+            push_char(&mut out, offset + i, c, opts);
+        }
+        offset += chunk.valid().len();
+        if !chunk.invalid().is_empty() {
+            out.push((offset, char::REPLACEMENT_CHARACTER));
+            offset += chunk.invalid().len();
+        }
+    }
+    out
+}
+
+fn push_char(out: &mut Vec<(usize, char)>, offset: usize, c: char, opts: &Sanitize) {
+    if c == '\t' && opts.tab_width > 0 {
+        let spaces = opts.tab_width - out.len() % opts.tab_width;
+        out.extend((0..spaces).map(|_| (offset, ' ')));
+    } else if !c.is_control() {
+        out.push((offset, c));
+    } else {
+        match opts.control {
+            Control::Caret if (c as u32) < 0x20 || c == '\x7f' => {
+                out.push((offset, '^'));
+                out.push((offset, ((c as u8) ^ 0x40) as char));
+            }
+            Control::Caret => out.push((offset, char::REPLACEMENT_CHARACTER)),
+            Control::Replace(r) => out.push((offset, r)),
+        }
+    }
+}
+
+/// `text` as it is safe to show, see [`sanitized_chars`].
+pub fn sanitize(text: &[u8], opts: &Sanitize) -> String {
+    sanitized_chars(text, opts)
+        .into_iter()
+        .map(|(_, c)| c)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_tabs_and_controls() {
+        let opts = Sanitize::default();
+        assert_eq!(sanitize(b"a\tb\x1b[0m\x7f", &opts), "a       b^[[0m^?");
+        let text = [&b"\xc2\x85"[..], "é".as_bytes(), b"\xff"].concat();
+        assert_eq!(sanitize(&text, &opts), "\u{fffd}é\u{fffd}");
+        let opts = Sanitize {
+            tab_width: 4,
+            control: Control::Replace('?'),
+        };
+        assert_eq!(sanitize(b"ab\tc\x01", &opts), "ab  c?");
+
+        let chars = sanitized_chars(b"\tx\xffy", &opts);
+        assert_eq!(chars[3], (0, ' '));
+        assert_eq!(chars[4], (1, 'x'));
+        assert_eq!(chars[5], (2, '\u{fffd}'));
+        assert_eq!(chars[6], (3, 'y'));
+    }
+}
@@ -0,0 +1,384 @@
+use std::fmt;
+use std::ops::Range;
+use std::time::Instant;
+
+use crate::metrics::MetricsSink;
+use crate::ranked::merge;
+use crate::trace::span;
+use crate::{
+    compute_bonuses, fold, gap_score, match_row, Case, Match, Metrics, RankedResults, Score,
+    SCORE_GAP_INNER, SCORE_MAX, SCORE_MIN,
+};
+
+/// A candidate along with what the DP needs to extend its match by one more
+/// query byte.
+#[derive(Debug)]
+struct Candidate {
+    text: Vec<u8>,
+    lower: Vec<u8>,
+    bonuses: Vec<Score>,
+    /// Where the case-sensitive subsequence match of the query ended, or
+    /// `None` once the query stopped matching.
+    next: Option<usize>,
+    /// The D row of the query's last byte.
+    d: Vec<Score>,
+    score: Score,
+}
+
+impl Candidate {
+    fn new(text: &[u8]) -> Candidate {
+        Candidate {
+            text: text.to_vec(),
+            lower: fold::to_ascii_lowercase(text),
+            bonuses: compute_bonuses(text),
+            next: Some(0),
+            d: vec![0.0; text.len()],
+            score: SCORE_MIN,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.next = Some(0);
+        self.score = SCORE_MIN;
+    }
+
+    /// Extends the match from `query[..pi]` to `query[..=pi]`.
+    fn push(&mut self, query: &[u8], pi: usize, folding: Folding, scratch: &mut Scratch) {
+        let next = match self.next {
+            Some(next) => next,
+            None => return,
+        };
+        let (text, qc) = folding.matching.apply(self, query[pi]);
+        self.next = text[next..]
+            .iter()
+            .position(|tc| *tc == qc)
+            .map(|i| next + i + 1);
+        if self.next.is_none() {
+            return;
+        }
+
+        let n = self.text.len();
+        let (prev_m, cur_d, cur_m) = scratch.rows(n);
+        // The previous row was scored as the last one, with a trailing gap;
+        // now that it is an inner row its M values need an inner gap instead.
+        let mut prev_score = SCORE_MIN;
+        for (m, d) in prev_m.iter_mut().zip(&self.d) {
+            prev_score = d.max(prev_score + SCORE_GAP_INNER);
+            *m = prev_score;
+        }
+        let (text, qc) = folding.scoring.apply(self, query[pi]);
+        match_row(
+            pi,
+            qc,
+            gap_score(pi, pi + 1),
+            text,
+            &self.bonuses,
+            (&self.d, prev_m),
+            (cur_d, cur_m),
+        );
+        self.d.copy_from_slice(cur_d);
+        self.score = if pi + 1 == n { SCORE_MAX } else { cur_m[n - 1] };
+    }
+}
+
+/// How a query is compared with candidates, for telling whether a match
+/// found for one query can be extended for another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Folding {
+    matching: Fold,
+    scoring: Fold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fold {
+    Case,
+    Keep,
+}
+
+impl Fold {
+    /// The candidate's text and query byte as compared.
+    fn apply(self, candidate: &Candidate, qc: u8) -> (&[u8], u8) {
+        match self {
+            Fold::Case => (&candidate.lower, qc.to_ascii_lowercase()),
+            Fold::Keep => (&candidate.text, qc),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Scratch {
+    prev_m: Vec<Score>,
+    cur_d: Vec<Score>,
+    cur_m: Vec<Score>,
+}
+
+impl Scratch {
+    fn rows(&mut self, n: usize) -> (&mut [Score], &mut [Score], &mut [Score]) {
+        for row in [&mut self.prev_m, &mut self.cur_d, &mut self.cur_m] {
+            if row.len() < n {
+                row.resize(n, 0.0);
+            }
+        }
+        (
+            &mut self.prev_m[..n],
+            &mut self.cur_d[..n],
+            &mut self.cur_m[..n],
+        )
+    }
+}
+
+/// Ranks candidates with fzy's scoring while the query is being typed.
+///
+/// Each candidate keeps the DP row of the query's last byte, so appending a
+/// byte to the query costs one row per candidate rather than a full re-score.
+/// Any other edit re-scores from scratch. Scores and matches are the same as
+/// [`Algorithm::Fzy`](crate::Algorithm::Fzy), or of fzy with a [`Case`] mode
+/// as [`Cased`](crate::Cased) has them.
+///
+/// Sessions are specific to fzy, whose DP rows they extend; other algorithms
+/// are ranked with a [`RankedSet`](crate::RankedSet).
+#[derive(Default)]
+pub struct QuerySession {
+    /// `None` to rank like plain fzy.
+    case: Option<Case>,
+    query: Vec<u8>,
+    candidates: Vec<Candidate>,
+    scratch: Scratch,
+    ranked: RankedResults,
+    metrics: Option<MetricsSink>,
+}
+
+impl fmt::Debug for QuerySession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("QuerySession")
+            .field("query", &self.query)
+            .field("candidates", &self.candidates.len())
+            .field("ranked", &self.ranked)
+            .finish_non_exhaustive()
+    }
+}
+
+impl QuerySession {
+    pub fn new() -> QuerySession {
+        QuerySession::default()
+    }
+
+    /// A session ranking like [`Cased`](crate::Cased) with fzy and `case`.
+    pub fn with_case(case: Case) -> QuerySession {
+        QuerySession {
+            case: Some(case),
+            ..QuerySession::default()
+        }
+    }
+
+    /// How `query` is compared with candidates. Plain fzy matches bytes of
+    /// the query's case and scores regardless of case.
+    fn folding(&self, query: &[u8]) -> Folding {
+        let (matching, scoring) = match self.case {
+            None => (Fold::Keep, Fold::Case),
+            Some(case) if case.respects(query) => (Fold::Keep, Fold::Keep),
+            Some(_) => (Fold::Case, Fold::Case),
+        };
+        Folding { matching, scoring }
+    }
+
+    /// Adds a candidate and returns its id.
+    pub fn push(&mut self, text: &[u8]) -> usize {
+        self.extend(&[text]).start
+    }
+
+    /// Adds candidates and returns their ids, merging their matches into the
+    /// ranking in one pass.
+    pub fn extend<T: AsRef<[u8]>>(&mut self, texts: &[T]) -> Range<usize> {
+        let start = self.candidates.len();
+        let folding = self.folding(&self.query);
+        let mut new = Vec::new();
+        for text in texts {
+            let id = self.candidates.len();
+            let mut candidate = Candidate::new(text.as_ref());
+            for pi in 0..self.query.len() {
+                candidate.push(&self.query, pi, folding, &mut self.scratch);
+            }
+            new.extend(rank_one(id, &candidate, &self.query));
+            self.candidates.push(candidate);
+        }
+        new.sort_by(Match::rank_cmp);
+        merge(&mut self.ranked.matches, new, Match::rank_cmp);
+        start..self.candidates.len()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&[u8]> {
+        self.candidates.get(id).map(|c| &c.text[..])
+    }
+
+    /// Drops every candidate, keeping the query.
+    pub fn clear(&mut self) {
+        self.candidates.clear();
+        self.ranked.matches.clear();
+    }
+
+    pub fn query(&self) -> &[u8] {
+        &self.query
+    }
+
+    /// Reports the counts and timings of every later
+    /// [`set_query`](QuerySession::set_query) to `sink`.
+    pub fn set_metrics(&mut self, sink: Option<MetricsSink>) {
+        self.metrics = sink;
+    }
+
+    /// Replaces the query, extending the cached rows when `query` starts with
+    /// the current one and is compared the same way.
+    pub fn set_query(&mut self, query: &[u8]) {
+        let matching = Instant::now();
+        let folding = self.folding(query);
+        let mut ranked: Vec<Match> = {
+            let _span = span!("score");
+            let start = if query.starts_with(&self.query) && folding == self.folding(&self.query) {
+                self.query.len()
+            } else {
+                self.candidates.iter_mut().for_each(Candidate::reset);
+                0
+            };
+            self.query = query.to_vec();
+            for candidate in &mut self.candidates {
+                for pi in start..query.len() {
+                    candidate.push(query, pi, folding, &mut self.scratch);
+                }
+            }
+            (self.candidates.iter().enumerate())
+                .filter_map(|(id, c)| rank_one(id, c, query))
+                .collect()
+        };
+        let _span = span!("sort");
+        let sorting = Instant::now();
+        ranked.sort_by(Match::rank_cmp);
+        if let Some(sink) = &self.metrics {
+            sink(&Metrics {
+                scanned: self.candidates.len(),
+                unmatched: self.candidates.len() - ranked.len(),
+                scored: ranked.len(),
+                match_time: sorting - matching,
+                sort_time: sorting.elapsed(),
+                ..Metrics::default()
+            });
+        }
+        self.ranked.matches = ranked;
+    }
+
+    pub fn matches(&self) -> &[Match] {
+        self.ranked.as_slice()
+    }
+
+    pub fn results(&self) -> &RankedResults {
+        &self.ranked
+    }
+
+    /// Number of candidates, matching or not.
+    pub fn total(&self) -> usize {
+        self.candidates.len()
+    }
+}
+
+fn rank_one(index: usize, candidate: &Candidate, query: &[u8]) -> Option<Match> {
+    candidate.next?;
+    let score = if query.is_empty() {
+        SCORE_MIN
+    } else {
+        candidate.score
+    };
+    Some(Match { index, score })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Algorithm, Cased, RankedSet};
+
+    const CANDIDATES: &[&str] = &[
+        "app/models/order",
+        "app/models/zrder",
+        "app/m/foo",
+        "Gemfile",
+        "Gemfile.lock",
+        "src/main.rs",
+        "amor",
+        "AMOR",
+    ];
+
+    #[test]
+    fn agrees_with_full_rescore() {
+        let mut session = QuerySession::new();
+        let mut set = RankedSet::new(b"");
+        for c in CANDIDATES {
+            session.push(c.as_bytes());
+            set.insert(c.as_bytes());
+        }
+        for query in [
+            "a", "am", "amo", "amor", "amo", "", "gemf", "gemfil", "ms", "AM",
+        ] {
+            session.set_query(query.as_bytes());
+            set.set_query(query.as_bytes());
+            assert_eq!(session.matches(), set.matches(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn agrees_with_cased() {
+        for case in [Case::Smart, Case::Ignore, Case::Respect] {
+            let mut session = QuerySession::with_case(case);
+            let mut set = RankedSet::with_algorithm(
+                b"",
+                Cased {
+                    algorithm: Algorithm::Fzy,
+                    case,
+                },
+            );
+            for c in CANDIDATES {
+                session.push(c.as_bytes());
+                set.insert(c.as_bytes());
+            }
+            for query in [
+                "a", "am", "amo", "AM", "AMO", "Am", "ge", "gemfile", "Gemfile", "g",
+            ] {
+                session.set_query(query.as_bytes());
+                set.set_query(query.as_bytes());
+                assert_eq!(session.matches(), set.matches(), "{:?} {}", case, query);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_metrics() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        let scored = Arc::new(AtomicUsize::new(0));
+        let sink = Arc::clone(&scored);
+        let mut session = QuerySession::new();
+        for c in CANDIDATES {
+            session.push(c.as_bytes());
+        }
+        session.set_metrics(Some(Arc::new(move |m: &Metrics| {
+            assert_eq!(m.scanned, CANDIDATES.len());
+            assert_eq!(m.unmatched + m.scored, m.scanned);
+            sink.store(m.scored, Ordering::SeqCst);
+        })));
+        session.set_query(b"Gemf");
+        assert_eq!(scored.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn push_after_query() {
+        let mut session = QuerySession::new();
+        session.set_query(b"amo");
+        session.push(b"app/m/foo");
+        session.push(b"app/models/foo");
+        session.push(b"bar");
+        let ids: Vec<usize> = session.matches().iter().map(|m| m.index).collect();
+        assert_eq!(ids, vec![1, 0]);
+        assert_eq!(session.extend(&[&b"amo"[..], b"x"]), 3..5);
+        let ids: Vec<usize> = session.matches().iter().map(|m| m.index).collect();
+        assert_eq!(ids, vec![3, 1, 0]);
+        assert_eq!(session.total(), 5);
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::RankedResults;
+
+/// One published ranking, immutable once published.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    generation: u64,
+    query: Vec<u8>,
+    results: RankedResults,
+}
+
+impl Snapshot {
+    /// Starts at `0` for the empty snapshot and grows with each publish.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn query(&self) -> &[u8] {
+        &self.query
+    }
+
+    pub fn results(&self) -> &RankedResults {
+        &self.results
+    }
+}
+
+/// Rankings shared between a thread producing them and threads showing them.
+///
+/// The producer swaps in a whole new [`Snapshot`]; readers take an `Arc` to
+/// the current one and read it without holding any lock. The generation can
+/// be polled without locking to tell whether there is anything new.
+#[derive(Debug, Default)]
+pub struct SharedResults {
+    generation: AtomicU64,
+    current: RwLock<Arc<Snapshot>>,
+}
+
+impl SharedResults {
+    pub fn new() -> SharedResults {
+        SharedResults::default()
+    }
+
+    /// Replaces the current ranking and returns its generation.
+    pub fn publish(&self, query: &[u8], results: RankedResults) -> u64 {
+        let mut current = self.current.write().unwrap();
+        let generation = current.generation + 1;
+        *current = Arc::new(Snapshot {
+            generation,
+            query: query.to_vec(),
+            results,
+        });
+        self.generation.store(generation, Ordering::Release);
+        generation
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn load(&self) -> Arc<Snapshot> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// The current ranking if it is newer than generation `seen`.
+    pub fn load_newer(&self, seen: u64) -> Option<Arc<Snapshot>> {
+        if self.generation() <= seen {
+            return None;
+        }
+        Some(self.load())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RankedSet;
+    use std::thread;
+
+    #[test]
+    fn publishes_snapshots() {
+        let shared = SharedResults::new();
+        assert_eq!(shared.load().generation(), 0);
+        assert!(shared.load_newer(0).is_none());
+
+        let mut set = RankedSet::new(b"");
+        set.insert(b"foo");
+        set.insert(b"bar");
+        let old = shared.load();
+        thread::scope(|s| {
+            s.spawn(|| {
+                set.set_query(b"b");
+                shared.publish(b"b", set.results().clone());
+            });
+        });
+
+        let snapshot = shared.load_newer(0).unwrap();
+        assert_eq!(snapshot.generation(), 1);
+        assert_eq!(snapshot.query(), b"b");
+        assert_eq!(snapshot.results().len(), 1);
+        // Readers holding an older snapshot keep seeing it unchanged.
+        assert!(old.results().is_empty());
+        assert!(shared.load_newer(1).is_none());
+    }
+}
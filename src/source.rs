@@ -0,0 +1,412 @@
+//! Where candidates come from. The command line combines any number of these
+//! into one candidate list.
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::filter::read_candidates_while;
+use crate::{read_candidates, FilterOptions};
+
+pub trait Source: Send {
+    /// Calls `f` with each candidate, in order, until the source runs out.
+    fn read(&mut self, opts: &FilterOptions, f: &mut dyn FnMut(&[u8])) -> io::Result<()>;
+
+    /// [`read`](Source::read), stopping once `f` returns `false`. Returns
+    /// whether the source was read to the end. Unless a source can stop
+    /// early, the rest of it is still read, just not passed to `f`.
+    fn read_while(
+        &mut self,
+        opts: &FilterOptions,
+        f: &mut dyn FnMut(&[u8]) -> bool,
+    ) -> io::Result<bool> {
+        let mut more = true;
+        self.read(opts, &mut |line| more = more && f(line))?;
+        Ok(more)
+    }
+}
+
+/// Delimited candidates on standard input.
+#[derive(Debug, Default)]
+pub struct Stdin;
+
+impl Source for Stdin {
+    fn read(&mut self, opts: &FilterOptions, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+        let stdin = io::stdin();
+        read_candidates(stdin.lock(), opts, f)
+    }
+}
+
+/// Delimited candidates read from a file.
+#[derive(Debug)]
+pub struct FileSource(pub PathBuf);
+
+impl Source for FileSource {
+    fn read(&mut self, opts: &FilterOptions, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+        read_candidates(BufReader::new(File::open(&self.0)?), opts, f)
+    }
+}
+
+/// The paths of the files below a directory, skipping hidden entries and not
+/// following symlinks. Paths are relative to the working directory when the
+/// root is `.`, and start with the root otherwise.
+#[derive(Debug)]
+pub struct Walk(pub PathBuf);
+
+impl Source for Walk {
+    #[cfg(not(feature = "walk"))]
+    fn read(&mut self, _: &FilterOptions, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+        let strip = self.0 == Path::new(".");
+        walk_dir(&self.0, strip, f)
+    }
+
+    /// With the `walk` feature, [`walk`] with the default options, which
+    /// also skips what `.gitignore` files ignore.
+    #[cfg(feature = "walk")]
+    fn read(&mut self, _: &FilterOptions, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+        // Unreadable entries are skipped rather than ending the walk.
+        for entry in walk(&self.0, &WalkOptions::default()).flatten() {
+            f(entry.text());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "walk"))]
+fn walk_dir(dir: &Path, strip: bool, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().as_bytes().starts_with(b".") {
+            continue;
+        }
+        let path = if strip {
+            PathBuf::from(entry.file_name())
+        } else {
+            entry.path()
+        };
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            // Unreadable directories are skipped rather than ending the walk.
+            let _ = walk_dir(&path, false, f);
+        } else {
+            f(path.as_os_str().as_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Which files [`walk`] yields.
+#[cfg(feature = "walk")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkOptions {
+    /// Include hidden files and directories, those starting with a `.`.
+    pub hidden: bool,
+    /// Follow symlinks to directories and yield the files below them.
+    pub follow_links: bool,
+    /// Skip what `.gitignore`, `.ignore` and git's exclude files ignore,
+    /// inside a git repository or not.
+    pub gitignore: bool,
+    /// Don't descend more than this many directories below the root.
+    pub max_depth: Option<usize>,
+    /// Fill in [`WalkEntry::modified`], at the cost of a `stat` per file.
+    pub modified: bool,
+}
+
+#[cfg(feature = "walk")]
+impl Default for WalkOptions {
+    fn default() -> WalkOptions {
+        WalkOptions {
+            hidden: false,
+            follow_links: false,
+            gitignore: true,
+            max_depth: None,
+            modified: false,
+        }
+    }
+}
+
+/// A file found by [`walk`].
+#[cfg(feature = "walk")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    /// When the file was last modified, with [`WalkOptions::modified`].
+    pub modified: Option<std::time::SystemTime>,
+}
+
+#[cfg(feature = "walk")]
+impl WalkEntry {
+    /// The path as a candidate.
+    pub fn text(&self) -> &[u8] {
+        self.path.as_os_str().as_bytes()
+    }
+}
+
+/// The files below `root`, everything but directories, as the `--walk`
+/// option of the command line finds them. Paths are relative to the working
+/// directory when the root is `.`, and start with the root otherwise.
+#[cfg(feature = "walk")]
+pub fn walk(root: &Path, opts: &WalkOptions) -> impl Iterator<Item = io::Result<WalkEntry>> {
+    let strip = root == Path::new(".");
+    let modified = opts.modified;
+    (walk_builder(root, opts).build()).filter_map(move |entry| walk_entry(entry, strip, modified))
+}
+
+/// [`walk`] on several threads, calling `f` from them with each file as it
+/// is found, in no particular order.
+#[cfg(feature = "walk")]
+pub fn walk_parallel<F>(root: &Path, opts: &WalkOptions, f: F)
+where
+    F: Fn(io::Result<WalkEntry>) + Sync,
+{
+    let strip = root == Path::new(".");
+    let modified = opts.modified;
+    walk_builder(root, opts).build_parallel().run(|| {
+        Box::new(|entry| {
+            if let Some(entry) = walk_entry(entry, strip, modified) {
+                f(entry);
+            }
+            ignore::WalkState::Continue
+        })
+    });
+}
+
+#[cfg(feature = "walk")]
+fn walk_entry(
+    entry: Result<ignore::DirEntry, ignore::Error>,
+    strip: bool,
+    modified: bool,
+) -> Option<io::Result<WalkEntry>> {
+    let entry = match entry {
+        Ok(entry) => entry,
+        Err(err) => return Some(Err(io::Error::other(err))),
+    };
+    if entry.file_type().is_none_or(|t| t.is_dir()) {
+        return None;
+    }
+    let modified = if modified {
+        entry.metadata().ok().and_then(|m| m.modified().ok())
+    } else {
+        None
+    };
+    let path = entry.into_path();
+    let path = match path.strip_prefix(".") {
+        Ok(relative) if strip => relative.to_path_buf(),
+        _ => path,
+    };
+    Some(Ok(WalkEntry { path, modified }))
+}
+
+#[cfg(feature = "walk")]
+fn walk_builder(root: &Path, opts: &WalkOptions) -> ignore::WalkBuilder {
+    let mut builder = ignore::WalkBuilder::new(root);
+    builder
+        .hidden(!opts.hidden)
+        .follow_links(opts.follow_links)
+        .git_ignore(opts.gitignore)
+        .git_exclude(opts.gitignore)
+        .git_global(opts.gitignore)
+        .ignore(opts.gitignore)
+        .parents(opts.gitignore)
+        .require_git(false)
+        .max_depth(opts.max_depth);
+    builder
+}
+
+/// The tags of a ctags `tags` file, one line each with the headers left
+/// out. Match them with [`tag_name`](crate::tags::tag_name) as the match
+/// transform to find tags by name.
+#[derive(Debug)]
+pub struct TagSource(pub PathBuf);
+
+impl Source for TagSource {
+    fn read(&mut self, opts: &FilterOptions, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+        let file = BufReader::new(File::open(&self.0)?);
+        read_candidates(file, opts, |line| {
+            if !line.starts_with(b"!_TAG_") {
+                f(line)
+            }
+        })
+    }
+}
+
+/// The delimited output of a shell command.
+#[derive(Debug)]
+pub struct CommandSource(pub String);
+
+impl Source for CommandSource {
+    fn read(&mut self, opts: &FilterOptions, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+        let read = self.read_while(opts, &mut |line| {
+            f(line);
+            true
+        });
+        read.map(|_| ())
+    }
+
+    /// Kills the command when `f` stops reading.
+    fn read_while(
+        &mut self,
+        opts: &FilterOptions,
+        f: &mut dyn FnMut(&[u8]) -> bool,
+    ) -> io::Result<bool> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.0)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().unwrap();
+        let result = read_candidates_while(BufReader::new(stdout), opts, f);
+        if let Ok(false) = result {
+            // It may have exited on its own already.
+            let _ = child.kill();
+        }
+        child.wait()?;
+        result
+    }
+}
+
+/// Several sources read one after the other.
+impl Source for Vec<Box<dyn Source>> {
+    fn read(&mut self, opts: &FilterOptions, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+        for source in self {
+            source.read(opts, f)?;
+        }
+        Ok(())
+    }
+
+    fn read_while(
+        &mut self,
+        opts: &FilterOptions,
+        f: &mut dyn FnMut(&[u8]) -> bool,
+    ) -> io::Result<bool> {
+        for source in self {
+            if !source.read_while(opts, f)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    fn collect(source: &mut dyn Source) -> Vec<String> {
+        let mut lines = Vec::new();
+        source
+            .read(&FilterOptions::default(), &mut |line| {
+                lines.push(String::from_utf8(line.to_vec()).unwrap())
+            })
+            .unwrap();
+        lines
+    }
+
+    #[test]
+    fn mixes_sources() {
+        let dir = env::temp_dir().join(format!("fzy-rs-source-{}", process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join("sub/a.rs"), "").unwrap();
+        fs::write(dir.join(".git/config"), "").unwrap();
+        fs::write(dir.join("bookmarks"), "one\r\ntwo\n").unwrap();
+        fs::write(dir.join("tags"), "!_TAG_FILE_SORTED\t1\nmain\ta.rs\t1\n").unwrap();
+
+        let mut walked = collect(&mut Walk(dir.clone()));
+        walked.sort();
+        let root = dir.to_str().unwrap();
+        assert_eq!(
+            walked,
+            vec![
+                format!("{}/bookmarks", root),
+                format!("{}/sub/a.rs", root),
+                format!("{}/tags", root)
+            ]
+        );
+
+        let mut sources: Vec<Box<dyn Source>> = vec![
+            Box::new(FileSource(dir.join("bookmarks"))),
+            Box::new(CommandSource("printf 'x\\ny'".to_string())),
+            Box::new(TagSource(dir.join("tags"))),
+        ];
+        assert_eq!(
+            collect(&mut sources),
+            vec!["one", "two", "x", "y", "main\ta.rs\t1"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stops_reading_early() {
+        let mut sources: Vec<Box<dyn Source>> = vec![
+            Box::new(CommandSource("yes".to_string())),
+            Box::new(CommandSource("echo never".to_string())),
+        ];
+        let mut lines = 0;
+        let read = sources.read_while(&FilterOptions::default(), &mut |line| {
+            assert_eq!(line, b"y");
+            lines += 1;
+            lines < 3
+        });
+        assert!(!read.unwrap());
+        assert_eq!(lines, 3);
+    }
+
+    #[cfg(feature = "walk")]
+    #[test]
+    fn walks_with_options() {
+        let dir = env::temp_dir().join(format!("fzy-rs-walk-{}", process::id()));
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::create_dir_all(dir.join("src/deep")).unwrap();
+        fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        fs::write(dir.join("target/out"), "").unwrap();
+        fs::write(dir.join("src/deep/a.rs"), "").unwrap();
+
+        let paths = |opts: &WalkOptions| -> Vec<PathBuf> {
+            let mut paths: Vec<PathBuf> = walk(&dir, opts)
+                .map(|entry| {
+                    entry
+                        .unwrap()
+                        .path
+                        .strip_prefix(&dir)
+                        .unwrap()
+                        .to_path_buf()
+                })
+                .collect();
+            paths.sort();
+            paths
+        };
+        assert_eq!(
+            paths(&WalkOptions::default()),
+            vec![PathBuf::from("src/deep/a.rs")]
+        );
+        let all = WalkOptions {
+            hidden: true,
+            gitignore: false,
+            ..WalkOptions::default()
+        };
+        assert_eq!(paths(&all).len(), 3);
+        let shallow = WalkOptions {
+            max_depth: Some(2),
+            ..all
+        };
+        assert_eq!(paths(&shallow).len(), 2);
+
+        let opts = WalkOptions {
+            modified: true,
+            ..WalkOptions::default()
+        };
+        assert!(walk(&dir, &opts).all(|entry| entry.unwrap().modified.is_some()));
+
+        let found = std::sync::Mutex::new(Vec::new());
+        walk_parallel(&dir, &all, |entry| {
+            found.lock().unwrap().push(entry.unwrap().path)
+        });
+        assert_eq!(found.into_inner().unwrap().len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+//! Forrest Smith's `fts_fuzzy_match` (v0.2.0), the reverse-engineered Sublime
+//! Text matcher: a bounded recursive search over alternative alignments.
+
+use crate::Score;
+
+const SEQUENTIAL_BONUS: i32 = 15;
+const SEPARATOR_BONUS: i32 = 30;
+const CAMEL_BONUS: i32 = 30;
+const FIRST_LETTER_BONUS: i32 = 15;
+const LEADING_LETTER_PENALTY: i32 = -5;
+const MAX_LEADING_LETTER_PENALTY: i32 = -15;
+const UNMATCHED_LETTER_PENALTY: i32 = -1;
+
+const RECURSION_LIMIT: usize = 10;
+const MAX_MATCHES: usize = 256;
+
+fn score_matches(text: &[u8], matches: &[usize]) -> i32 {
+    let mut score = 100;
+    score += (LEADING_LETTER_PENALTY * matches[0] as i32).max(MAX_LEADING_LETTER_PENALTY);
+    score += UNMATCHED_LETTER_PENALTY * (text.len() - matches.len()) as i32;
+    for (i, &cur) in matches.iter().enumerate() {
+        if i > 0 && cur == matches[i - 1] + 1 {
+            score += SEQUENTIAL_BONUS;
+        }
+        if cur == 0 {
+            score += FIRST_LETTER_BONUS;
+            continue;
+        }
+        let (neighbor, c) = (text[cur - 1], text[cur]);
+        if neighbor.is_ascii_lowercase() && c.is_ascii_uppercase() {
+            score += CAMEL_BONUS;
+        }
+        if neighbor == b'_' || neighbor == b' ' {
+            score += SEPARATOR_BONUS;
+        }
+    }
+    score
+}
+
+/// Matches `pat[pi..]` against `text[ti..]`, `matches` holding the positions
+/// already matched. Returns the best score and its positions.
+fn match_recursive(
+    pat: &[u8],
+    mut pi: usize,
+    text: &[u8],
+    mut ti: usize,
+    mut matches: Vec<usize>,
+    recursions: &mut usize,
+) -> Option<(i32, Vec<usize>)> {
+    *recursions += 1;
+    if *recursions >= RECURSION_LIMIT || pi == pat.len() || ti == text.len() {
+        return None;
+    }
+
+    let mut best_recursive: Option<(i32, Vec<usize>)> = None;
+    while pi < pat.len() && ti < text.len() {
+        if pat[pi].eq_ignore_ascii_case(&text[ti]) {
+            if matches.len() >= MAX_MATCHES {
+                return None;
+            }
+            // Try skipping this occurrence in favour of a later one.
+            let recursive = match_recursive(pat, pi, text, ti + 1, matches.clone(), recursions);
+            if let Some((score, positions)) = recursive {
+                if best_recursive
+                    .as_ref()
+                    .is_none_or(|(best, _)| score > *best)
+                {
+                    best_recursive = Some((score, positions));
+                }
+            }
+            matches.push(ti);
+            pi += 1;
+        }
+        ti += 1;
+    }
+
+    let matched = pi == pat.len();
+    let score = if matched {
+        score_matches(text, &matches)
+    } else {
+        0
+    };
+    match best_recursive {
+        Some((best, positions)) if !matched || best > score => Some((best, positions)),
+        _ if matched => Some((score, matches)),
+        _ => None,
+    }
+}
+
+/// Case-insensitively matches `pat` against `text`, returning the score and
+/// matched positions.
+pub(crate) fn match_sublime(pat: &[u8], text: &[u8]) -> Option<(Score, Vec<usize>)> {
+    let mut recursions = 0;
+    match_recursive(pat, 0, text, 0, Vec::new(), &mut recursions)
+        .map(|(score, positions)| (Score::from(score), positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_like_fts_fuzzy_match() {
+        assert_eq!(match_sublime(b"ab", b"ab"), Some((130.0, vec![0, 1])));
+        assert_eq!(match_sublime(b"ab", b"xab"), Some((109.0, vec![1, 2])));
+        assert_eq!(match_sublime(b"ba", b"ab"), None);
+        assert_eq!(match_sublime(b"", b"ab"), None);
+    }
+
+    #[test]
+    fn finds_better_alignment_recursively() {
+        // The greedy alignment takes the 'b' in "abc", recursion finds the
+        // camel case hump.
+        assert_eq!(match_sublime(b"ab", b"abcaBar"), Some((140.0, vec![0, 4])));
+        assert_eq!(match_sublime(b"fb", b"fxb_bar"), Some((140.0, vec![0, 4])));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{FuzzyAlgorithm, SCORE_MIN};
+
+/// A query close to one that matched nothing, for "did you mean" prompts.
+///
+/// Tries swapping two adjacent bytes, then dropping one, then ever shorter
+/// prefixes, returning the first kind of fix that matches anything. Among
+/// fixes of the same kind the one matching the most candidates wins, then the
+/// one with the best scoring match. `None` when `query` already matches or
+/// nothing short of the empty query does.
+pub fn did_you_mean<A, Q, T>(algorithm: &A, query: Q, candidates: &[T]) -> Option<Vec<u8>>
+where
+    A: FuzzyAlgorithm + ?Sized,
+    Q: AsRef<[u8]>,
+    T: AsRef<[u8]>,
+{
+    let query = query.as_ref();
+    let matches = |q: &[u8]| (candidates.iter()).any(|c| algorithm.has_match(q, c.as_ref()));
+    let rate = |q: &[u8]| {
+        (candidates.iter())
+            .filter(|c| algorithm.has_match(q, c.as_ref()))
+            .fold((0, SCORE_MIN), |(n, best), c| {
+                (n + 1, best.max(algorithm.score(q, c.as_ref())))
+            })
+    };
+    if query.is_empty() || matches(query) {
+        return None;
+    }
+
+    let swaps = (1..query.len()).filter_map(|i| {
+        let mut q = query.to_vec();
+        q.swap(i - 1, i);
+        (q != query).then_some(q)
+    });
+    let deletions = (0..query.len()).map(|i| [&query[..i], &query[i + 1..]].concat());
+    for variants in [swaps.collect::<Vec<_>>(), deletions.collect()] {
+        let best = (variants.into_iter())
+            .filter(|q| !q.is_empty())
+            .map(|q| (rate(&q), q))
+            .filter(|((n, _), _)| *n > 0)
+            .reduce(|best, next| if next.0 > best.0 { next } else { best });
+        if let Some((_, q)) = best {
+            return Some(q);
+        }
+    }
+
+    (1..query.len())
+        .rev()
+        .map(|len| &query[..len])
+        .find(|q| matches(q))
+        .map(<[u8]>::to_vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    #[test]
+    fn suggests_fixes() {
+        let candidates = ["src/score.rs", "src/main.rs", "README.md"];
+        let suggest = |q: &str| {
+            did_you_mean(&Algorithm::Fzy, q.as_bytes(), &candidates)
+                .map(|q| String::from_utf8(q).unwrap())
+        };
+        assert_eq!(suggest("scroe").as_deref(), Some("score"));
+        assert_eq!(suggest("mainx").as_deref(), Some("main"));
+        assert_eq!(suggest("mainxyz").as_deref(), Some("main"));
+        assert_eq!(suggest("main"), None);
+        assert_eq!(suggest("qqq"), None);
+    }
+}
@@ -0,0 +1,137 @@
+//! ctags `tags` files, for jumping to a tag picked by its name.
+
+use std::io::{self, BufRead};
+
+use crate::algorithm::rank;
+use crate::{FuzzyAlgorithm, Match};
+
+/// One line of a tags file: `name \t file \t address;" \t fields...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: Vec<u8>,
+    pub file: Vec<u8>,
+    /// An ex command finding the tag, a line number or a search pattern
+    /// such as `/^fn main() {$/`.
+    pub address: Vec<u8>,
+    /// The `kind` extension field, such as `f` for functions.
+    pub kind: Option<Vec<u8>>,
+    /// The line of the tag, from a numeric address or the `line` field.
+    pub line: Option<usize>,
+}
+
+impl Tag {
+    /// Parses a line of a tags file, `None` for `!_TAG_` headers and
+    /// malformed lines.
+    pub fn parse(line: &[u8]) -> Option<Tag> {
+        if line.starts_with(b"!_TAG_") {
+            return None;
+        }
+        let mut fields = line.splitn(3, |b| *b == b'\t');
+        let name = fields.next().filter(|name| !name.is_empty())?;
+        let file = fields.next()?;
+        let rest = fields.next()?;
+        // A search pattern may contain tabs, so the address runs up to the
+        // last `;"` that ends a field.
+        let end = (0..rest.len().saturating_sub(1))
+            .rev()
+            .find(|&i| &rest[i..i + 2] == b";\"" && matches!(rest.get(i + 2), None | Some(b'\t')));
+        let (address, extensions) = match end {
+            Some(end) => (&rest[..end], rest.get(end + 3..).unwrap_or_default()),
+            None => (rest, &[][..]),
+        };
+        let mut tag = Tag {
+            name: name.to_vec(),
+            file: file.to_vec(),
+            address: address.to_vec(),
+            kind: None,
+            line: parse_number(address),
+        };
+        for field in extensions.split(|b| *b == b'\t').filter(|f| !f.is_empty()) {
+            match field.iter().position(|b| *b == b':') {
+                Some(colon) if &field[..colon] == b"kind" => {
+                    tag.kind = Some(field[colon + 1..].to_vec())
+                }
+                Some(colon) if &field[..colon] == b"line" => {
+                    tag.line = parse_number(&field[colon + 1..]).or(tag.line)
+                }
+                Some(_) => {}
+                // A field without a name is the kind.
+                None => tag.kind = Some(field.to_vec()),
+            }
+        }
+        Some(tag)
+    }
+}
+
+fn parse_number(text: &[u8]) -> Option<usize> {
+    std::str::from_utf8(text).ok()?.parse().ok()
+}
+
+/// The tags of a tags file, skipping headers and malformed lines.
+pub fn read_tags<R: BufRead>(reader: R) -> io::Result<Vec<Tag>> {
+    let mut tags = Vec::new();
+    for line in reader.split(b'\n') {
+        let mut line = line?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        tags.extend(Tag::parse(&line));
+    }
+    Ok(tags)
+}
+
+/// The tag name of a tags file line, what it is matched on, e.g. as a
+/// [`Step::Map`](crate::Step::Map) of the lines of a tags file.
+pub fn tag_name(line: &[u8]) -> Vec<u8> {
+    line.split(|b| *b == b'\t')
+        .next()
+        .unwrap_or_default()
+        .to_vec()
+}
+
+/// Matches `query` against the names of `tags`, best first, `index`
+/// pointing into `tags`.
+pub fn rank_tags<A: FuzzyAlgorithm>(algorithm: &A, query: &[u8], tags: &[Tag]) -> Vec<Match> {
+    let names: Vec<&[u8]> = tags.iter().map(|tag| &tag.name[..]).collect();
+    rank(algorithm, query, &names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    const TAGS: &[u8] = b"!_TAG_FILE_FORMAT\t2\t/extended format/\n\
+        main\tsrc/main.rs\t/^fn main() {$/;\"\tf\tline:410\n\
+        Matcher\tsrc/matcher.rs\t12;\"\tkind:s\n\
+        a\tb\t/^x\ty$/;\"\n\
+        broken\n";
+
+    #[test]
+    fn parses_tags() {
+        let tags = read_tags(TAGS).unwrap();
+        assert_eq!(tags.len(), 3);
+        assert_eq!(
+            tags[0],
+            Tag {
+                name: b"main".to_vec(),
+                file: b"src/main.rs".to_vec(),
+                address: b"/^fn main() {$/".to_vec(),
+                kind: Some(b"f".to_vec()),
+                line: Some(410),
+            }
+        );
+        assert_eq!(tags[1].line, Some(12));
+        assert_eq!(tags[1].kind, Some(b"s".to_vec()));
+        assert_eq!(tags[2].address, b"/^x\ty$/".to_vec());
+        assert_eq!(tag_name(b"main\tsrc/main.rs\t1"), b"main".to_vec());
+    }
+
+    #[test]
+    fn ranks_by_name() {
+        let tags = read_tags(TAGS).unwrap();
+        let matches = rank_tags(&Algorithm::Fzy, b"ma", &tags);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(tags[matches[0].index].file, b"src/main.rs".to_vec());
+    }
+}
@@ -0,0 +1,28 @@
+//! Spans and events for the `tracing` feature, compiled away without it.
+
+/// Enters a `tracing` span at trace level until the returned guard drops.
+macro_rules! span {
+    ($name:expr) => {{
+        #[cfg(feature = "tracing")]
+        let guard = tracing::trace_span!($name).entered();
+        #[cfg(not(feature = "tracing"))]
+        let guard = $crate::trace::NoSpan;
+        guard
+    }};
+}
+
+/// Emits a debug level `tracing` event with the given fields.
+macro_rules! event {
+    ($($field:ident = $value:expr),+ $(,)?; $message:expr) => {{
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($field = $value),+, $message);
+        #[cfg(not(feature = "tracing"))]
+        let _ = ($(&$value),+);
+    }};
+}
+
+/// Stands in for a span guard without the `tracing` feature.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
+
+pub(crate) use {event, span};
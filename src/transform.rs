@@ -0,0 +1,89 @@
+use std::borrow::Cow;
+
+use crate::strip_ansi;
+
+/// One step of a [`Transform`].
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Strip ASCII whitespace from both ends.
+    Trim,
+    /// Lowercase ASCII letters.
+    Lowercase,
+    /// Drop this prefix when the candidate starts with it.
+    StripPrefix(Vec<u8>),
+    /// Drop ANSI escape sequences, see [`strip_ansi`].
+    StripAnsi,
+    /// Any other rewrite.
+    Map(fn(&[u8]) -> Vec<u8>),
+}
+
+/// A chain of steps applied to candidates as they are read, such as
+/// [`FilterOptions::match_transform`](crate::FilterOptions::match_transform).
+#[derive(Debug, Clone, Default)]
+pub struct Transform {
+    steps: Vec<Step>,
+}
+
+impl Transform {
+    pub fn new() -> Transform {
+        Transform::default()
+    }
+
+    /// Adds a step run after the ones already added.
+    pub fn push(&mut self, step: Step) -> &mut Transform {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn apply<'a>(&self, text: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut text = Cow::Borrowed(text);
+        for step in &self.steps {
+            text = match step {
+                Step::Trim => match text {
+                    Cow::Borrowed(t) => Cow::Borrowed(t.trim_ascii()),
+                    Cow::Owned(t) => Cow::Owned(t.trim_ascii().to_vec()),
+                },
+                Step::Lowercase => Cow::Owned(text.to_ascii_lowercase()),
+                Step::StripPrefix(prefix) => match text {
+                    Cow::Borrowed(t) => Cow::Borrowed(t.strip_prefix(&prefix[..]).unwrap_or(t)),
+                    Cow::Owned(t) => match t.strip_prefix(&prefix[..]) {
+                        Some(rest) => Cow::Owned(rest.to_vec()),
+                        None => Cow::Owned(t),
+                    },
+                },
+                Step::StripAnsi => match text {
+                    Cow::Borrowed(t) => strip_ansi(t),
+                    Cow::Owned(t) => Cow::Owned(strip_ansi(&t).into_owned()),
+                },
+                Step::Map(f) => Cow::Owned(f(&text)),
+            };
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_steps_in_order() {
+        let mut transform = Transform::new();
+        transform
+            .push(Step::Trim)
+            .push(Step::StripPrefix(b"./".to_vec()))
+            .push(Step::Lowercase);
+        assert!(matches!(transform.apply(b"src/a"), Cow::Owned(_)));
+        assert_eq!(&transform.apply(b"  ./Src/Main.rs\t")[..], b"src/main.rs");
+        assert_eq!(&transform.apply(b"a/./B")[..], b"a/./b");
+
+        let mut transform = Transform::new();
+        transform.push(Step::Trim).push(Step::Map(|t| t.repeat(2)));
+        assert_eq!(&transform.apply(b" ab ")[..], b"abab");
+        assert!(matches!(Transform::new().apply(b"x"), Cow::Borrowed(b"x")));
+    }
+}
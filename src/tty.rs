@@ -0,0 +1,870 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use crate::picker::{Event, Outcome, Picker};
+use crate::width::{char_width, display_width};
+use crate::{sanitized_chars, FuzzyAlgorithm, Observer, Progress, Sanitize};
+
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+const HSCROLL: isize = 8;
+const SPINNER: [char; 4] = ['-', '\\', '|', '/'];
+/// Most candidates taken in before a redraw, so that keys are handled while a
+/// fast source streams.
+const FRAME_CANDIDATES: usize = 20_000;
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Enable mouse reporting for click-to-select and wheel scrolling.
+    pub mouse: bool,
+    /// Put the prompt at the top with results growing downwards.
+    pub reverse: bool,
+    /// How tabs and other control characters in candidates are shown.
+    pub sanitize: Sanitize,
+    /// Keys accepting like enter, reported by index in
+    /// [`Outcome::AcceptKey`].
+    pub expect: Vec<Input>,
+    /// Whether the picker shows candidates as they arrive.
+    pub start: Start,
+}
+
+/// How [`run`] takes in candidates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Start {
+    /// Show candidates as they arrive, handling keys meanwhile.
+    #[default]
+    Streaming,
+    /// Read every candidate before showing any or handling a key, including
+    /// after a reload, so the first ranking shown is final.
+    Sync,
+}
+
+/// A decoded chunk of terminal input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Char(char),
+    Ctrl(char),
+    Enter,
+    Backspace,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// An SGR mouse report: button code and 0-based screen column and row.
+    Mouse {
+        button: u16,
+        col: usize,
+        row: usize,
+        press: bool,
+    },
+}
+
+/// Parses a key name as used by `--expect`: `ctrl-a` to `ctrl-z`, `enter`,
+/// `esc`, `tab`, `backspace`, `up`, `down`, `left`, `right` or a single
+/// character.
+pub fn parse_key(name: &str) -> Option<Input> {
+    let key = match name {
+        "enter" => Input::Enter,
+        "esc" => Input::Esc,
+        "tab" => Input::Ctrl('i'),
+        "backspace" => Input::Backspace,
+        "up" => Input::Up,
+        "down" => Input::Down,
+        "left" => Input::Left,
+        "right" => Input::Right,
+        _ => {
+            let (ctrl, name) = match name.strip_prefix("ctrl-") {
+                Some(name) => (true, name),
+                None => (false, name),
+            };
+            let mut chars = name.chars();
+            let c = chars.next().filter(|_| chars.next().is_none())?;
+            match (ctrl, c) {
+                (true, 'a'..='z') => Input::Ctrl(c),
+                (true, _) => return None,
+                (false, _) => Input::Char(c),
+            }
+        }
+    };
+    Some(key)
+}
+
+/// Decodes raw terminal bytes, ignoring sequences it doesn't understand.
+pub fn parse_input(mut bytes: &[u8]) -> Vec<Input> {
+    let mut inputs = Vec::new();
+    while let Some(&b) = bytes.first() {
+        let (input, len) = match b {
+            b'\r' | b'\n' => (Some(Input::Enter), 1),
+            0x7f | 0x08 => (Some(Input::Backspace), 1),
+            0x1b => parse_escape(bytes),
+            0x01..=0x1a => (Some(Input::Ctrl((b'a' + b - 1) as char)), 1),
+            _ => {
+                let len = utf8_len(b).min(bytes.len());
+                let c = std::str::from_utf8(&bytes[..len])
+                    .ok()
+                    .and_then(|s| s.chars().next());
+                (c.map(Input::Char), len)
+            }
+        };
+        inputs.extend(input);
+        bytes = &bytes[len..];
+    }
+    inputs
+}
+
+fn utf8_len(b: u8) -> usize {
+    match b {
+        0xf0..=0xff => 4,
+        0xe0..=0xef => 3,
+        0xc0..=0xdf => 2,
+        _ => 1,
+    }
+}
+
+fn parse_escape(bytes: &[u8]) -> (Option<Input>, usize) {
+    match bytes.get(1) {
+        Some(b'[') | Some(b'O') => {}
+        _ => return (Some(Input::Esc), 1),
+    }
+    match bytes.get(2) {
+        Some(b'A') => (Some(Input::Up), 3),
+        Some(b'B') => (Some(Input::Down), 3),
+        Some(b'C') => (Some(Input::Right), 3),
+        Some(b'D') => (Some(Input::Left), 3),
+        Some(b'<') => {
+            let end = match bytes.iter().position(|b| *b == b'M' || *b == b'm') {
+                Some(end) => end,
+                None => return (None, bytes.len()),
+            };
+            let fields: Vec<usize> = std::str::from_utf8(&bytes[3..end])
+                .unwrap_or("")
+                .split(';')
+                .filter_map(|f| f.parse().ok())
+                .collect();
+            let input = match fields[..] {
+                [button, col, row] if col > 0 && row > 0 => Some(Input::Mouse {
+                    button: button as u16,
+                    col: col - 1,
+                    row: row - 1,
+                    press: bytes[end] == b'M',
+                }),
+                _ => None,
+            };
+            (input, end + 1)
+        }
+        _ => {
+            let end = bytes[2..]
+                .iter()
+                .position(|b| (0x40..=0x7e).contains(b))
+                .map_or(bytes.len(), |end| end + 3);
+            (None, end)
+        }
+    }
+}
+
+fn stty(tty: &File, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(tty.try_clone()?)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The controlling terminal in raw mode, restored when dropped.
+pub struct Terminal {
+    tty: File,
+    saved: String,
+    mouse: bool,
+}
+
+impl Terminal {
+    pub fn open(opts: &Options) -> io::Result<Terminal> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        let saved = stty(&tty, &["-g"])?;
+        // Reads time out after a tenth of a second so the spinner keeps moving.
+        stty(&tty, &["raw", "-echo", "min", "0", "time", "1"])?;
+        let mut term = Terminal {
+            tty,
+            saved,
+            mouse: opts.mouse,
+        };
+        term.tty.write_all(b"\x1b[?1049h")?;
+        if term.mouse {
+            term.tty.write_all(b"\x1b[?1000h\x1b[?1006h")?;
+        }
+        Ok(term)
+    }
+
+    /// Terminal size as `(rows, columns)`, asked of the terminal driver so
+    /// that it is cheap enough to check every frame.
+    pub fn size(&self) -> io::Result<(usize, usize)> {
+        let mut size = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // Safety: TIOCGWINSZ only writes a `winsize` through the pointer.
+        if unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        match (size.ws_row, size.ws_col) {
+            (0, _) | (_, 0) => Ok((24, 80)),
+            (rows, cols) => Ok((rows as usize, cols as usize)),
+        }
+    }
+
+    /// Whether input is waiting, so that [`read`](Terminal::read) returns
+    /// without waiting for it.
+    pub fn has_input(&self) -> io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // Safety: poll only writes the `revents` of the one `pollfd` it's given.
+        match unsafe { libc::poll(&mut fd, 1, 0) } {
+            -1 => Err(io::Error::last_os_error()),
+            n => Ok(n > 0),
+        }
+    }
+
+    pub fn read(&mut self) -> io::Result<Vec<Input>> {
+        let mut buf = [0; 1024];
+        let n = self.tty.read(&mut buf)?;
+        Ok(parse_input(&buf[..n]))
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.tty.write_all(bytes)?;
+        self.tty.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.mouse {
+            let _ = self.tty.write_all(b"\x1b[?1000l\x1b[?1006l");
+        }
+        let _ = self.tty.write_all(b"\x1b[?1049l");
+        let _ = stty(&self.tty, &[&self.saved]);
+    }
+}
+
+/// Chooses the range of characters to show in `width` columns of a candidate
+/// whose characters take `widths` columns each, reserving a column for an
+/// ellipsis on each side that gets cut. Without scrolling, the window is moved
+/// right just enough to show the matched characters at `positions`.
+fn fit(widths: &[usize], positions: &[usize], width: usize, scroll: isize) -> (usize, usize) {
+    let len = widths.len();
+    // `before[i]` is the number of columns taken by the characters before `i`.
+    let before: Vec<usize> = std::iter::once(0)
+        .chain(widths.iter().scan(0, |total, w| {
+            *total += w;
+            Some(*total)
+        }))
+        .collect();
+    let columns = |from: usize, to: usize| before[to] - before[from];
+    // The end of the longest run from `start` fitting in `avail` columns.
+    let take = |start: usize, avail: usize| {
+        (start..len)
+            .find(|&end| columns(start, end + 1) > avail)
+            .unwrap_or(len)
+    };
+    if before[len] <= width || width < 3 {
+        return (0, take(0, width));
+    }
+    let mut start = 0;
+    if let (Some(&first), Some(&last)) = (positions.first(), positions.last()) {
+        while start < first && columns(start, last + 1) + 2 > width {
+            start += 1;
+        }
+    }
+    let max_start = (0..len)
+        .find(|&start| columns(start, len) < width)
+        .unwrap_or(len);
+    let start = if scroll < 0 {
+        start.saturating_sub(scroll.unsigned_abs())
+    } else {
+        start.saturating_add(scroll as usize).min(max_start)
+    };
+    let avail = if start > 0 { width - 1 } else { width };
+    let end = if columns(start, len) <= avail {
+        len
+    } else {
+        take(start, avail - 1)
+    };
+    (start, end)
+}
+
+/// Writes `text` into `out` sanitized, highlighting the bytes at `positions`
+/// and truncating it to `width` terminal columns around them.
+fn draw_candidate(
+    out: &mut Vec<u8>,
+    text: &[u8],
+    positions: &[usize],
+    width: usize,
+    scroll: isize,
+    sanitize: &Sanitize,
+) {
+    let chars = sanitized_chars(text, sanitize);
+    let matched: Vec<usize> = chars
+        .iter()
+        .enumerate()
+        .filter(|(_, (offset, _))| positions.contains(offset))
+        .map(|(i, _)| i)
+        .collect();
+    let widths: Vec<usize> = chars.iter().map(|&(_, c)| char_width(c)).collect();
+    let (start, end) = fit(&widths, &matched, width, scroll);
+    if start > 0 {
+        out.extend_from_slice("\u{2026}".as_bytes());
+    }
+    for (offset, c) in &chars[start..end] {
+        let highlight = positions.contains(offset);
+        if highlight {
+            out.extend_from_slice(b"\x1b[33m");
+        }
+        out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        if highlight {
+            out.extend_from_slice(b"\x1b[39m");
+        }
+    }
+    if end < chars.len() {
+        out.extend_from_slice("\u{2026}".as_bytes());
+    }
+}
+
+/// Screen lines left for results after the status line and prompt.
+fn result_rows(rows: usize) -> usize {
+    rows.saturating_sub(2)
+}
+
+/// Renders the picker bottom-up: the prompt on the last line, the match
+/// counter above it and the best result just above that, or top-down with
+/// `reverse`. `spinner` is shown next to the counter while candidates are
+/// still arriving.
+pub fn draw<A: FuzzyAlgorithm>(
+    picker: &Picker<A>,
+    rows: usize,
+    cols: usize,
+    spinner: Option<char>,
+    opts: &Options,
+) -> Vec<u8> {
+    let mut results = vec![Vec::new(); result_rows(rows)];
+    for (line, (i, m)) in results.iter_mut().zip(picker.visible()) {
+        let text = picker.get(m.index).unwrap_or_default();
+        let positions = picker
+            .algorithm()
+            .positions(picker.query(), text)
+            .unwrap_or_default();
+        let mut scroll = 0;
+        if i == picker.cursor() {
+            line.extend_from_slice(b"\x1b[7m");
+            scroll = picker.hscroll();
+        }
+        if picker.is_marked(m.index) {
+            line.extend_from_slice(b"\x1b[1m");
+        }
+        draw_candidate(line, text, &positions, cols, scroll, &opts.sanitize);
+        line.extend_from_slice(b"\x1b[0m");
+    }
+
+    let mut status = format!("  {}/{}", picker.results().len(), picker.total());
+    if picker.is_multi() {
+        match picker.mark_limit() {
+            Some(limit) => {
+                status.push_str(&format!(" ({}/{} marked", picker.marked().len(), limit))
+            }
+            None => status.push_str(&format!(" ({} marked", picker.marked().len())),
+        }
+        if picker.at_mark_limit() {
+            status.push_str(", limit reached");
+        }
+        status.push(')');
+    }
+    if let Some(spinner) = spinner {
+        status.push(' ');
+        status.push(spinner);
+    }
+    let mut prompt = b"> ".to_vec();
+    prompt.extend_from_slice(picker.query());
+
+    let mut lines = Vec::with_capacity(rows);
+    if opts.reverse {
+        lines.push(prompt.clone());
+        lines.push(status.into_bytes());
+        lines.extend(results);
+    } else {
+        lines.extend(results.into_iter().rev());
+        lines.push(status.into_bytes());
+        lines.push(prompt.clone());
+    }
+
+    let mut out = b"\x1b[H".to_vec();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            out.extend_from_slice(b"\r\n");
+        }
+        out.extend_from_slice(b"\x1b[2K");
+        out.extend_from_slice(line);
+    }
+    if opts.reverse {
+        let col = display_width(&prompt) + 1;
+        out.extend_from_slice(format!("\x1b[1;{}H", col).as_bytes());
+    }
+    out
+}
+
+/// Maps a screen row to a row of the result window.
+fn result_row(row: usize, rows: usize, opts: &Options) -> Option<usize> {
+    let height = result_rows(rows);
+    let row = if opts.reverse {
+        row.checked_sub(2)?
+    } else {
+        (height.checked_sub(1)?).checked_sub(row)?
+    };
+    Some(row).filter(|row| *row < height)
+}
+
+fn event(
+    input: Input,
+    rows: usize,
+    opts: &Options,
+    last_click: &mut Option<(usize, Instant)>,
+) -> Option<Event> {
+    // Up and down follow the layout: towards worse matches is up when the
+    // results grow upwards.
+    let (up, down) = if opts.reverse {
+        (Event::Prev, Event::Next)
+    } else {
+        (Event::Next, Event::Prev)
+    };
+    let event = match input {
+        Input::Char(c) => Event::Insert(c),
+        Input::Backspace | Input::Ctrl('h') => Event::Backspace,
+        Input::Ctrl('w') => Event::DeleteWord,
+        Input::Ctrl('u') => Event::ClearQuery,
+        Input::Up | Input::Ctrl('p') | Input::Ctrl('k') => up,
+        Input::Down | Input::Ctrl('n') | Input::Ctrl('j') => down,
+        Input::Left => Event::ScrollHorizontal(-HSCROLL),
+        Input::Right => Event::ScrollHorizontal(HSCROLL),
+        Input::Ctrl('i') => Event::ToggleMark,
+        Input::Enter => Event::Accept,
+        Input::Esc | Input::Ctrl('c') | Input::Ctrl('g') => Event::Abort,
+        Input::Mouse { button: 64, .. } => Event::Scroll(if opts.reverse { -1 } else { 1 }),
+        Input::Mouse { button: 65, .. } => Event::Scroll(if opts.reverse { 1 } else { -1 }),
+        Input::Mouse {
+            button: 0,
+            row,
+            press: true,
+            ..
+        } => {
+            let row = result_row(row, rows, opts)?;
+            let now = Instant::now();
+            let double = matches!(*last_click, Some((r, t)) if r == row && now - t < DOUBLE_CLICK);
+            *last_click = Some((row, now));
+            if double {
+                Event::DoubleClick(row)
+            } else {
+                Event::Click(row)
+            }
+        }
+        _ => return None,
+    };
+    Some(event)
+}
+
+/// Ctrl-p and ctrl-n recall queries when there is a history, as do up and
+/// down while nothing matches.
+fn history_event<A: FuzzyAlgorithm>(input: Input, picker: &Picker<A>) -> Option<Event> {
+    if !picker.has_history() {
+        return None;
+    }
+    let empty = picker.matches().is_empty();
+    match input {
+        Input::Ctrl('p') => Some(Event::HistoryPrev),
+        Input::Ctrl('n') => Some(Event::HistoryNext),
+        Input::Up if empty => Some(Event::HistoryPrev),
+        Input::Down if empty => Some(Event::HistoryNext),
+        _ => None,
+    }
+}
+
+/// Runs `picker` on the controlling terminal until a candidate is accepted or
+/// the user aborts, adding candidates from `incoming` as they arrive.
+///
+/// With `reload`, ctrl-r drops every candidate, and the receiver they came
+/// from, and streams in new ones from the receiver it returns.
+pub fn run<A: FuzzyAlgorithm>(
+    picker: &mut Picker<A>,
+    opts: &Options,
+    mut incoming: Option<Receiver<Vec<u8>>>,
+    mut reload: Option<&mut dyn FnMut() -> Receiver<Vec<u8>>>,
+) -> io::Result<Outcome> {
+    let mut term = Terminal::open(opts)?;
+    let mut last_click = None;
+    let mut spinner = Spinner::default();
+    loop {
+        let backlog = receive(picker, &mut incoming, opts.start, &mut spinner);
+        let spinner = incoming.as_ref().map(|_| spinner.frame());
+
+        let (rows, cols) = term.size()?;
+        picker.set_height(result_rows(rows));
+        term.write(&draw(picker, rows, cols, spinner, opts))?;
+        // Waiting for keys would hold up the candidates left for next frame.
+        if backlog && !term.has_input()? {
+            continue;
+        }
+        for input in term.read()? {
+            if let Some(key) = opts.expect.iter().position(|key| *key == input) {
+                match picker.handle(Event::AcceptKey(key)) {
+                    Some(outcome) => return Ok(outcome),
+                    None => continue,
+                }
+            }
+            if input == Input::Ctrl('r') {
+                if let Some(reload) = &mut reload {
+                    picker.clear();
+                    // Dropping the old receiver lets its sender stop reading.
+                    incoming.take();
+                    incoming = Some(reload());
+                }
+                continue;
+            }
+            let event =
+                history_event(input, picker).or_else(|| event(input, rows, opts, &mut last_click));
+            if let Some(event) = event {
+                if let Some(outcome) = picker.handle(event) {
+                    return Ok(outcome);
+                }
+            }
+        }
+    }
+}
+
+/// The spinner next to the match counter, turning as the [`Progress`] of
+/// arriving candidates advances rather than with every frame.
+#[derive(Debug, Default)]
+struct Spinner {
+    progress: Progress,
+    shown: Option<usize>,
+    turns: usize,
+}
+
+impl Spinner {
+    fn frame(&mut self) -> char {
+        if self.shown != Some(self.progress.processed) {
+            self.shown = Some(self.progress.processed);
+            self.turns += 1;
+        }
+        SPINNER[self.turns % SPINNER.len()]
+    }
+}
+
+impl Observer for Spinner {
+    fn progress(&mut self, progress: &Progress) {
+        self.progress = *progress;
+    }
+}
+
+/// Pushes up to [`FRAME_CANDIDATES`] of the candidates that arrived, or with
+/// [`Start::Sync`] all of them, as one batch, telling `observer` about it and
+/// dropping `incoming` once it's exhausted. Returns whether more candidates
+/// may be waiting.
+fn receive<A: FuzzyAlgorithm>(
+    picker: &mut Picker<A>,
+    incoming: &mut Option<Receiver<Vec<u8>>>,
+    start: Start,
+    observer: &mut dyn Observer,
+) -> bool {
+    let mut batch = Vec::new();
+    if start == Start::Sync {
+        batch.extend(incoming.take().into_iter().flatten());
+    }
+    while let Some(rx) = incoming {
+        if batch.len() == FRAME_CANDIDATES {
+            break;
+        }
+        match rx.try_recv() {
+            Ok(text) => batch.push(text),
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => *incoming = None,
+        }
+    }
+    if !batch.is_empty() {
+        picker.extend(&batch);
+        observer.progress(&Progress {
+            processed: picker.total(),
+            total: None,
+            matched: picker.matches().len(),
+        });
+    }
+    incoming.is_some() && batch.len() == FRAME_CANDIDATES
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(
+            parse_input(b"a\xc3\xa9\x7f\r\x15\x1b[A\x1b"),
+            vec![
+                Input::Char('a'),
+                Input::Char('\u{e9}'),
+                Input::Backspace,
+                Input::Enter,
+                Input::Ctrl('u'),
+                Input::Up,
+                Input::Esc,
+            ]
+        );
+        assert_eq!(parse_input(b"\x1b[1;5Cx"), vec![Input::Char('x')]);
+    }
+
+    #[test]
+    fn recalls_history_with_keys() {
+        let mut picker = Picker::new();
+        picker.push(b"foo");
+        assert_eq!(history_event(Input::Ctrl('p'), &picker), None);
+        picker.set_history(vec![b"x".to_vec()]);
+        assert_eq!(
+            history_event(Input::Ctrl('p'), &picker),
+            Some(Event::HistoryPrev)
+        );
+        assert_eq!(history_event(Input::Up, &picker), None);
+        picker.set_query(b"z");
+        assert_eq!(
+            history_event(Input::Down, &picker),
+            Some(Event::HistoryNext)
+        );
+    }
+
+    #[test]
+    fn parses_key_names() {
+        assert_eq!(parse_key("ctrl-o"), Some(Input::Ctrl('o')));
+        assert_eq!(parse_key("tab"), Some(Input::Ctrl('i')));
+        assert_eq!(parse_key("é"), Some(Input::Char('é')));
+        assert_eq!(parse_key("ctrl-1"), None);
+        assert_eq!(parse_key("f13"), None);
+        assert_eq!(parse_key(""), None);
+    }
+
+    #[test]
+    fn receives_candidates() {
+        let mut picker = Picker::new();
+        let (tx, rx) = mpsc::channel();
+        let mut incoming = Some(rx);
+        tx.send(b"a".to_vec()).unwrap();
+        let mut spinner = Spinner::default();
+        let first = spinner.frame();
+        receive(&mut picker, &mut incoming, Start::Streaming, &mut spinner);
+        assert_eq!(picker.total(), 1);
+        assert!(incoming.is_some());
+        assert_eq!(spinner.progress.matched, 1);
+        let turned = spinner.frame();
+        assert_ne!(turned, first);
+        // Without new candidates the spinner stays put.
+        assert!(!receive(
+            &mut picker,
+            &mut incoming,
+            Start::Streaming,
+            &mut spinner
+        ));
+        assert_eq!(spinner.frame(), turned);
+
+        // A fast source is taken in a frame's worth at a time.
+        for _ in 0..=FRAME_CANDIDATES {
+            tx.send(b"c".to_vec()).unwrap();
+        }
+        assert!(receive(
+            &mut picker,
+            &mut incoming,
+            Start::Streaming,
+            &mut spinner
+        ));
+        assert_eq!(picker.total(), 1 + FRAME_CANDIDATES);
+        assert!(!receive(
+            &mut picker,
+            &mut incoming,
+            Start::Streaming,
+            &mut spinner
+        ));
+        assert_eq!(picker.total(), 2 + FRAME_CANDIDATES);
+
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx.send(b"b".to_vec()).unwrap();
+        });
+        receive(&mut picker, &mut incoming, Start::Sync, &mut spinner);
+        sender.join().unwrap();
+        assert_eq!(picker.total(), 3 + FRAME_CANDIDATES);
+        assert!(incoming.is_none());
+        assert_eq!(spinner.progress.processed, 3 + FRAME_CANDIDATES);
+    }
+
+    #[test]
+    fn parses_mouse() {
+        assert_eq!(
+            parse_input(b"\x1b[<0;3;10M\x1b[<65;1;1m"),
+            vec![
+                Input::Mouse {
+                    button: 0,
+                    col: 2,
+                    row: 9,
+                    press: true
+                },
+                Input::Mouse {
+                    button: 65,
+                    col: 0,
+                    row: 0,
+                    press: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_clicks_to_rows() {
+        let mut last_click = None;
+        let opts = Options::default();
+        let click = |row| Input::Mouse {
+            button: 0,
+            col: 0,
+            row,
+            press: true,
+        };
+        assert_eq!(
+            event(click(7), 10, &opts, &mut last_click),
+            Some(Event::Click(0))
+        );
+        assert_eq!(
+            event(click(7), 10, &opts, &mut last_click),
+            Some(Event::DoubleClick(0))
+        );
+        assert_eq!(
+            event(click(0), 10, &opts, &mut last_click),
+            Some(Event::Click(7))
+        );
+        assert_eq!(event(click(8), 10, &opts, &mut last_click), None);
+        assert_eq!(event(click(9), 10, &opts, &mut last_click), None);
+
+        let opts = Options {
+            reverse: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            event(click(2), 10, &opts, &mut last_click),
+            Some(Event::Click(0))
+        );
+        assert_eq!(event(click(1), 10, &opts, &mut last_click), None);
+        assert_eq!(
+            event(Input::Up, 10, &opts, &mut last_click),
+            Some(Event::Prev)
+        );
+    }
+
+    #[test]
+    fn draws_status_line() {
+        let mut picker = Picker::new();
+        picker.push(b"foo");
+        picker.push(b"bar");
+        picker.handle(Event::Insert('f'));
+        let opts = Options::default();
+        let screen = String::from_utf8(draw(&picker, 4, 20, Some('|'), &opts)).unwrap();
+        assert!(screen.contains("  1/2 |\r\n"));
+        assert!(screen.ends_with("> f"));
+    }
+
+    #[test]
+    fn draws_marks() {
+        let mut picker = Picker::new();
+        picker.push(b"foo");
+        picker.push(b"bar");
+        picker.set_height(2);
+        picker.set_multi(Some(1));
+        picker.handle(Event::ToggleMark);
+        let screen = String::from_utf8(draw(&picker, 4, 40, None, &Options::default())).unwrap();
+        assert!(screen.contains("\x1b[1mfoo"));
+        assert!(screen.contains("  2/2 (1/1 marked, limit reached)\r\n"));
+    }
+
+    #[test]
+    fn draws_reversed() {
+        let mut picker = Picker::new();
+        picker.push(b"foo");
+        picker.push(b"bar");
+        picker.set_height(2);
+        let opts = Options {
+            reverse: true,
+            ..Options::default()
+        };
+        let screen = String::from_utf8(draw(&picker, 4, 20, None, &opts)).unwrap();
+        let lines: Vec<&str> = screen.split("\r\n").collect();
+        assert_eq!(lines[0], "\x1b[H\x1b[2K> ");
+        assert_eq!(lines[1], "\x1b[2K  2/2");
+        assert!(lines[2].contains("foo"));
+        assert!(lines[3].contains("bar"));
+    }
+
+    #[test]
+    fn fits_long_candidates() {
+        let narrow = [1; 20];
+        assert_eq!(fit(&narrow[..5], &[], 10, 0), (0, 5));
+        assert_eq!(fit(&narrow, &[1], 10, 0), (0, 9));
+        // The match at 15 is shown with ellipses on both sides.
+        assert_eq!(fit(&narrow, &[15], 10, 0), (8, 16));
+        assert_eq!(fit(&narrow, &[2, 15], 10, 0), (2, 10));
+        assert_eq!(fit(&narrow, &[18], 10, 0), (11, 20));
+        assert_eq!(fit(&narrow, &[], 10, 4), (4, 12));
+        assert_eq!(fit(&narrow, &[], 10, 100), (11, 20));
+        assert_eq!(fit(&narrow, &[15], 10, -100), (0, 9));
+        // Wide characters take two columns each.
+        let wide = [2; 10];
+        assert_eq!(fit(&wide[..5], &[], 10, 0), (0, 5));
+        assert_eq!(fit(&wide, &[], 10, 0), (0, 4));
+        assert_eq!(fit(&wide, &[9], 10, 0), (6, 10));
+    }
+
+    #[test]
+    fn truncates_with_ellipsis() {
+        let mut out = Vec::new();
+        let sanitize = Sanitize::default();
+        draw_candidate(&mut out, b"abcdefghij", &[8], 6, 0, &sanitize);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\u{2026}fgh\x1b[33mi\x1b[39mj"
+        );
+
+        let mut out = Vec::new();
+        draw_candidate(&mut out, b"a\tb\x1b", &[2], 20, 0, &sanitize);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "a       \x1b[33mb\x1b[39m^["
+        );
+
+        let mut out = Vec::new();
+        draw_candidate(
+            &mut out,
+            "日本語のファイル".as_bytes(),
+            &[],
+            9,
+            0,
+            &sanitize,
+        );
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "日本語の\u{2026}");
+        assert_eq!(display_width(out.as_bytes()), 9);
+    }
+}
@@ -0,0 +1,159 @@
+//! Terminal column widths of candidates, for fitting them and their
+//! highlighted positions into a fixed number of columns.
+
+/// Characters taking no column: combining marks, zero width spaces and
+/// joiners, and variation selectors.
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036f),
+    (0x0483, 0x0489),
+    (0x0591, 0x05bd),
+    (0x0610, 0x061a),
+    (0x064b, 0x065f),
+    (0x1ab0, 0x1aff),
+    (0x1dc0, 0x1dff),
+    (0x200b, 0x200f),
+    (0x20d0, 0x20ff),
+    (0xfe00, 0xfe0f),
+    (0xfe20, 0xfe2f),
+    (0xe0100, 0xe01ef),
+];
+
+/// East Asian wide and fullwidth characters, and emoji, taking two columns.
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115f),
+    (0x2e80, 0x303e),
+    (0x3041, 0x33ff),
+    (0x3400, 0x4dbf),
+    (0x4e00, 0x9fff),
+    (0xa000, 0xa4cf),
+    (0xac00, 0xd7a3),
+    (0xf900, 0xfaff),
+    (0xfe30, 0xfe4f),
+    (0xff00, 0xff60),
+    (0xffe0, 0xffe6),
+    (0x1f300, 0x1f64f),
+    (0x1f900, 0x1f9ff),
+    (0x20000, 0x2fffd),
+    (0x30000, 0x3fffd),
+];
+
+fn in_table(table: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    table
+        .binary_search_by(|&(lo, hi)| {
+            if hi < c {
+                std::cmp::Ordering::Less
+            } else if lo > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Columns `c` takes on a terminal; control characters take none.
+pub fn char_width(c: char) -> usize {
+    if c.is_control() || in_table(ZERO_WIDTH, c) {
+        0
+    } else if in_table(WIDE, c) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Columns `text` takes on a terminal, invalid UTF-8 counting one column per
+/// U+FFFD it is shown as.
+pub fn display_width(text: &[u8]) -> usize {
+    String::from_utf8_lossy(text).chars().map(char_width).sum()
+}
+
+/// A candidate cut down to a number of columns, see [`truncate_to_width`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncated {
+    pub text: String,
+    /// The positions that are still shown, as byte offsets into `text`.
+    pub positions: Vec<usize>,
+    /// Columns `text` takes.
+    pub width: usize,
+}
+
+/// Cuts `text` down to at most `columns` columns, ending it with `…` when
+/// anything was cut, and maps `positions`, byte offsets into `text` such as
+/// from [`positions`](crate::positions), to offsets into the result. A wide
+/// character that doesn't fit whole is left out, as are zero width ones
+/// after the cut.
+pub fn truncate_to_width(text: &[u8], positions: &[usize], columns: usize) -> Truncated {
+    let mut out = Truncated {
+        text: String::new(),
+        positions: Vec::new(),
+        width: 0,
+    };
+    let fits = display_width(text) <= columns;
+    let budget = if fits {
+        columns
+    } else {
+        columns.saturating_sub(1)
+    };
+    let mut offset = 0;
+    for chunk in text.utf8_chunks() {
+        let valid = chunk.valid().char_indices().map(|(i, c)| (offset + i, c));
+        let invalid = (!chunk.invalid().is_empty())
+            .then_some((offset + chunk.valid().len(), char::REPLACEMENT_CHARACTER));
+        for (at, c) in valid.chain(invalid) {
+            let w = char_width(c);
+            if out.width + w > budget {
+                if columns > 0 {
+                    out.text.push('\u{2026}');
+                    out.width += 1;
+                }
+                return out;
+            }
+            if positions.contains(&at) {
+                out.positions.push(out.text.len());
+            }
+            out.text.push(c);
+            out.width += w;
+        }
+        offset += chunk.valid().len() + chunk.invalid().len();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_columns() {
+        assert_eq!(display_width(b"abc"), 3);
+        assert_eq!(display_width("日本".as_bytes()), 4);
+        assert_eq!(display_width("e\u{301}".as_bytes()), 1);
+        assert_eq!(display_width(b"a\xffb"), 3);
+    }
+
+    #[test]
+    fn truncates_to_columns() {
+        let fits = truncate_to_width(b"main.rs", &[0, 5], 7);
+        assert_eq!(fits.text, "main.rs");
+        assert_eq!(fits.positions, vec![0, 5]);
+
+        let cut = truncate_to_width(b"main.rs", &[0, 5], 5);
+        assert_eq!(cut.text, "main\u{2026}");
+        assert_eq!(cut.positions, vec![0]);
+        assert_eq!(cut.width, 5);
+
+        // 日 is 3 bytes and 2 columns; 本 doesn't fit in the remaining one.
+        let text = "a日本b".as_bytes();
+        let cut = truncate_to_width(text, &[1, 4, 7], 5);
+        assert_eq!(cut.text, "a日\u{2026}");
+        assert_eq!(cut.positions, vec![1]);
+        assert_eq!(cut.width, 4);
+
+        let cut = truncate_to_width(b"\xffab", &[1], 2);
+        assert_eq!(cut.text, "\u{fffd}\u{2026}");
+        assert!(cut.positions.is_empty());
+        assert_eq!(truncate_to_width(b"ab", &[], 0).text, "");
+    }
+}